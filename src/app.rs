@@ -1,17 +1,118 @@
-use crate::asset::{Asset, AssetEnum};
+use crate::asset::{Asset as _, AssetEnum, dropped_file_from_path, format_bytes, named_file};
+use crate::clipboard::{CopyMode, Paste};
+use crate::diagnostics::{DiagnosticsLog, Severity};
+use crate::dialog::OpenDialog;
+use crate::export;
+use crate::figure::{FigureEntry, FigureSettings, encode_figure, render_figure};
+use crate::flicker::Flicker;
+use crate::gallery;
+use crate::image::annotation;
+use crate::image::colormap::ScalarChannel;
+use crate::image::image::{ImageAsset, set_decode_budget};
+use crate::image::measure;
+use crate::image::pixels::Pixels;
 use crate::image::viewer::ImageViewerWidget;
+use crate::loader::Loader;
+use crate::metrics::{ImageMetrics, diff_image};
 use crate::model_viewer::ModelViewerWidget;
-use crate::selector::Selector;
-use crate::viewer::ViewerWidget;
+use crate::pairing::{Pairing, metrics_against_first};
+use crate::project::{PROJECT_EXTENSION, Project, ProjectAsset, is_project_file};
+use crate::registry::AssetRegistry;
+use crate::selector::{ItemAction, Selector};
+use crate::sequence::{self, FrameExport, FrameMetrics};
+use crate::timeline::{SERIES_COLORS, Timeline, TimelineAction};
+use crate::viewer::ViewerWidget as _;
+use crate::watcher::Watcher;
+use anyhow::Context as _;
 use egui_toast::{Toast, ToastOptions, Toasts};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+/// Recently opened files kept in the Open menu
+const MAX_RECENT_FILES: usize = 10;
+
+/// The session is persisted with eframe, assets are restored from their paths
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
 pub struct App {
+    #[serde(skip)]
     items: Vec<AssetEnum>,
+    #[serde(skip)]
+    registry: Arc<AssetRegistry>,
+    #[serde(skip)]
+    loader: Loader,
+    /// Loads the assets whose file changed again
+    #[serde(skip)]
+    reloader: Loader,
+    /// Loads of `reloader` for changed files, by the path they were queued for
+    #[serde(skip)]
+    file_reloads: Vec<(u64, PathBuf)>,
+    #[serde(skip)]
+    paste: Paste,
+    /// Number of images pasted so far, used to name them
+    #[serde(skip)]
+    pasted_count: usize,
+    copy_mode: CopyMode,
+    #[serde(skip)]
+    open_dialog: OpenDialog,
+    /// Files and projects opened by hand, most recent first
+    recent_files: Vec<PathBuf>,
+    /// Paths of the loaded assets, in order, only up to date when saved
+    asset_paths: Vec<PathBuf>,
+    /// Ids of the assets in `asset_paths`, which differ from their file names once renamed
+    asset_ids: Vec<String>,
     image_viewer: ImageViewerWidget,
+    #[serde(skip)]
     model_viewer: ModelViewerWidget,
     selector: Selector,
     sidebar_open: bool,
+    #[serde(skip)]
     help_open: bool,
+    #[serde(skip)]
+    roi_export_open: bool,
+    #[serde(skip)]
+    figure_open: bool,
+    /// Line profile and area statistics of the selected images
+    #[serde(skip)]
+    measure_open: bool,
+    #[serde(skip)]
+    flicker_open: bool,
+    #[serde(skip)]
+    project_open: bool,
+    #[serde(skip)]
+    watch_open: bool,
+    #[serde(skip)]
+    memory_open: bool,
+    #[serde(skip)]
+    log_open: bool,
+    /// Every asset as a contact sheet instead of the viewer
+    #[serde(skip)]
+    gallery_open: bool,
+    #[serde(skip)]
+    log: DiagnosticsLog,
+    /// Images that were not shown recently are freed above this many megabytes
+    memory_budget_mb: usize,
+    watcher: Watcher,
+    pairing: Pairing,
+    #[serde(skip)]
+    pairing_open: bool,
+    /// Open numbered files dropped or picked together as one sequence
+    group_sequences: bool,
+    timeline: Timeline,
+    #[serde(skip)]
+    frame_metrics: FrameMetrics,
+    #[serde(skip)]
+    frame_export: FrameExport,
+    #[serde(skip)]
+    flicker: Flicker,
+    figure_settings: FigureSettings,
+    /// Id of the image that differences and metrics are computed against
+    reference: Option<String>,
+    /// Folder exports are written to, unused on the web where files are downloaded
+    export_folder: String,
+    /// Project file the session is saved to and was last opened from
+    project_path: String,
+    #[serde(skip)]
     toasts: Toasts,
 }
 
@@ -19,20 +120,63 @@ impl Default for App {
     fn default() -> Self {
         Self {
             items: vec![],
+            registry: Arc::new(AssetRegistry::default()),
+            loader: Loader::default(),
+            reloader: Loader::default(),
+            file_reloads: vec![],
+            paste: Paste::default(),
+            pasted_count: 0,
+            copy_mode: CopyMode::default(),
+            open_dialog: OpenDialog::default(),
+            recent_files: vec![],
+            asset_paths: vec![],
+            asset_ids: vec![],
             toasts: Toasts::new()
                 .anchor(egui::Align2::RIGHT_BOTTOM, (10.0, 10.0))
                 .direction(egui::Direction::BottomUp),
             image_viewer: ImageViewerWidget::default(),
-            model_viewer: ModelViewerWidget::default(),
+            model_viewer: ModelViewerWidget,
             selector: Selector::new(),
             sidebar_open: false,
             help_open: false,
+            roi_export_open: false,
+            figure_open: false,
+            measure_open: false,
+            flicker_open: false,
+            project_open: false,
+            watch_open: false,
+            memory_open: false,
+            log_open: false,
+            gallery_open: false,
+            log: DiagnosticsLog::default(),
+            memory_budget_mb: if cfg!(target_arch = "wasm32") {
+                1024
+            } else {
+                4096
+            },
+            watcher: Watcher::default(),
+            pairing: Pairing::default(),
+            pairing_open: false,
+            group_sequences: false,
+            timeline: Timeline::default(),
+            frame_metrics: FrameMetrics::default(),
+            frame_export: FrameExport::default(),
+            flicker: Flicker::default(),
+            figure_settings: FigureSettings::default(),
+            reference: None,
+            export_folder: "exports".to_owned(),
+            project_path: format!("comparison.{PROJECT_EXTENSION}"),
         }
     }
 }
 
 impl App {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        Self::with_registry(cc, AssetRegistry::default())
+    }
+
+    /// Start with additional formats registered, e.g. in-house ones
+    pub fn with_registry(cc: &eframe::CreationContext<'_>, registry: AssetRegistry) -> Self {
         cc.egui_ctx.style_mut(|style| {
             // No dropshadows
             style.visuals.window_shadow = egui::epaint::Shadow::NONE;
@@ -44,10 +188,556 @@ impl App {
             );
         });
 
-        Default::default()
+        let mut app: Self = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+            .unwrap_or_default();
+        app.registry = Arc::new(registry);
+        app.loader = Loader::new(app.registry.clone());
+        app.reloader = Loader::new(app.registry.clone());
+        set_decode_budget(app.memory_budget_bytes() as u64);
+        app.restore_assets(&cc.egui_ctx);
+        if app.pairing.folders.len() >= 2 {
+            if let Err(e) = app.pairing.scan(&app.registry) {
+                log::warn!("Failed to pair the folders of the previous session: {e:#}");
+            }
+        }
+        app
+    }
+
+    /// Reload the assets of the previous session from disk
+    fn restore_assets(&mut self, ctx: &egui::Context) {
+        let ids = std::mem::take(&mut self.asset_ids);
+        for (i, path) in std::mem::take(&mut self.asset_paths).iter().enumerate() {
+            let file = dropped_file_from_path(path);
+            let label = ids.get(i).filter(|id| **id != file.name).cloned();
+            self.loader.load(ctx, file, label, false);
+        }
+    }
+
+    /// Add the assets that finished loading, keeping the order they were queued in
+    fn handle_loaded(&mut self) {
+        let loaded = self.loader.poll();
+        if loaded.is_empty() {
+            return;
+        }
+
+        for loaded in loaded {
+            for warning in &loaded.warnings {
+                self.log
+                    .push(Severity::Warning, Some(&loaded.name), warning);
+            }
+            match loaded.result {
+                Ok(asset) => {
+                    if loaded.select {
+                        self.selector.select(self.items.len());
+                        if let Some(path) = asset.get_file_path() {
+                            self.add_recent(path.to_path_buf());
+                        }
+                    }
+                    self.items.push(asset);
+                }
+                Err(e) => self.load_error("Failed to load", &loaded.name, &e),
+            }
+        }
+
+        // the selection of a session or project may refer to an asset that failed to load
+        if !self.loader.is_loading() {
+            self.selector.clamp(self.items.len());
+        }
+    }
+
+    /// Open files given on the command line, projects, assets or folders to pair
+    pub fn open_paths(&mut self, ctx: &egui::Context, paths: impl IntoIterator<Item = PathBuf>) {
+        let files = paths
+            .into_iter()
+            .map(|path| dropped_file_from_path(&path))
+            .collect();
+        self.open_files(ctx, files);
+    }
+
+    /// Open dropped or picked files, numbered ones as sequences if enabled
+    fn open_files(&mut self, ctx: &egui::Context, files: Vec<egui::DroppedFile>) {
+        let files = if self.group_sequences {
+            sequence::group_numbered(&files)
+        } else {
+            files
+        };
+        for file in files {
+            self.open_file(ctx, file);
+        }
+    }
+
+    /// Replace the session with the one saved in a project file
+    fn open_project(&mut self, ctx: &egui::Context, path: &Path) {
+        let project = std::fs::read(path)
+            .with_context(|| format!("Failed to read {}", path.display()))
+            .and_then(|bytes| Project::from_bytes(&bytes));
+        let mut project = match project {
+            Ok(project) => project,
+            Err(e) => {
+                self.load_error("Failed to open", &path.display().to_string(), &e);
+                return;
+            }
+        };
+
+        project.resolve(path.parent().unwrap_or(Path::new("")));
+        self.items.clear();
+        self.loader.cancel_all();
+        for project_asset in project.assets {
+            self.loader.load(
+                ctx,
+                dropped_file_from_path(&project_asset.path),
+                Some(project_asset.label),
+                false,
+            );
+        }
+        self.selector.select(project.selected_index);
+        self.reference = project.reference;
+        self.selector.set_grouping(project.grouping);
+        self.image_viewer = project.image_viewer;
+        self.pairing.shown = None;
+        self.project_path = path.display().to_string();
+        self.add_recent(path.to_path_buf());
+    }
+
+    fn add_recent(&mut self, path: PathBuf) {
+        self.recent_files.retain(|recent| *recent != path);
+        self.recent_files.insert(0, path);
+        self.recent_files.truncate(MAX_RECENT_FILES);
+    }
+
+    /// Show the file picker, for assets and, natively, projects
+    fn show_open_dialog(&mut self, ctx: &egui::Context) {
+        let mut extensions = self.registry.extensions();
+        if !cfg!(target_arch = "wasm32") {
+            extensions.push(PROJECT_EXTENSION);
+        }
+        if let Err(e) = self.open_dialog.open(ctx, &extensions) {
+            self.error(&format!("Failed to show the file picker: {e:#}"));
+        }
+    }
+
+    /// Open files picked in the file picker like dropped files
+    fn handle_picked_files(&mut self, ctx: &egui::Context) {
+        let files = self.open_dialog.poll();
+        if !files.is_empty() {
+            self.open_files(ctx, files);
+        }
+    }
+
+    fn show_open_menu(&mut self, ui: &mut egui::Ui) {
+        let ctx = ui.ctx().clone();
+        if ui.button("Open…").clicked() {
+            self.show_open_dialog(&ctx);
+        }
+        // files picked on the web have no path to open them from again
+        if cfg!(target_arch = "wasm32") {
+            return;
+        }
+        ui.checkbox(&mut self.group_sequences, "Numbered files as sequences")
+            .on_hover_text("Open frame_0001.png, frame_0002.png, … together as frame_####.png");
+
+        ui.separator();
+        if self.recent_files.is_empty() {
+            ui.weak("No recent files");
+            return;
+        }
+        let mut reopen = None;
+        for path in &self.recent_files {
+            let name = path.file_name().map_or_else(
+                || path.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            );
+            if ui
+                .button(name)
+                .on_hover_text(path.display().to_string())
+                .clicked()
+            {
+                reopen = Some(path.clone());
+            }
+        }
+        if ui.button("Clear recent").clicked() {
+            self.recent_files.clear();
+        }
+        if let Some(path) = reopen {
+            self.open_paths(&ctx, [path]);
+        }
+    }
+
+    /// Write the session to the project file, returns how many assets were left out
+    fn save_project(&self) -> anyhow::Result<usize> {
+        let mut path = PathBuf::from(&self.project_path);
+        if !is_project_file(&self.project_path) {
+            path.set_extension(PROJECT_EXTENSION);
+        }
+
+        let assets: Vec<ProjectAsset> = self
+            .items
+            .iter()
+            .filter_map(|item| {
+                item.get_file_path().map(|file_path| ProjectAsset {
+                    path: file_path.to_path_buf(),
+                    label: item.get_id().to_owned(),
+                })
+            })
+            .collect();
+        let skipped = self.items.len() - assets.len();
+
+        let mut project = Project {
+            assets,
+            selected_index: self.selector.selected_index,
+            reference: self.reference.clone(),
+            grouping: self.selector.grouping(),
+            image_viewer: self.image_viewer.clone(),
+            ..Default::default()
+        };
+        // a bare file name has an empty parent, which is the current directory
+        let absolute = std::path::absolute(&path).unwrap_or_else(|_| path.clone());
+        project.make_relative(absolute.parent().unwrap_or(Path::new(".")));
+
+        let bytes = project.to_bytes()?;
+        std::fs::write(&path, bytes)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(skipped)
+    }
+
+    fn show_project(&mut self, ctx: &egui::Context) {
+        let mut open = self.project_open;
+        let mut save = false;
+        let mut load = false;
+        egui::Window::new("Project")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label("Assets, labels, groups, the reference and view settings");
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut self.project_path);
+                });
+                ui.horizontal(|ui| {
+                    save = ui.button("Save").clicked();
+                    load = ui.button("Open").clicked();
+                });
+                ui.weak(format!(
+                    "Drop a .{PROJECT_EXTENSION} file or pass it on the command line to open it"
+                ));
+            });
+        self.project_open = open;
+
+        if save {
+            match self.save_project() {
+                Ok(0) => self.info(&format!("Saved {}", self.project_path)),
+                Ok(skipped) => self.warning(&format!(
+                    "Saved {}, {skipped} assets without a file were left out",
+                    self.project_path
+                )),
+                Err(e) => self.error(&format!("Failed to save project: {e:#}")),
+            }
+        }
+        if load {
+            let path = PathBuf::from(&self.project_path);
+            self.open_project(ctx, &path);
+        }
+    }
+
+    /// Reload assets whose files changed and load new files from the watched folders
+    fn handle_file_changes(&mut self, ctx: &egui::Context) {
+        let changes = self.watcher.poll(
+            ctx,
+            &self.registry,
+            self.items.iter().filter_map(|item| item.get_file_path()),
+        );
+
+        // reloaded in the background, once for all the assets of a file, and swapped in by
+        // `handle_reloaded`
+        for path in changes.modified {
+            let load = self
+                .reloader
+                .load(ctx, dropped_file_from_path(&path), None, false);
+            self.file_reloads.push((load, path));
+        }
+
+        self.open_paths(ctx, changes.created);
+    }
+
+    /// Swap in the assets loaded again after their file changed, keeping their view settings
+    fn handle_reloaded(&mut self) {
+        for loaded in self.reloader.poll() {
+            for warning in &loaded.warnings {
+                self.log
+                    .push(Severity::Warning, Some(&loaded.name), warning);
+            }
+            let redecoded = self.items.iter().position(|item| {
+                matches!(item, AssetEnum::Image(image_asset) if image_asset.is_redecoded_by(loaded.id))
+            });
+            let changed = self
+                .file_reloads
+                .iter()
+                .position(|(load, _)| *load == loaded.id)
+                .map(|index| self.file_reloads.remove(index).1);
+            // ids are not unique, the targets are found by the load or the changed file
+            let targets: Vec<usize> = match (redecoded, changed) {
+                (Some(index), _) => vec![index],
+                (None, Some(path)) => (0..self.items.len())
+                    .filter(|&i| self.items[i].get_file_path() == Some(path.as_path()))
+                    .collect(),
+                (None, None) => vec![],
+            };
+            let swapped = loaded.result.and_then(|reloaded| {
+                // closed while loading
+                let Some((&last, others)) = targets.split_last() else {
+                    return Ok(());
+                };
+                for &index in others {
+                    self.items[index].take_reloaded(reloaded.duplicate())?;
+                }
+                self.items[last].take_reloaded(reloaded)
+            });
+            if let Err(e) = swapped {
+                if let Some(AssetEnum::Image(image_asset)) =
+                    redecoded.and_then(|index| self.items.get_mut(index))
+                {
+                    image_asset.stop_redecode();
+                }
+                self.load_error("Failed to reload", &loaded.name, &e);
+            }
+        }
+    }
+
+    /// Pair the files of a folder with the other folders, the first set is opened once there are two
+    fn add_pair_folder(&mut self, ctx: &egui::Context, folder: PathBuf) {
+        self.pairing.add_folder(folder);
+        self.pairing_open = true;
+        if self.pairing.folders.len() < 2 {
+            return;
+        }
+        match self.pairing.scan(&self.registry) {
+            Ok(()) => self.open_pair_set(ctx, self.pairing.current),
+            Err(e) => self.error(&format!("Failed to pair the folders: {e:#}")),
+        }
+    }
+
+    /// Replace the assets with a set of paired files, named after their folders so that the
+    /// reference, flicker and groups carry over from set to set
+    fn open_pair_set(&mut self, ctx: &egui::Context, index: usize) {
+        let files = self.pairing.show_set(index);
+        if files.is_empty() {
+            return;
+        }
+        // replace the images of the previous set, other assets stay open
+        let labels = self.pairing.labels();
+        self.loader.cancel_labeled(&labels);
+        let in_pair = |item: &AssetEnum| labels.iter().any(|label| label == item.get_id());
+        let kept: Vec<usize> = (0..self.items.len())
+            .filter(|&i| !in_pair(&self.items[i]))
+            .collect();
+        self.selector.keep(&kept);
+        self.items.retain(|item| !in_pair(item));
+        for (i, (label, path)) in files.into_iter().enumerate() {
+            self.loader
+                .load(ctx, dropped_file_from_path(&path), Some(label), i == 0);
+        }
+        self.reference = labels.into_iter().next();
+    }
+
+    /// Measure the shown set against the first folder once its images loaded
+    fn update_pair_metrics(&mut self, ctx: &egui::Context) {
+        let Some(index) = self.pairing.needs_metrics() else {
+            return;
+        };
+        if self.loader.is_loading() || !self.redecode_all(ctx) {
+            return;
+        }
+        let labels = self.pairing.labels();
+        let images: Vec<Option<&Pixels>> = labels
+            .iter()
+            .map(|label| {
+                self.items.iter().find_map(|item| match item {
+                    AssetEnum::Image(image_asset) if image_asset.get_id() == label => {
+                        Some(&image_asset.pixels)
+                    }
+                    _ => None,
+                })
+            })
+            .collect();
+        // folders whose image failed to load or was closed have no metrics, the navigator
+        // shows them as not comparable
+        let metrics = metrics_against_first(&images);
+        self.pairing.set_metrics(index, metrics);
+    }
+
+    fn show_pairing(&mut self, ctx: &egui::Context) {
+        let mut open = self.pairing_open;
+        let mut picked = None;
+        egui::Window::new("Pairs")
+            .collapsible(false)
+            .resizable(true)
+            .open(&mut open)
+            .show(ctx, |ui| picked = self.pairing.show(ui, &self.registry));
+        self.pairing_open = open;
+        if let Some(index) = picked {
+            self.open_pair_set(ctx, index);
+        }
+    }
+
+    fn show_watch_settings(&mut self, ctx: &egui::Context) {
+        let mut open = self.watch_open;
+        egui::Window::new("Watch")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| self.watcher.show_settings(ui));
+        self.watch_open = open;
+    }
+
+    /// Convert images into the working space, from their chosen or detected color space.
+    /// Images shown as data keep their values as decoded. Images decoded again for it are
+    /// queued once shown
+    fn sync_color_spaces(&mut self) {
+        let viewer = &self.image_viewer;
+        let color = viewer.color();
+        let errors: Vec<(String, anyhow::Error)> = self
+            .items
+            .iter_mut()
+            .filter_map(|item| match item {
+                AssetEnum::Image(image_asset) => {
+                    let input = color.input(&image_asset.id, image_asset.detected_color_space());
+                    let working = if viewer.shows_data(image_asset) {
+                        input
+                    } else {
+                        color.working
+                    };
+                    image_asset
+                        .set_color_spaces(input, working)
+                        .err()
+                        .map(|e| (image_asset.id.clone(), e))
+                }
+                AssetEnum::Model(_) => None,
+            })
+            .collect();
+        for (id, error) in errors {
+            self.load_error("Failed to convert", &id, &error);
+        }
+    }
+
+    /// Queue the image at `index` to be decoded again if it was evicted or converted into other
+    /// color spaces, `true` once its pixels are there
+    fn redecode(&mut self, ctx: &egui::Context, index: usize) -> bool {
+        let Some(AssetEnum::Image(image_asset)) = self.items.get_mut(index) else {
+            return true;
+        };
+        let id = image_asset.get_id().to_owned();
+        match image_asset.redecode(ctx) {
+            Ok(Some(file)) => {
+                // ids are not unique, the result is swapped in by the id of the load
+                let load = self.reloader.load(ctx, file, Some(id), false);
+                image_asset.set_redecode_load(load);
+            }
+            Ok(None) => {}
+            Err(e) => self.load_error("Failed to decode", &id, &e),
+        }
+        match self.items.get(index) {
+            Some(AssetEnum::Image(image_asset)) => image_asset.is_decoded(),
+            _ => true,
+        }
+    }
+
+    /// Queue every evicted image to be decoded again, `true` once all of them are
+    fn redecode_all(&mut self, ctx: &egui::Context) -> bool {
+        let mut decoded = true;
+        for index in 0..self.items.len() {
+            decoded &= self.redecode(ctx, index);
+        }
+        decoded
+    }
+
+    /// Decode images that were evicted, before using the pixels of every image
+    fn ensure_images_decoded(&mut self) {
+        let errors: Vec<(String, anyhow::Error)> = self
+            .items
+            .iter_mut()
+            .filter_map(|item| match item {
+                AssetEnum::Image(image_asset) => image_asset
+                    .ensure_decoded()
+                    .err()
+                    .map(|e| (image_asset.get_id().to_owned(), e)),
+                AssetEnum::Model(_) => None,
+            })
+            .collect();
+        for (id, error) in errors {
+            self.load_error("Failed to decode", &id, &error);
+        }
+    }
+
+    fn memory_budget_bytes(&self) -> usize {
+        self.memory_budget_mb * 1_000_000
+    }
+
+    /// Free the least recently shown images until the memory budget is met
+    fn enforce_memory_budget(&mut self, ctx: &egui::Context) {
+        let budget = self.memory_budget_bytes();
+        // a single file may not take more than the whole budget either
+        set_decode_budget(budget as u64);
+        let mut total: usize = self.items.iter().map(|item| item.memory_usage()).sum();
+        if total <= budget {
+            return;
+        }
+
+        let pass = ctx.cumulative_pass_nr();
+        let mut images: Vec<&mut ImageAsset> = self
+            .items
+            .iter_mut()
+            .filter_map(|item| match item {
+                AssetEnum::Image(image_asset) if image_asset.last_shown() != pass => {
+                    Some(image_asset)
+                }
+                _ => None,
+            })
+            .collect();
+        images.sort_by_key(|image_asset| image_asset.last_shown());
+
+        for image_asset in images {
+            if total <= budget {
+                break;
+            }
+            let before = image_asset.memory_usage();
+            image_asset.evict();
+            total -= before - image_asset.memory_usage();
+        }
+    }
+
+    fn show_memory(&mut self, ctx: &egui::Context) {
+        let mut open = self.memory_open;
+        egui::Window::new("Memory")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let total: usize = self.items.iter().map(|item| item.memory_usage()).sum();
+                ui.label(format!(
+                    "{} used by {} assets",
+                    format_bytes(total),
+                    self.items.len()
+                ));
+                ui.add(
+                    egui::Slider::new(&mut self.memory_budget_mb, 128..=32_768)
+                        .logarithmic(true)
+                        .text("Budget")
+                        .suffix(" MB"),
+                );
+                ui.weak(
+                    "Images that were not shown recently are freed above the budget, \
+                     and decoded again from their file when needed. \
+                     Files that do not fit in it are not opened",
+                );
+            });
+        self.memory_open = open;
     }
 
     pub fn error(&mut self, message: &str) {
+        self.log.push(Severity::Error, None, message);
         let toast = Toast {
             kind: egui_toast::ToastKind::Error,
             text: egui::WidgetText::from(message),
@@ -57,6 +747,141 @@ impl App {
         self.toasts.add(toast);
     }
 
+    pub fn warning(&mut self, message: &str) {
+        self.log.push(Severity::Warning, None, message);
+        let toast = Toast {
+            kind: egui_toast::ToastKind::Warning,
+            text: egui::WidgetText::from(message),
+            options: ToastOptions::default().duration_in_seconds(3.0),
+            style: Default::default(),
+        };
+        self.toasts.add(toast);
+    }
+
+    /// Apply what was picked in the selector, `previous` is the selection before it was shown
+    fn handle_selector(&mut self, action: Option<ItemAction>, previous: &[usize]) {
+        match action {
+            Some(ItemAction::Rename(index, name)) => self.rename_asset(index, name),
+            Some(ItemAction::Duplicate(index)) => self.duplicate_asset(index),
+            Some(ItemAction::SetReference(index)) => {
+                self.reference = self.items.get(index).map(|item| item.get_id().to_owned());
+            }
+            None => {}
+        }
+
+        // assets removed in the selector are no longer flickered, loading ones are kept
+        let (items, loader) = (&self.items, &self.loader);
+        self.flicker.ids.retain(|id| {
+            items.iter().any(|item| item.get_id() == id)
+                || loader.pending_names().any(|name| name == id)
+        });
+
+        // a multi-selection is what flicker alternates between
+        let selected = self.selector.selected();
+        if selected.len() > 1 && selected != previous {
+            self.flicker.ids = self.selected_ids();
+        }
+    }
+
+    /// Ids of the selected assets, in list order
+    fn selected_ids(&self) -> Vec<String> {
+        self.selector
+            .selected()
+            .into_iter()
+            .filter_map(|i| self.items.get(i))
+            .map(|item| item.get_id().to_owned())
+            .collect()
+    }
+
+    /// Rename an asset and everything keyed by its name, names have to be unique
+    fn rename_asset(&mut self, index: usize, name: String) {
+        let taken = self
+            .items
+            .iter()
+            .enumerate()
+            .any(|(i, item)| i != index && item.get_id() == name);
+        if taken {
+            self.warning(&format!("Another asset is already named {name}"));
+            return;
+        }
+        let Some(item) = self.items.get_mut(index) else {
+            return;
+        };
+        let old_name = item.get_id().to_owned();
+        let old_key = annotation::key(item);
+        item.set_id(name.clone());
+        let new_key = annotation::key(item);
+        self.selector.rename(&old_name, &name);
+        for id in &mut self.flicker.ids {
+            if *id == old_name {
+                id.clone_from(&name);
+            }
+        }
+        self.image_viewer
+            .annotations_mut()
+            .rename(&old_key, &new_key);
+        self.image_viewer.color_mut().rename(&old_name, &name);
+        if self.reference.as_deref() == Some(old_name.as_str()) {
+            self.reference = Some(name);
+        }
+    }
+
+    /// Add a copy of an asset, e.g. to view it with other settings, and show it
+    fn duplicate_asset(&mut self, index: usize) {
+        let Some(item) = self.items.get(index) else {
+            return;
+        };
+        let original = item.get_id().to_owned();
+        let mut copy = item.duplicate();
+        let name = (1..)
+            .map(|n| match n {
+                1 => format!("{original} copy"),
+                n => format!("{original} copy {n}"),
+            })
+            .find(|name| !self.items.iter().any(|item| item.get_id() == name))
+            .unwrap_or_default();
+        self.selector.group_like(&name, &original);
+        self.image_viewer.color_mut().copy(&original, &name);
+        copy.set_id(name);
+        self.image_viewer
+            .annotations_mut()
+            .copy(&annotation::key(item), &annotation::key(&copy));
+        self.items.push(copy);
+        self.selector.select(self.items.len() - 1);
+    }
+
+    /// Toast the outermost error of a failed load, the log keeps the whole cause chain
+    fn load_error(&mut self, action: &str, file: &str, error: &anyhow::Error) {
+        self.log.push_error(Some(file), error);
+        let toast = Toast {
+            kind: egui_toast::ToastKind::Error,
+            text: egui::WidgetText::from(format!("{action} {file}: {error}, see the log")),
+            options: ToastOptions::default().duration_in_seconds(3.0),
+            style: Default::default(),
+        };
+        self.toasts.add(toast);
+    }
+
+    fn show_log(&mut self, ctx: &egui::Context) {
+        let mut open = self.log_open;
+        egui::Window::new("Log")
+            .resizable(true)
+            .default_width(420.0)
+            .open(&mut open)
+            .show(ctx, |ui| self.log.show(ui));
+        self.log_open = open;
+    }
+
+    pub fn info(&mut self, message: &str) {
+        let toast = Toast {
+            kind: egui_toast::ToastKind::Info,
+            text: egui::WidgetText::from(message),
+            options: ToastOptions::default().duration_in_seconds(3.0),
+            style: Default::default(),
+        };
+        self.toasts.add(toast);
+    }
+
     pub fn handle_file_drop(&mut self, ctx: &egui::Context) {
         // check if fies are dropped
         let is_dropped = ctx.input(|i| !i.raw.dropped_files.is_empty());
@@ -65,24 +890,153 @@ impl App {
         }
 
         let files = ctx.input(|i| i.raw.dropped_files.clone());
-        for file in files {
-            let loaded_asset = AssetEnum::from_dropped_file(ctx, &file);
+        self.open_files(ctx, files);
+    }
 
-            match loaded_asset {
-                Err(e) => {
-                    self.error(&format!(
-                        "Failed to load asset from file: {}. Error: {}",
-                        file.name, e
-                    ));
-                }
-                Ok(asset) => {
-                    self.items.push(asset);
-                    self.selector.selected_index = self.items.len() - 1;
+    /// Open a dropped or picked file, a project, an asset or a folder to pair
+    fn open_file(&mut self, ctx: &egui::Context, file: egui::DroppedFile) {
+        let file = named_file(file);
+        if let Some(folder) = file.path.as_ref().filter(|path| path.is_dir()) {
+            self.add_pair_folder(ctx, folder.clone());
+            return;
+        }
+        // the name is the last part of the path, if there is one
+        let is_project = file
+            .path
+            .as_deref()
+            .map_or_else(|| is_project_file(&file.name), is_project_file);
+        if is_project {
+            match &file.path {
+                Some(path) => self.open_project(ctx, path),
+                None => {
+                    self.error("Projects refer to files on disk, open them in the desktop app");
                 }
             }
+            return;
+        }
+
+        self.loader.load(ctx, file, None, true);
+    }
+
+    /// Queue pasted images as new assets, named `pasted_1.png`, `pasted_2.png`, …
+    fn handle_paste(&mut self, ctx: &egui::Context) {
+        let files = match self.paste.poll(ctx) {
+            Ok(files) => files,
+            Err(e) => {
+                self.error(&format!("Failed to paste: {e:#}"));
+                return;
+            }
+        };
+        for file in files {
+            let label = self.paste_label(&file.name);
+            self.loader.load(ctx, file, Some(label), true);
+        }
+    }
+
+    /// An id for a pasted file that no other asset has
+    fn paste_label(&mut self, name: &str) -> String {
+        let extension = Path::new(name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("png");
+        loop {
+            self.pasted_count += 1;
+            let label = format!("pasted_{}.{extension}", self.pasted_count);
+            let taken = self.items.iter().any(|item| item.get_id() == label)
+                || self.loader.pending_names().any(|name| name == label);
+            if !taken {
+                return label;
+            }
+        }
+    }
+
+    /// Copy the selected image to the clipboard on Ctrl+C, unless a text field has focus
+    fn handle_copy(&mut self, ctx: &egui::Context) {
+        let copy = ctx.input(|i| i.events.contains(&egui::Event::Copy));
+        if !copy || ctx.wants_keyboard_input() {
+            return;
+        }
+        self.copy_image(ctx);
+    }
+
+    fn copy_image(&mut self, ctx: &egui::Context) {
+        match self.view_image() {
+            Ok(image) => {
+                let [width, height] = image.size;
+                ctx.copy_image(image);
+                self.info(&format!(
+                    "Copied {} {width}x{height}",
+                    self.copy_mode.label().to_lowercase()
+                ));
+            }
+            Err(e) => self.error(&format!("Failed to copy: {e:#}")),
         }
     }
 
+    /// Save the selected image as picked by the copy mode, natively where the user picks
+    fn save_image_as(&mut self) {
+        let Some(item) = self.items.get(self.selector.selected_index) else {
+            return;
+        };
+        let id = item.get_id().to_owned();
+        let name = export::export_name(&id, item.get_file_path(), self.copy_mode.suffix(), "png");
+        let saved = self.view_image().and_then(|image| {
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let Some(path) = crate::dialog::save_path(&name, export::SAVE_EXTENSIONS) else {
+                    return Ok(None);
+                };
+                let extension = path
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .unwrap_or("png");
+                let bytes = export::encode_image(&image, extension)?;
+                std::fs::write(&path, bytes)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                Ok(Some(path.display().to_string()))
+            }
+            #[cfg(target_arch = "wasm32")]
+            {
+                let bytes = export::encode_image(&image, "png")?;
+                export::save_file(&self.export_folder, &name, &bytes).map(Some)
+            }
+        });
+        match saved {
+            Ok(Some(path)) => self.info(&format!("Saved {path}")),
+            Ok(None) => {}
+            Err(e) => self.error(&format!("Failed to save {id}: {e:#}")),
+        }
+    }
+
+    /// The selected image as picked by the copy mode
+    fn view_image(&mut self) -> anyhow::Result<egui::ColorImage> {
+        let index = self.selector.selected_index;
+        if self.copy_mode == CopyMode::Diff {
+            self.ensure_images_decoded();
+            let Some(AssetEnum::Image(image_asset)) = self.items.get(index) else {
+                anyhow::bail!("Only images can be copied");
+            };
+            let reference = self
+                .reference_image()
+                .context("Pick a reference in the Figure window to copy differences")?;
+            return diff_image(
+                &image_asset.pixels,
+                reference,
+                self.figure_settings.diff_gain,
+            )
+            .context("The image and the reference differ in size");
+        }
+
+        let Some(AssetEnum::Image(image_asset)) = self.items.get_mut(index) else {
+            anyhow::bail!("Only images can be copied");
+        };
+        image_asset.ensure_decoded()?;
+        Ok(match self.copy_mode {
+            CopyMode::Displayed => self.image_viewer.displayed_image(image_asset),
+            CopyMode::Raw | CopyMode::Diff => image_asset.pixels.quantize(),
+        })
+    }
+
     pub fn show_drop_overlay(&mut self, ctx: &egui::Context, ui: &egui::Ui) {
         let hovering = ctx.input(|i| !i.raw.hovered_files.is_empty());
         if !hovering {
@@ -113,26 +1067,30 @@ impl App {
     }
 
     pub fn show_viewer(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
-        // make info window
         let spacing = 8.0;
         let panel_rect = ui.max_rect();
-        let window = egui::Window::new(egui::RichText::new("Info").size(12.0))
-            .collapsible(true)
-            .resizable(false)
-            .pivot(egui::Align2::RIGHT_TOP)
-            .fixed_pos(egui::pos2(
-                panel_rect.right() - spacing,
-                panel_rect.top() + spacing,
-            ))
-            .default_width(200.0);
+        let window = info_window(panel_rect, spacing);
 
-        // get selected asset
-        let asset_opt = self.items.get_mut(self.selector.selected_index);
+        // get selected asset, or the one shown by flicker
+        let flicker_index = self
+            .flicker
+            .current(ctx)
+            .and_then(|id| self.items.iter().position(|item| item.get_id() == id));
+        let shown = flicker_index.unwrap_or(self.selector.selected_index);
+        let frame_count = self.show_timeline_frame(ctx, shown);
+        // pixels are decoded again in the background, the previous ones stay shown until then
+        self.redecode(ctx, shown);
+        if matches!(self.items.get(shown), Some(AssetEnum::Image(image_asset)) if image_asset.is_evicted())
+        {
+            ui.centered_and_justified(|ui| ui.spinner());
+            return;
+        }
+        let asset_opt = self.items.get_mut(shown);
 
         // handle case of no asset
         let Some(asset) = asset_opt else {
             ui.centered_and_justified(|ui| {
-                ui.label(egui::RichText::new("📁 Drop Files to view").size(18.0));
+                ui.label(egui::RichText::new("📁 Drop files or press Ctrl+O to view").size(18.0));
             });
             return;
         };
@@ -147,18 +1105,667 @@ impl App {
             }
         }
 
+        // name the asset shown by flicker
+        if flicker_index.is_some() {
+            let text_pos = panel_rect.left_top() + egui::vec2(spacing, spacing);
+            paint_label(ui.painter(), text_pos, asset.get_id());
+        }
+
+        let mut timeline_action = None;
+        if frame_count > 1 {
+            let sequence = match asset {
+                AssetEnum::Image(image_asset) => image_asset.sequence(),
+                AssetEnum::Model(_) => None,
+            };
+            timeline_action = self.timeline.show(
+                ui,
+                panel_rect,
+                frame_count,
+                sequence,
+                &self.frame_metrics,
+                &self.frame_export,
+            );
+        }
+
         // show info window
+        let mut copy = false;
+        let mut save = false;
+        let mut export_annotations = None;
         window.show(ctx, |ui| match asset {
             AssetEnum::Image(image_asset) => {
-                self.image_viewer.show_info(ui);
+                self.image_viewer.show_info(ui, image_asset);
+                if self.image_viewer.roi().is_some() && ui.button("Export ROI…").clicked() {
+                    self.roi_export_open = true;
+                }
+                let measure = self.image_viewer.measure();
+                if (measure.line.is_some() || measure.area.is_some())
+                    && ui.button("Compare measurements…").clicked()
+                {
+                    self.measure_open = true;
+                }
+                let annotations = self.image_viewer.annotations().for_asset(image_asset);
+                export_annotations = show_annotation_export(ui, annotations);
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("copy mode")
+                        .selected_text(self.copy_mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in CopyMode::ALL {
+                                ui.selectable_value(&mut self.copy_mode, mode, mode.label());
+                            }
+                        });
+                    copy = ui.button("📋 Copy").on_hover_text("Ctrl+C").clicked();
+                    save = ui.button("💾 Save as…").clicked();
+                });
+            }
+            AssetEnum::Model(model) => {
+                self.model_viewer.show_info(ui, model);
             }
-            AssetEnum::Model(model) => {}
         });
+        if copy {
+            self.copy_image(ctx);
+        }
+        if save {
+            self.save_image_as();
+        }
+        if let Some(burned_in) = export_annotations {
+            self.export_annotations(shown, burned_in);
+        }
+        if let Some(action) = timeline_action {
+            self.handle_timeline_action(ctx, shown, action);
+        }
+    }
+
+    fn handle_timeline_action(
+        &mut self,
+        ctx: &egui::Context,
+        shown: usize,
+        action: TimelineAction,
+    ) {
+        match action {
+            TimelineAction::ComputeMetrics => self.compute_frame_metrics(ctx),
+            TimelineAction::ExportFrames => self.export_frames(ctx, shown),
+            TimelineAction::CancelExport => self.frame_export.cancel(),
+        }
+    }
+
+    /// Move the shown asset, if a sequence, to the frame of the timeline.
+    /// Returns its number of frames
+    fn show_timeline_frame(&mut self, ctx: &egui::Context, index: usize) -> usize {
+        let Some(AssetEnum::Image(image_asset)) = self.items.get_mut(index) else {
+            return 1;
+        };
+        let count = image_asset.frame_count();
+        if count > 1 {
+            let delays = image_asset.sequence().and_then(|s| s.delays.clone());
+            let frame = self.timeline.update(ctx, count, delays.as_deref());
+            if let Err(e) = image_asset.set_frame(ctx, frame) {
+                let id = image_asset.get_id().to_owned();
+                self.load_error("Failed to decode", &id, &e);
+            }
+        }
+        count
+    }
+
+    /// Move every sequence to the frame of the timeline while paused, so that figures,
+    /// copies and exports of all of them show the same frame
+    fn sync_frames(&mut self, ctx: &egui::Context) {
+        if self.timeline.playing {
+            return;
+        }
+        let frame = self.timeline.frame;
+        let errors: Vec<(String, anyhow::Error)> = self
+            .items
+            .iter_mut()
+            .filter_map(|item| match item {
+                AssetEnum::Image(image_asset) if image_asset.frame_count() > 1 => image_asset
+                    .set_frame(ctx, frame)
+                    .err()
+                    .map(|e| (image_asset.get_id().to_owned(), e)),
+                _ => None,
+            })
+            .collect();
+        for (id, error) in errors {
+            self.load_error("Failed to decode", &id, &error);
+        }
+    }
+
+    /// Save the annotations of an image drawn into it as shown, or as JSON
+    fn export_annotations(&mut self, index: usize, burned_in: bool) {
+        let Some(AssetEnum::Image(image_asset)) = self.items.get_mut(index) else {
+            return;
+        };
+        let id = image_asset.id.clone();
+        let stem = export::export_stem(&id, image_asset.get_file_path());
+        let annotations = self
+            .image_viewer
+            .annotations()
+            .for_asset(image_asset)
+            .to_vec();
+        let saved = if burned_in {
+            let mut image = self.image_viewer.displayed_image(image_asset);
+            annotation::burn_in(&mut image, &annotations)
+                .and_then(|()| export::encode_png(&image))
+                .and_then(|bytes| {
+                    let name = format!("{stem}_annotated.png");
+                    export::save_file(&self.export_folder, &name, &bytes)
+                })
+        } else {
+            annotation::to_json(&id, image_asset.image.size, &annotations).and_then(|bytes| {
+                let name = format!("{stem}_annotations.json");
+                export::save_file(&self.export_folder, &name, &bytes)
+            })
+        };
+        match saved {
+            Ok(path) => self.info(&format!("Exported {path}")),
+            Err(e) => self.error(&format!("Failed to export the annotations of {id}: {e:#}")),
+        }
+    }
+
+    /// Save every frame of a sequence or animation as numbered PNG files, e.g. `spin_0001.png`,
+    /// in the background
+    fn export_frames(&mut self, ctx: &egui::Context, index: usize) {
+        let Some(AssetEnum::Image(image_asset)) = self.items.get(index) else {
+            return;
+        };
+        let id = image_asset.get_id().to_owned();
+        let frames = image_asset.frames();
+        let working = image_asset.working_space();
+        let stem = export::export_stem(&id, image_asset.get_file_path()).replace('#', "");
+        let stem = match stem.trim_end_matches(['_', '-', '.']) {
+            "" => "frame",
+            stem => stem,
+        };
+        // every frame would be a separate download
+        if cfg!(target_arch = "wasm32") && frames.len() > sequence::MAX_WEB_EXPORT_FRAMES {
+            self.error(&format!(
+                "The web version saves up to {} frames, {id} has {}",
+                sequence::MAX_WEB_EXPORT_FRAMES,
+                frames.len()
+            ));
+            return;
+        }
+        self.frame_export
+            .start(ctx, id, frames, working, &self.export_folder, stem);
+    }
+
+    /// Report the frames saved by `export_frames` once done
+    fn poll_frame_export(&mut self) {
+        let id = self.frame_export.id.clone();
+        match self.frame_export.poll() {
+            Some(Ok(saved)) => self.info(&format!("Saved {saved} frames of {id}")),
+            Some(Err(e)) => self.error(&format!("Failed to save the frames of {id}: {e:#}")),
+            None => {}
+        }
+    }
+
+    /// Compare every frame of the sequences to the reference in the background
+    fn compute_frame_metrics(&mut self, ctx: &egui::Context) {
+        self.ensure_images_decoded();
+        let frames = |item: &AssetEnum| match item {
+            AssetEnum::Image(image_asset) => {
+                Some((image_asset.get_id().to_owned(), image_asset.frames()))
+            }
+            AssetEnum::Model(_) => None,
+        };
+        let reference = self.reference.as_deref();
+        let Some(reference_frames) = self
+            .items
+            .iter()
+            .filter(|item| Some(item.get_id()) == reference)
+            .find_map(frames)
+        else {
+            self.error("Pick a reference image to compare the sequences to");
+            return;
+        };
+        let sequences: Vec<_> = self
+            .items
+            .iter()
+            .filter(|item| Some(item.get_id()) != reference)
+            .filter(|item| matches!(item, AssetEnum::Image(image_asset) if image_asset.frame_count() > 1))
+            .filter_map(frames)
+            .collect();
+        if sequences.is_empty() {
+            self.warning("There are no other sequences to compare to the reference");
+            return;
+        }
+        self.frame_metrics.start(ctx, reference_frames, sequences);
+    }
+
+    /// Crop the region of interest out of every image, as separate files or a single montage
+    fn export_roi(&self, roi: egui::Rect, as_montage: bool) -> anyhow::Result<Vec<String>> {
+        let crops: Vec<(String, egui::ColorImage)> = self
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                AssetEnum::Image(image_asset) => {
+                    export::crop(&image_asset.image, roi).map(|crop| {
+                        let stem =
+                            export::export_stem(image_asset.get_id(), image_asset.get_file_path());
+                        (stem, crop)
+                    })
+                }
+                AssetEnum::Model(_) => None,
+            })
+            .collect();
+        anyhow::ensure!(!crops.is_empty(), "The ROI does not overlap any image");
+
+        if as_montage {
+            let images: Vec<egui::ColorImage> = crops.into_iter().map(|(_, crop)| crop).collect();
+            let montage = export::montage(&images, 8, egui::Color32::WHITE);
+            let bytes = export::encode_png(&montage)?;
+            let path = export::save_file(&self.export_folder, "roi_montage.png", &bytes)?;
+            return Ok(vec![path]);
+        }
+
+        crops
+            .iter()
+            .map(|(stem, crop)| {
+                let bytes = export::encode_png(crop)?;
+                export::save_file(&self.export_folder, &format!("{stem}_roi.png"), &bytes)
+            })
+            .collect()
+    }
+
+    fn show_roi_export(&mut self, ctx: &egui::Context) {
+        let Some(roi) = self.image_viewer.roi() else {
+            self.roi_export_open = false;
+            return;
+        };
+
+        let mut open = self.roi_export_open;
+        let mut export_montage = None;
+        egui::Window::new("Export ROI")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Region ({}, {}) {}x{} of every image",
+                    roi.min.x,
+                    roi.min.y,
+                    roi.width(),
+                    roi.height()
+                ));
+                if !cfg!(target_arch = "wasm32") {
+                    ui.horizontal(|ui| {
+                        ui.label("Folder:");
+                        ui.text_edit_singleline(&mut self.export_folder);
+                    });
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Save crops").clicked() {
+                        export_montage = Some(false);
+                    }
+                    if ui.button("Save montage").clicked() {
+                        export_montage = Some(true);
+                    }
+                });
+            });
+        self.roi_export_open = open;
+
+        if let Some(as_montage) = export_montage {
+            self.ensure_images_decoded();
+            match self.export_roi(roi, as_montage) {
+                Ok(paths) => self.info(&format!("Exported {}", paths.join(", "))),
+                Err(e) => self.error(&format!("Failed to export ROI: {e:#}")),
+            }
+        }
+    }
+
+    /// Line profile and area statistics of every selected image, to compare them
+    fn show_measurements(&mut self, ctx: &egui::Context) {
+        let mut open = self.measure_open;
+        let mut measured = vec![];
+        for index in self.selector.selected() {
+            // listed once decoded again
+            if !self.redecode(ctx, index) {
+                continue;
+            }
+            let Some(AssetEnum::Image(image_asset)) = self.items.get_mut(index) else {
+                continue;
+            };
+            // cached by the version of the pixels, areas over large images are slow to measure
+            let version = image_asset.pixels_version();
+            let measure = self.image_viewer.measure_mut();
+            let profile = measure.profile_of(version, &image_asset.pixels).to_vec();
+            let stats = measure.stats_of(version, &image_asset.pixels).cloned();
+            measured.push((image_asset.get_id().to_owned(), profile, stats));
+        }
+
+        egui::Window::new("Measurements")
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.weak("Select several images to compare them");
+                let measure = self.image_viewer.measure_mut();
+                if measure.line.is_some() {
+                    egui::ComboBox::from_label("Profile")
+                        .selected_text(measure.channel.long_label())
+                        .show_ui(ui, |ui| {
+                            for channel in ScalarChannel::ALL {
+                                ui.selectable_value(
+                                    &mut measure.channel,
+                                    channel,
+                                    channel.long_label(),
+                                );
+                            }
+                        });
+                    let series: Vec<(String, Vec<[f32; 4]>)> = measured
+                        .iter()
+                        .map(|(id, profile, _)| (id.clone(), profile.clone()))
+                        .collect();
+                    measure::show_profile(ui, measure.channel, &series, &SERIES_COLORS);
+                }
+                for (i, (id, _, stats)) in measured.iter().enumerate() {
+                    if let Some(stats) = stats {
+                        ui.separator();
+                        ui.colored_label(SERIES_COLORS[i % SERIES_COLORS.len()], id);
+                        measure::show_stats(ui, stats);
+                    }
+                }
+            });
+        self.measure_open = open;
+    }
+
+    fn reference_image(&self) -> Option<&Pixels> {
+        let reference = self.reference.as_deref()?;
+        self.items.iter().find_map(|item| match item {
+            AssetEnum::Image(image_asset) if image_asset.get_id() == reference => {
+                Some(&image_asset.pixels)
+            }
+            _ => None,
+        })
+    }
+
+    /// Render the comparison of the images off-screen and save it,
+    /// only the selected ones when more than one is selected
+    fn export_figure(&self) -> anyhow::Result<String> {
+        let settings = &self.figure_settings;
+        let reference = self.reference_image();
+        let selected = self.selector.selected();
+
+        // compared in the working space, shown in sRGB
+        let entries: Vec<FigureEntry<'_>> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| selected.len() < 2 || selected.contains(i))
+            .filter_map(|(_, item)| match item {
+                AssetEnum::Image(image_asset) => Some(image_asset),
+                AssetEnum::Model(_) => None,
+            })
+            .map(|image_asset| {
+                let is_reference = self.reference.as_deref() == Some(image_asset.get_id());
+                let compare_to = reference.filter(|_| !is_reference);
+                FigureEntry {
+                    label: image_asset.get_id(),
+                    image: &image_asset.image,
+                    is_reference,
+                    diff: compare_to
+                        .filter(|_| settings.diffs)
+                        .and_then(|r| diff_image(&image_asset.pixels, r, settings.diff_gain)),
+                    metrics: compare_to.and_then(|r| ImageMetrics::compute(&image_asset.pixels, r)),
+                }
+            })
+            .collect();
+
+        let figure = render_figure(&entries, self.image_viewer.roi(), settings)?;
+        let bytes = encode_figure(&figure, settings)?;
+        let name = format!("figure.{}", settings.format.extension());
+        export::save_file(&self.export_folder, &name, &bytes)
+    }
+
+    fn show_figure_export(&mut self, ctx: &egui::Context) {
+        let mut open = self.figure_open;
+        let mut export = false;
+        egui::Window::new("Export figure")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::ComboBox::from_label("Reference")
+                    .selected_text(self.reference.as_deref().unwrap_or("None"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.reference, None, "None");
+                        for item in &self.items {
+                            if let AssetEnum::Image(image_asset) = item {
+                                let id = image_asset.get_id();
+                                ui.selectable_value(&mut self.reference, Some(id.to_owned()), id);
+                            }
+                        }
+                    });
+                if self.reference.is_none() {
+                    ui.weak("Pick a reference to show differences and metrics");
+                }
+
+                self.figure_settings.show_settings(ui);
+                let selected = self.selector.selected().len();
+                if selected > 1 {
+                    ui.weak(format!("Only the {selected} selected images are exported"));
+                }
+
+                if !cfg!(target_arch = "wasm32") {
+                    ui.horizontal(|ui| {
+                        ui.label("Folder:");
+                        ui.text_edit_singleline(&mut self.export_folder);
+                    });
+                }
+                export = ui.button("Export").clicked();
+            });
+        self.figure_open = open;
+
+        if export {
+            self.ensure_images_decoded();
+            match self.export_figure() {
+                Ok(path) => self.info(&format!("Exported {path}")),
+                Err(e) => self.error(&format!("Failed to export figure: {e:#}")),
+            }
+        }
+    }
+
+    fn show_flicker_settings(&mut self, ctx: &egui::Context) {
+        let mut open = self.flicker_open;
+        egui::Window::new("Flicker")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let ids = self.items.iter().map(|item| item.get_id());
+                self.flicker.show_settings(ui, ids);
+            });
+        self.flicker_open = open;
+    }
+
+    fn show_help(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Help")
+            .collapsible(false)
+            .resizable(true)
+            .open(&mut self.help_open)
+            .show(ctx, |ui| {
+                ui.label("📁 Drop files into the window or open them from the Open menu");
+                ui.separator();
+                ui.heading("Keyboard Shortcuts");
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("S").monospace().strong());
+                    ui.label("Toggle between sidebar and bottom bar");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("?").monospace().strong());
+                    ui.label("Toggle help");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("←/→").monospace().strong());
+                    ui.label("Switch between assets");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("Ctrl/Shift+click").monospace().strong());
+                    ui.label("Select several assets, for flicker and the figure");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("Drag").monospace().strong());
+                    ui.label("Reorder assets, or move them onto a group header");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("Right click").monospace().strong());
+                    ui.label("Rename, duplicate, group, remove or set as reference");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("G").monospace().strong());
+                    ui.label("Toggle the gallery of all assets, hover a tile for details");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("F").monospace().strong());
+                    ui.label("Toggle flicker between the assets picked in the Flicker window");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("Space").monospace().strong());
+                    ui.label("Hold to show the next asset in hold flicker mode");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("Ctrl+O").monospace().strong());
+                    ui.label("Open files, recent ones are in the Open menu");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("Ctrl+V").monospace().strong());
+                    ui.label("Paste an image from the clipboard as a new asset");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("Ctrl+C").monospace().strong());
+                    ui.label("Copy the selected image, as picked in the Info window");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("K").monospace().strong());
+                    ui.label("Play or pause sequences, videos and animations in sync");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(", / .").monospace().strong());
+                    ui.label("Previous or next frame");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("PgUp/PgDn").monospace().strong());
+                    ui.label("Step through the sets of the folders paired in the Pairs window");
+                });
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("Esc").monospace().strong());
+                    ui.label("Cancel loading, middle click a loading entry to cancel only it");
+                });
+                self.image_viewer.show_help(ui);
+            });
+    }
+
+    /// Global shortcuts, letter keys are ignored while typing into a text field
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::S)) {
+            self.sidebar_open = !self.sidebar_open;
+        }
+
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::O)) {
+            self.show_open_dialog(ctx);
+        }
+
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::Questionmark)) {
+            self.help_open = !self.help_open;
+        }
+
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::G)) {
+            self.gallery_open = !self.gallery_open;
+        }
+
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::F)) {
+            self.flicker.toggle(ctx);
+        }
+
+        if !ctx.wants_keyboard_input() {
+            if ctx.input(|i| i.key_pressed(egui::Key::K)) {
+                self.timeline.toggle_playing(ctx);
+            }
+            if ctx.input(|i| i.key_pressed(egui::Key::Comma)) {
+                self.timeline.step(-1);
+            }
+            if ctx.input(|i| i.key_pressed(egui::Key::Period)) {
+                self.timeline.step(1);
+            }
+        }
+
+        let sets = self.pairing.sets.len();
+        let current = self.pairing.current;
+        if !ctx.wants_keyboard_input() {
+            if ctx.input(|i| i.key_pressed(egui::Key::PageDown)) && current + 1 < sets {
+                self.open_pair_set(ctx, current + 1);
+            }
+            if ctx.input(|i| i.key_pressed(egui::Key::PageUp)) && current > 0 && sets > 0 {
+                self.open_pair_set(ctx, current - 1);
+            }
+        }
+
+        if self.loader.is_loading() && ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.loader.cancel_all();
+        }
+    }
+
+    /// The windows toggled in the footer
+    fn show_windows(&mut self, ctx: &egui::Context) {
+        if self.help_open {
+            self.show_help(ctx);
+        }
+
+        if self.roi_export_open {
+            self.show_roi_export(ctx);
+        }
+
+        if self.figure_open {
+            self.show_figure_export(ctx);
+        }
+
+        if self.measure_open {
+            self.show_measurements(ctx);
+        }
+
+        if self.flicker_open {
+            self.show_flicker_settings(ctx);
+        }
+
+        if self.project_open {
+            self.show_project(ctx);
+        }
+
+        if self.watch_open {
+            self.show_watch_settings(ctx);
+        }
+
+        if self.pairing_open {
+            self.show_pairing(ctx);
+        }
+
+        if self.memory_open {
+            self.show_memory(ctx);
+        }
+
+        if self.log_open {
+            self.show_log(ctx);
+        }
     }
 
     pub fn show_footer(&mut self, ui: &mut egui::Ui) {
         ui.horizontal(|ui| {
+            ui.menu_button("Open", |ui| self.show_open_menu(ui));
             ui.toggle_value(&mut self.help_open, "Help");
+            ui.toggle_value(&mut self.gallery_open, "Gallery");
+            ui.toggle_value(&mut self.selector.thumbnails, "Thumbnails");
+            ui.toggle_value(&mut self.figure_open, "Figure");
+            ui.toggle_value(&mut self.flicker_open, "Flicker");
+            if !cfg!(target_arch = "wasm32") {
+                ui.toggle_value(&mut self.project_open, "Project");
+                ui.toggle_value(&mut self.watch_open, "Watch");
+                ui.toggle_value(&mut self.pairing_open, "Pairs");
+            }
+            ui.toggle_value(&mut self.memory_open, "Memory");
+            let log_label = match self.log.len() {
+                0 => "Log".to_owned(),
+                n => format!("Log ({n})"),
+            };
+            ui.toggle_value(&mut self.log_open, log_label);
 
             ui.label(egui::RichText::new("v0.1.0").small());
             ui.hyperlink_to(
@@ -169,44 +1776,98 @@ impl App {
     }
 }
 
+/// Window with the details of the shown asset, in the top right corner of the panel
+fn info_window(panel_rect: egui::Rect, spacing: f32) -> egui::Window<'static> {
+    egui::Window::new(egui::RichText::new("Info").size(12.0))
+        .collapsible(true)
+        .resizable(false)
+        .pivot(egui::Align2::RIGHT_TOP)
+        .fixed_pos(egui::pos2(
+            panel_rect.right() - spacing,
+            panel_rect.top() + spacing,
+        ))
+        .default_width(200.0)
+}
+
+/// Name on a dark background with its top left corner at `pos`
+fn paint_label(painter: &egui::Painter, pos: egui::Pos2, text: &str) {
+    let galley = painter.layout_no_wrap(
+        text.to_owned(),
+        egui::FontId::proportional(16.0),
+        egui::Color32::WHITE,
+    );
+    let background = egui::Rect::from_min_size(pos, galley.size()).expand(4.0);
+    painter.rect_filled(background, 4.0, egui::Color32::from_black_alpha(160));
+    painter.galley(pos, galley, egui::Color32::WHITE);
+}
+
+/// Buttons to export the annotations of an image if it has any, `Some(true)` to burn them in
+fn show_annotation_export(
+    ui: &mut egui::Ui,
+    annotations: &[annotation::Annotation],
+) -> Option<bool> {
+    if annotations.is_empty() {
+        return None;
+    }
+    ui.horizontal(|ui| {
+        ui.label("Export annotations:");
+        let burned_in = ui.button("Burned in").clicked();
+        let json = ui.button("JSON").clicked();
+        burned_in.then_some(true).or(json.then_some(false))
+    })
+    .inner
+}
+
+/// Memory use of an asset in the selector, and whether it is the reference
+fn item_detail(item: &AssetEnum, reference: Option<&str>) -> String {
+    let memory = format_bytes(item.memory_usage());
+    if reference == Some(item.get_id()) {
+        format!("reference · {memory}")
+    } else {
+        memory
+    }
+}
+
 impl eframe::App for App {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        let kept: Vec<usize> = (0..self.items.len())
+            .filter(|&i| self.items[i].get_file_path().is_some())
+            .collect();
+        self.asset_paths = kept
+            .iter()
+            .filter_map(|&i| self.items[i].get_file_path().map(Path::to_path_buf))
+            .collect();
+        self.asset_ids = kept
+            .iter()
+            .map(|&i| self.items[i].get_id().to_owned())
+            .collect();
+        // pasted assets are not restored, the selection refers to the restored ones
+        let selector = self.selector.clone();
+        self.selector.keep(&kept);
+        eframe::set_value(storage, eframe::APP_KEY, self);
+        self.selector = selector;
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.handle_file_drop(ctx);
-
-        // handle input
-        if ctx.input(|i| i.key_pressed(egui::Key::S)) {
-            self.sidebar_open = !self.sidebar_open;
-        }
-
-        if ctx.input(|i| i.key_pressed(egui::Key::Questionmark)) {
-            self.help_open = !self.help_open;
+        self.handle_picked_files(ctx);
+        self.handle_paste(ctx);
+        self.handle_copy(ctx);
+        self.handle_loaded();
+        self.handle_reloaded();
+        self.sync_color_spaces();
+        self.sync_frames(ctx);
+        self.frame_metrics.poll();
+        self.poll_frame_export();
+        self.pairing
+            .poll_summary(ctx, self.image_viewer.color().working);
+        self.update_pair_metrics(ctx);
+        if !cfg!(target_arch = "wasm32") {
+            self.handle_file_changes(ctx);
         }
 
-        if self.help_open {
-            egui::Window::new("Help")
-                .collapsible(false)
-                .resizable(true)
-                .open(&mut self.help_open)
-                .show(ctx, |ui| {
-                    ui.label("📁 Drop files into the window to view");
-                    ui.separator();
-                    ui.heading("Keyboard Shortcuts");
-                    ui.add_space(8.0);
-                    ui.horizontal(|ui| {
-                        ui.label(egui::RichText::new("S").monospace().strong());
-                        ui.label("Toggle between sidebar and bottom bar");
-                    });
-                    ui.horizontal(|ui| {
-                        ui.label(egui::RichText::new("?").monospace().strong());
-                        ui.label("Toggle help");
-                    });
-                    ui.horizontal(|ui| {
-                        ui.label(egui::RichText::new("←/→").monospace().strong());
-                        ui.label("Switch between assets");
-                    });
-                    self.image_viewer.show_help(ui);
-                });
-        }
+        self.handle_shortcuts(ctx);
+        self.show_windows(ctx);
 
         egui::CentralPanel::default()
             .frame(egui::Frame {
@@ -215,7 +1876,15 @@ impl eframe::App for App {
                 ..Default::default()
             })
             .show(ctx, |ui| {
-                self.show_viewer(ui, ctx);
+                if self.gallery_open {
+                    let selected = self.selector.selected();
+                    if gallery::show(ui, &mut self.items, &mut self.selector) {
+                        self.gallery_open = false;
+                    }
+                    self.handle_selector(None, &selected);
+                } else {
+                    self.show_viewer(ui, ctx);
+                }
                 self.show_drop_overlay(ctx, ui);
             });
 
@@ -223,8 +1892,20 @@ impl eframe::App for App {
             .resizable(true)
             .show_animated(ctx, self.sidebar_open, |ui| {
                 // selector ui
-                self.selector
-                    .show(ui, &mut self.items, |item| &item.get_id());
+                let selected = self.selector.selected();
+                let reference = self.reference.as_deref();
+                let action = self.selector.show(
+                    ui,
+                    &mut self.items,
+                    |item| item.get_id(),
+                    |item| item_detail(item, reference),
+                    |item, ctx, hovered| item.thumbnail().texture(ctx, hovered),
+                );
+                self.handle_selector(action, &selected);
+                if let Some(i) = Selector::show_placeholders(ui, self.loader.pending_names(), false)
+                {
+                    self.loader.cancel(i);
+                }
                 // Push footer to bottom
                 ui.with_layout(egui::Layout::bottom_up(egui::Align::Center), |ui| {
                     ui.add_space(5.0);
@@ -235,14 +1916,27 @@ impl eframe::App for App {
 
         egui::TopBottomPanel::bottom("bottom_bar").show_animated(ctx, !self.sidebar_open, |ui| {
             ui.horizontal(|ui| {
-                self.selector
-                    .show_horizontal(ui, &mut self.items, |item| &item.get_id());
+                let selected = self.selector.selected();
+                let reference = self.reference.as_deref();
+                let action = self.selector.show_horizontal(
+                    ui,
+                    &mut self.items,
+                    |item| item.get_id(),
+                    |item| item_detail(item, reference),
+                    |item, ctx, hovered| item.thumbnail().texture(ctx, hovered),
+                );
+                self.handle_selector(action, &selected);
+                if let Some(i) = Selector::show_placeholders(ui, self.loader.pending_names(), true)
+                {
+                    self.loader.cancel(i);
+                }
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     self.show_footer(ui);
                 });
             });
         });
 
+        self.enforce_memory_budget(ctx);
         self.toasts.show(ctx);
     }
 }
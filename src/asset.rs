@@ -1,14 +1,139 @@
 use crate::image::image::ImageAsset;
 use crate::model_asset::MeshModel;
-use anyhow::{Context, Result, bail};
-use egui;
+use crate::thumbnail::Thumbnail;
+use anyhow::Result;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+/// Why an asset could not be loaded, attached to the `anyhow` error of a load
+#[derive(Debug)]
+pub enum LoadError {
+    /// No registered format matches the file
+    UnsupportedFormat { supported: String },
+    /// The format was recognized but decoding it failed, the cause is the decoder error
+    Decode { format: &'static str },
+    /// The file carries no data to load
+    MissingData(String),
+    /// Reading the file failed
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+impl LoadError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::UnsupportedFormat { .. } => "unsupported format",
+            Self::Decode { .. } => "decode failure",
+            Self::MissingData(_) => "missing data",
+            Self::Io { .. } => "I/O",
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat { supported } => {
+                write!(f, "Unsupported format, supported formats are {supported}")
+            }
+            Self::Decode { format } => write!(f, "Failed to decode as {format}"),
+            Self::MissingData(what) => write!(f, "{what}"),
+            Self::Io { path, source } if source.kind() == std::io::ErrorKind::NotFound => {
+                write!(f, "{} not found, was it moved or deleted?", path.display())
+            }
+            Self::Io { path, .. } => write!(f, "Failed to read {}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Assets are created from files by the loaders in [`crate::registry::AssetRegistry`]
 pub trait Asset {
-    fn from_dropped_file(ctx: &egui::Context, file: &egui::DroppedFile) -> Result<Self>
+    fn get_id(&self) -> &str;
+
+    /// Rename the asset, e.g. to the label it has in a project
+    fn set_id(&mut self, id: String);
+
+    /// Path the asset was loaded from, if it came from disk
+    fn get_file_path(&self) -> Option<&Path>;
+
+    /// Bytes held in RAM and on the GPU
+    fn memory_usage(&self) -> usize;
+
+    /// Take the contents of `reloaded`, this asset loaded again from its file, keeping the id
+    /// and settings of this one
+    fn take_reloaded(&mut self, reloaded: Self) -> Result<()>
     where
         Self: Sized;
 
-    fn get_id(&self) -> &str;
+    /// Preview shown in the selector and the gallery
+    fn thumbnail(&mut self) -> &mut Thumbnail;
+}
+
+/// Human readable size, e.g. `12.3 MB`
+pub fn format_bytes(bytes: usize) -> String {
+    let bytes = bytes as f64;
+    if bytes >= 1e9 {
+        format!("{:.1} GB", bytes / 1e9)
+    } else if bytes >= 1e6 {
+        format!("{:.1} MB", bytes / 1e6)
+    } else {
+        format!("{:.0} kB", bytes / 1e3)
+    }
+}
+
+/// A dropped file pointing to a file on disk, as if it were dropped natively
+pub fn dropped_file_from_path(path: &Path) -> egui::DroppedFile {
+    egui::DroppedFile {
+        path: Some(path.to_path_buf()),
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        ..Default::default()
+    }
+}
+
+/// A dropped file with its name filled in, native drops only carry a path
+pub fn named_file(mut file: egui::DroppedFile) -> egui::DroppedFile {
+    if file.name.is_empty() {
+        if let Some(name) = file.path.as_ref().and_then(|path| path.file_name()) {
+            file.name = name.to_string_lossy().into_owned();
+        }
+    }
+    file
+}
+
+/// Contents of a dropped file, native drops only carry a path
+pub fn file_bytes(file: &egui::DroppedFile) -> Result<Arc<[u8]>, LoadError> {
+    if let Some(bytes) = &file.bytes {
+        return Ok(bytes.clone());
+    }
+    let path = file
+        .path
+        .as_ref()
+        .ok_or_else(|| LoadError::MissingData("The file has neither contents nor a path".into()))?;
+    read_file(path)
+}
+
+pub fn read_file(path: &Path) -> Result<Arc<[u8]>, LoadError> {
+    std::fs::read(path)
+        .map(Into::into)
+        .map_err(|source| LoadError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
 }
 
 pub enum AssetEnum {
@@ -16,27 +141,66 @@ pub enum AssetEnum {
     Model(MeshModel),
 }
 
+impl AssetEnum {
+    /// A copy with the same pixels or mesh, textures and caches are built again
+    pub fn duplicate(&self) -> Self {
+        match self {
+            Self::Image(image_asset) => Self::Image(image_asset.duplicate()),
+            Self::Model(model_asset) => Self::Model(model_asset.clone()),
+        }
+    }
+}
+
 impl Asset for AssetEnum {
     fn get_id(&self) -> &str {
         match self {
-            AssetEnum::Image(image_asset) => &image_asset.get_id(),
-            AssetEnum::Model(model_asset) => &model_asset.get_id(),
+            Self::Image(image_asset) => image_asset.get_id(),
+            Self::Model(model_asset) => model_asset.get_id(),
         }
     }
 
-    fn from_dropped_file(ctx: &egui::Context, file: &egui::DroppedFile) -> Result<Self>
-    where
-        Self: Sized,
-    {
-        let filename = file.name.clone();
-        let extension = filename.rsplit('.').next().context("No File Extension")?;
-
-        match extension {
-            "png" | "jpg" | "jpeg" | "bmp" => {
-                Ok(AssetEnum::Image(ImageAsset::from_dropped_file(ctx, file)?))
+    fn set_id(&mut self, id: String) {
+        match self {
+            Self::Image(image_asset) => image_asset.set_id(id),
+            Self::Model(model_asset) => model_asset.set_id(id),
+        }
+    }
+
+    fn get_file_path(&self) -> Option<&Path> {
+        match self {
+            Self::Image(image_asset) => image_asset.get_file_path(),
+            Self::Model(model_asset) => model_asset.get_file_path(),
+        }
+    }
+
+    fn memory_usage(&self) -> usize {
+        match self {
+            Self::Image(image_asset) => image_asset.memory_usage(),
+            Self::Model(model_asset) => model_asset.memory_usage(),
+        }
+    }
+
+    fn take_reloaded(&mut self, reloaded: Self) -> Result<()> {
+        match (self, reloaded) {
+            (Self::Image(image_asset), Self::Image(reloaded)) => {
+                image_asset.take_reloaded(reloaded)
+            }
+            (Self::Model(model_asset), Self::Model(reloaded)) => {
+                model_asset.take_reloaded(reloaded)
+            }
+            // the file now holds another kind of asset, there are no settings to keep
+            (this, mut reloaded) => {
+                reloaded.set_id(this.get_id().to_owned());
+                *this = reloaded;
+                Ok(())
             }
-            "obj" | "glb" => Ok(AssetEnum::Model(MeshModel::from_dropped_file(ctx, file)?)),
-            s => bail!("Unsupported format {s}"),
+        }
+    }
+
+    fn thumbnail(&mut self) -> &mut Thumbnail {
+        match self {
+            Self::Image(image_asset) => image_asset.thumbnail(),
+            Self::Model(model_asset) => model_asset.thumbnail(),
         }
     }
 }
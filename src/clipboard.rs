@@ -0,0 +1,139 @@
+/// What Ctrl+C puts on the clipboard and Save as writes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum CopyMode {
+    /// The pixels as loaded
+    Raw,
+    /// The pixels as shown, after tonemapping or colormapping
+    #[default]
+    Displayed,
+    /// The difference to the reference image
+    Diff,
+}
+
+impl CopyMode {
+    pub const ALL: [Self; 3] = [Self::Raw, Self::Displayed, Self::Diff];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Raw => "Raw pixels",
+            Self::Displayed => "Displayed",
+            Self::Diff => "Difference",
+        }
+    }
+
+    /// Appended to the asset id when saving, e.g. `render_displayed.png`
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Self::Raw => "raw",
+            Self::Displayed => "displayed",
+            Self::Diff => "diff",
+        }
+    }
+}
+
+/// Images pasted into the window, read from the system clipboard on Ctrl+V
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+pub struct Paste {
+    /// Opened on the first paste
+    clipboard: Option<arboard::Clipboard>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Paste {
+    /// Pasted files since the last call. Every Ctrl+V reaches the app as a key release, also
+    /// when it pastes text, so a clipboard without an image is not an error
+    pub fn poll(&mut self, ctx: &egui::Context) -> anyhow::Result<Vec<egui::DroppedFile>> {
+        use anyhow::Context as _;
+
+        let pasted = ctx.input(|i| {
+            i.events.iter().any(|event| {
+                matches!(
+                    event,
+                    egui::Event::Key {
+                        key: egui::Key::V,
+                        pressed: false,
+                        modifiers,
+                        ..
+                    } if modifiers.command
+                )
+            })
+        });
+        if !pasted || ctx.wants_keyboard_input() {
+            return Ok(vec![]);
+        }
+
+        let clipboard = match &mut self.clipboard {
+            Some(clipboard) => clipboard,
+            None => self
+                .clipboard
+                .insert(arboard::Clipboard::new().context("Failed to open the clipboard")?),
+        };
+        let data = match clipboard.get_image() {
+            Ok(data) => data,
+            Err(arboard::Error::ContentNotAvailable) => return Ok(vec![]),
+            Err(e) => return Err(e).context("Failed to read an image from the clipboard"),
+        };
+        let image =
+            egui::ColorImage::from_rgba_unmultiplied([data.width, data.height], &data.bytes);
+        // encoded, so it goes through the same loading path as dropped files
+        let bytes = crate::export::encode_png(&image)?;
+        Ok(vec![egui::DroppedFile {
+            name: "pasted.png".to_owned(),
+            mime: "image/png".to_owned(),
+            bytes: Some(bytes.into()),
+            ..Default::default()
+        }])
+    }
+}
+
+/// Images pasted into the page, collected by a listener on the document
+#[cfg(target_arch = "wasm32")]
+#[derive(Default)]
+pub struct Paste {
+    files: std::rc::Rc<std::cell::RefCell<Vec<egui::DroppedFile>>>,
+    installed: bool,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Paste {
+    /// Pasted files since the last call, the listener is installed on the first call
+    pub fn poll(&mut self, ctx: &egui::Context) -> anyhow::Result<Vec<egui::DroppedFile>> {
+        if !self.installed {
+            self.installed = true;
+            self.install(ctx)?;
+        }
+        Ok(self.files.borrow_mut().drain(..).collect())
+    }
+
+    fn install(&self, ctx: &egui::Context) -> anyhow::Result<()> {
+        use anyhow::Context as _;
+        use eframe::wasm_bindgen::JsCast as _;
+        use eframe::wasm_bindgen::closure::Closure;
+
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .context("No document")?;
+        let files = self.files.clone();
+        let ctx = ctx.clone();
+        let listener = Closure::<dyn FnMut(web_sys::ClipboardEvent)>::new(
+            move |event: web_sys::ClipboardEvent| {
+                let Some(list) = event.clipboard_data().and_then(|data| data.files()) else {
+                    return;
+                };
+                for file in (0..list.length()).filter_map(|i| list.get(i)) {
+                    if !file.type_().starts_with("image/") {
+                        continue;
+                    }
+                    crate::dialog::read_file(&ctx, file, files.clone());
+                }
+            },
+        );
+        document
+            .add_event_listener_with_callback("paste", listener.as_ref().unchecked_ref())
+            .map_err(|e| anyhow::anyhow!("Failed to listen for pastes: {e:?}"))?;
+        // the listener lives as long as the page
+        listener.forget();
+        Ok(())
+    }
+}
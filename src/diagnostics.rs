@@ -0,0 +1,147 @@
+use crate::asset::LoadError;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+
+    fn color(self, visuals: &egui::Visuals) -> egui::Color32 {
+        match self {
+            Self::Warning => visuals.warn_fg_color,
+            Self::Error => visuals.error_fg_color,
+        }
+    }
+}
+
+/// An error or warning kept in the log
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Wall clock time, UTC
+    pub time: String,
+    pub file: Option<String>,
+    /// Kind of load error, if it was one
+    pub kind: Option<&'static str>,
+    pub message: String,
+    /// Underlying errors, outermost first
+    pub causes: Vec<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.time, self.severity.label())?;
+        if let Some(kind) = self.kind {
+            write!(f, " ({kind})")?;
+        }
+        if let Some(file) = &self.file {
+            write!(f, " {file}")?;
+        }
+        write!(f, ": {}", self.message)?;
+        for cause in &self.causes {
+            write!(f, "\n    caused by: {cause}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Current time of day as `HH:MM:SS`
+fn timestamp() -> String {
+    let seconds = web_time::SystemTime::now()
+        .duration_since(web_time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (hours, minutes, seconds) = ((seconds / 3600) % 24, (seconds / 60) % 60, seconds % 60);
+    format!("{hours:02}:{minutes:02}:{seconds:02}")
+}
+
+/// Every error and warning of the session, unlike toasts they stay until cleared
+#[derive(Default)]
+pub struct DiagnosticsLog {
+    entries: Vec<Diagnostic>,
+}
+
+impl DiagnosticsLog {
+    pub fn push(&mut self, severity: Severity, file: Option<&str>, message: &str) {
+        self.entries.push(Diagnostic {
+            severity,
+            time: timestamp(),
+            file: file.map(str::to_owned),
+            kind: None,
+            message: message.to_owned(),
+            causes: vec![],
+        });
+    }
+
+    /// Log an error with its cause chain, and the kind of load error it is
+    pub fn push_error(&mut self, file: Option<&str>, error: &anyhow::Error) {
+        let mut chain = error.chain().map(ToString::to_string);
+        self.entries.push(Diagnostic {
+            severity: Severity::Error,
+            time: timestamp(),
+            file: file.map(str::to_owned),
+            kind: error.downcast_ref::<LoadError>().map(LoadError::kind),
+            message: chain.next().unwrap_or_default(),
+            causes: chain.collect(),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Copy all").clicked() {
+                let text: Vec<String> = self.entries.iter().map(ToString::to_string).collect();
+                ui.ctx().copy_text(text.join("\n"));
+            }
+            if ui.button("Clear").clicked() {
+                self.entries.clear();
+            }
+        });
+        ui.separator();
+
+        if self.entries.is_empty() {
+            ui.weak("Nothing went wrong so far");
+            return;
+        }
+
+        egui::ScrollArea::vertical()
+            .max_height(400.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for (i, entry) in self.entries.iter().enumerate() {
+                    ui.push_id(i, |ui| Self::show_entry(ui, entry));
+                }
+            });
+    }
+
+    fn show_entry(ui: &mut egui::Ui, entry: &Diagnostic) {
+        ui.horizontal(|ui| {
+            ui.weak(&entry.time);
+            ui.colored_label(
+                entry.severity.color(ui.visuals()),
+                entry.kind.unwrap_or(entry.severity.label()),
+            );
+            if let Some(file) = &entry.file {
+                ui.strong(file);
+            }
+            if ui.small_button("📋").on_hover_text("Copy").clicked() {
+                ui.ctx().copy_text(entry.to_string());
+            }
+        });
+        ui.label(&entry.message);
+        for cause in &entry.causes {
+            ui.weak(format!("↳ {cause}"));
+        }
+        ui.separator();
+    }
+}
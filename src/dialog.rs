@@ -0,0 +1,119 @@
+/// Files picked in an Open dialog, handed out like dropped files
+#[derive(Default)]
+pub struct OpenDialog {
+    #[cfg(not(target_arch = "wasm32"))]
+    picked: Vec<egui::DroppedFile>,
+    #[cfg(target_arch = "wasm32")]
+    picked: std::rc::Rc<std::cell::RefCell<Vec<egui::DroppedFile>>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl OpenDialog {
+    /// Show the native file picker, filtered to `extensions`
+    #[expect(clippy::unnecessary_wraps)] // the same signature as the web picker, which can fail
+    pub fn open(&mut self, _ctx: &egui::Context, extensions: &[&str]) -> anyhow::Result<()> {
+        let paths = rfd::FileDialog::new()
+            .add_filter("Supported files", extensions)
+            .add_filter("All files", &["*"])
+            .pick_files()
+            .unwrap_or_default();
+        self.picked.extend(
+            paths
+                .iter()
+                .map(|path| crate::asset::dropped_file_from_path(path)),
+        );
+        Ok(())
+    }
+
+    /// Files picked since the last call
+    pub fn poll(&mut self) -> Vec<egui::DroppedFile> {
+        std::mem::take(&mut self.picked)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl OpenDialog {
+    /// Click a hidden `<input type=file>`, the picked files are read in the background
+    pub fn open(&self, ctx: &egui::Context, extensions: &[&str]) -> anyhow::Result<()> {
+        use anyhow::Context as _;
+        use eframe::wasm_bindgen::JsCast as _;
+        use eframe::wasm_bindgen::closure::Closure;
+
+        let js_error = |e: eframe::wasm_bindgen::JsValue| anyhow::anyhow!("{e:?}");
+
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .context("No document")?;
+        let input = document
+            .create_element("input")
+            .map_err(js_error)?
+            .dyn_into::<web_sys::HtmlInputElement>()
+            .map_err(|element| js_error(element.into()))?;
+        input.set_type("file");
+        input.set_multiple(true);
+        let accept: Vec<String> = extensions
+            .iter()
+            .map(|extension| format!(".{extension}"))
+            .collect();
+        input.set_accept(&accept.join(","));
+
+        let picked = self.picked.clone();
+        let ctx = ctx.clone();
+        let target = input.clone();
+        let listener = Closure::once(move || {
+            let Some(list) = target.files() else {
+                return;
+            };
+            for file in (0..list.length()).filter_map(|i| list.get(i)) {
+                read_file(&ctx, file, picked.clone());
+            }
+        });
+        input.set_onchange(Some(listener.as_ref().unchecked_ref()));
+        // one small closure is leaked per dialog, the input has no owner to drop it with
+        listener.forget();
+        input.click();
+        Ok(())
+    }
+
+    /// Files picked since the last call
+    pub fn poll(&self) -> Vec<egui::DroppedFile> {
+        self.picked.borrow_mut().drain(..).collect()
+    }
+}
+
+/// Read a file of the page in the background, it is pushed to `files` once read
+#[cfg(target_arch = "wasm32")]
+pub fn read_file(
+    ctx: &egui::Context,
+    file: web_sys::File,
+    files: std::rc::Rc<std::cell::RefCell<Vec<egui::DroppedFile>>>,
+) {
+    let ctx = ctx.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        let Ok(buffer) = wasm_bindgen_futures::JsFuture::from(file.array_buffer()).await else {
+            return;
+        };
+        files.borrow_mut().push(egui::DroppedFile {
+            name: file.name(),
+            mime: file.type_(),
+            bytes: Some(js_sys::Uint8Array::new(&buffer).to_vec().into()),
+            ..Default::default()
+        });
+        ctx.request_repaint();
+    });
+}
+
+/// Ask where to save `name`, `None` if cancelled
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_path(name: &str, extensions: &[&str]) -> Option<std::path::PathBuf> {
+    rfd::FileDialog::new()
+        .set_file_name(name)
+        .add_filter("Images", extensions)
+        .save_file()
+}
+
+/// Ask for a folder, `None` if cancelled
+#[cfg(not(target_arch = "wasm32"))]
+pub fn pick_folder() -> Option<std::path::PathBuf> {
+    rfd::FileDialog::new().pick_folder()
+}
@@ -0,0 +1,224 @@
+use anyhow::{Context as _, Result};
+use std::io::Cursor;
+
+/// Crop `roi`, given in pixel coordinates, out of the image. `None` if it lies outside
+pub fn crop(image: &egui::ColorImage, roi: egui::Rect) -> Option<egui::ColorImage> {
+    let [width, height] = image.size;
+    let x0 = roi.min.x.round().clamp(0.0, width as f32) as usize;
+    let y0 = roi.min.y.round().clamp(0.0, height as f32) as usize;
+    let x1 = roi.max.x.round().clamp(0.0, width as f32) as usize;
+    let y1 = roi.max.y.round().clamp(0.0, height as f32) as usize;
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+    Some(image.region_by_pixels([x0, y0], [x1 - x0, y1 - y0]))
+}
+
+/// Place images next to each other, top aligned, separated by `gap` pixels
+pub fn montage(
+    images: &[egui::ColorImage],
+    gap: usize,
+    background: egui::Color32,
+) -> egui::ColorImage {
+    let width = images.iter().map(|image| image.width()).sum::<usize>()
+        + gap * images.len().saturating_sub(1);
+    let height = images.iter().map(|image| image.height()).max().unwrap_or(0);
+
+    let mut out = egui::ColorImage::filled([width, height], background);
+    let mut x_offset = 0;
+    for image in images {
+        blit(&mut out, image, [x_offset, 0]);
+        x_offset += image.width() + gap;
+    }
+    out
+}
+
+/// Copy `image` into `target` with its top left corner at `pos`, clipping at the edges
+pub fn blit(target: &mut egui::ColorImage, image: &egui::ColorImage, pos: [usize; 2]) {
+    let [target_width, target_height] = target.size;
+    for y in 0..image.height().min(target_height.saturating_sub(pos[1])) {
+        for x in 0..image.width().min(target_width.saturating_sub(pos[0])) {
+            target[(pos[0] + x, pos[1] + y)] = image[(x, y)];
+        }
+    }
+}
+
+pub fn to_rgba_image(image: &egui::ColorImage) -> Result<image::RgbaImage> {
+    let rgba: Vec<u8> = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_srgba_unmultiplied())
+        .collect();
+    image::RgbaImage::from_raw(image.width() as u32, image.height() as u32, rgba)
+        .context("Invalid image size")
+}
+
+pub fn from_rgba_image(image: &image::RgbaImage) -> egui::ColorImage {
+    let size = [image.width() as usize, image.height() as usize];
+    egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw())
+}
+
+pub fn encode_png(image: &egui::ColorImage) -> Result<Vec<u8>> {
+    let buffer = to_rgba_image(image)?;
+    let mut bytes = Cursor::new(Vec::new());
+    buffer
+        .write_to(&mut bytes, image::ImageFormat::Png)
+        .context("Failed to encode PNG")?;
+    Ok(bytes.into_inner())
+}
+
+/// Extensions a processed image can be saved as
+pub const SAVE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "bmp", "tif", "tiff", "tga"];
+
+/// Encode in the format of the file extension, PNG when it is not one of `SAVE_EXTENSIONS`
+pub fn encode_image(image: &egui::ColorImage, extension: &str) -> Result<Vec<u8>> {
+    let format = image::ImageFormat::from_extension(extension)
+        .filter(|_| SAVE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+        .unwrap_or(image::ImageFormat::Png);
+    let buffer = image::DynamicImage::ImageRgba8(to_rgba_image(image)?);
+    // JPEG has no alpha channel
+    let buffer = match format {
+        image::ImageFormat::Jpeg => image::DynamicImage::ImageRgb8(buffer.to_rgb8()),
+        _ => buffer,
+    };
+    let mut bytes = Cursor::new(Vec::new());
+    buffer
+        .write_to(&mut bytes, format)
+        .with_context(|| format!("Failed to encode {format:?}"))?;
+    Ok(bytes.into_inner())
+}
+
+/// Write an exported file into `folder`, returns where it was written
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_file(folder: &str, name: &str, bytes: &[u8]) -> Result<String> {
+    let folder = std::path::Path::new(folder);
+    std::fs::create_dir_all(folder)
+        .with_context(|| format!("Failed to create {}", folder.display()))?;
+    let path = folder.join(name);
+    std::fs::write(&path, bytes).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path.display().to_string())
+}
+
+/// Offer an exported file as a browser download, `folder` is ignored on the web
+#[cfg(target_arch = "wasm32")]
+pub fn save_file(_folder: &str, name: &str, bytes: &[u8]) -> Result<String> {
+    use eframe::wasm_bindgen::JsCast as _;
+    use eframe::wasm_bindgen::closure::Closure;
+
+    /// Time the browser has to start the download before its URL is revoked, in milliseconds
+    const REVOKE_DELAY_MS: i32 = 60_000;
+
+    let js_error = |e: eframe::wasm_bindgen::JsValue| anyhow::anyhow!("{e:?}");
+
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    let blob = web_sys::Blob::new_with_u8_array_sequence(&parts).map_err(js_error)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(js_error)?;
+
+    let window = web_sys::window().context("No window")?;
+    let document = window.document().context("No document")?;
+    let anchor = document
+        .create_element("a")
+        .map_err(js_error)?
+        .dyn_into::<web_sys::HtmlAnchorElement>()
+        .map_err(|element| js_error(element.into()))?;
+    anchor.set_href(&url);
+    anchor.set_download(name);
+    anchor.click();
+
+    // some browsers start the download after `click` returns, the URL must outlive it
+    let revoke = Closure::once_into_js(move || {
+        if let Err(e) = web_sys::Url::revoke_object_url(&url) {
+            log::warn!("Failed to revoke the URL of a download: {e:?}");
+        }
+    });
+    window
+        .set_timeout_with_callback_and_timeout_and_arguments_0(
+            revoke.unchecked_ref(),
+            REVOKE_DELAY_MS,
+        )
+        .map_err(js_error)?;
+    Ok(name.to_owned())
+}
+
+/// Stem of the files exported from an asset, its id without the extension. Files on disk
+/// are prefixed with their folder, so same-named files from different folders, e.g. `gt` and
+/// `ours`, are not exported over each other
+pub fn export_stem(id: &str, path: Option<&std::path::Path>) -> String {
+    let stem = std::path::Path::new(id)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(id);
+    let folder = path
+        .and_then(|path| path.parent())
+        .and_then(|folder| folder.file_name())
+        .map(|folder| folder.to_string_lossy());
+    match folder {
+        Some(folder) => format!("{folder}_{stem}"),
+        None => stem.to_owned(),
+    }
+}
+
+/// File name for an export derived from an asset, e.g. `ours/render.png` -> `ours_render_roi.png`
+pub fn export_name(
+    id: &str,
+    path: Option<&std::path::Path>,
+    suffix: &str,
+    extension: &str,
+) -> String {
+    format!("{}_{suffix}.{extension}", export_stem(id, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn export_names_of_same_named_files_differ_by_folder() {
+        let gt = export_name(
+            "render.png",
+            Some(Path::new("/set/gt/render.png")),
+            "roi",
+            "png",
+        );
+        let ours = export_name(
+            "render.png",
+            Some(Path::new("/set/ours/render.png")),
+            "roi",
+            "png",
+        );
+        assert_eq!(gt, "gt_render_roi.png");
+        assert_eq!(ours, "ours_render_roi.png");
+    }
+
+    #[test]
+    fn export_names_of_pasted_images_use_the_id() {
+        assert_eq!(
+            export_name("pasted_1.png", None, "annotated", "png"),
+            "pasted_1_annotated.png"
+        );
+    }
+
+    #[test]
+    fn crop_clamps_to_the_image() {
+        let image = egui::ColorImage::filled([4, 3], egui::Color32::RED);
+        let roi = egui::Rect::from_min_max(egui::pos2(2.0, 1.0), egui::pos2(10.0, 10.0));
+        assert_eq!(crop(&image, roi).map(|crop| crop.size), Some([2, 2]));
+        let outside = egui::Rect::from_min_max(egui::pos2(5.0, 0.0), egui::pos2(8.0, 2.0));
+        assert!(crop(&image, outside).is_none());
+    }
+
+    #[test]
+    fn montage_places_images_side_by_side() {
+        let images = [
+            egui::ColorImage::filled([2, 2], egui::Color32::RED),
+            egui::ColorImage::filled([3, 1], egui::Color32::BLUE),
+        ];
+        let montage = montage(&images, 1, egui::Color32::WHITE);
+        assert_eq!(montage.size, [6, 2]);
+        assert_eq!(montage[(1, 1)], egui::Color32::RED);
+        assert_eq!(montage[(2, 0)], egui::Color32::WHITE);
+        assert_eq!(montage[(3, 0)], egui::Color32::BLUE);
+        assert_eq!(montage[(3, 1)], egui::Color32::WHITE);
+    }
+}
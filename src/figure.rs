@@ -0,0 +1,402 @@
+use crate::export::{self, blit};
+use crate::metrics::ImageMetrics;
+use ab_glyph::{Font as _, FontRef, PxScale, ScaleFont as _};
+use anyhow::{Context as _, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum FigureFormat {
+    Png,
+    Pdf,
+}
+
+impl FigureFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Pdf => "pdf",
+        }
+    }
+}
+
+/// Layout of an exported comparison figure
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct FigureSettings {
+    pub columns: usize,
+    /// Scale of the images relative to their native resolution
+    pub scale: f32,
+    pub labels: bool,
+    /// Show the region of interest magnified under every image
+    pub insets: bool,
+    /// Show the difference to the reference under every image
+    pub diffs: bool,
+    pub diff_gain: f32,
+    /// Caption every image with its metrics against the reference
+    pub metrics: bool,
+    pub font_size: f32,
+    pub format: FigureFormat,
+    /// Resolution of the PDF page, in pixels per inch
+    pub dpi: f32,
+}
+
+impl Default for FigureSettings {
+    fn default() -> Self {
+        Self {
+            columns: 4,
+            scale: 1.0,
+            labels: true,
+            insets: true,
+            diffs: false,
+            diff_gain: 4.0,
+            metrics: true,
+            font_size: 20.0,
+            format: FigureFormat::Png,
+            dpi: 300.0,
+        }
+    }
+}
+
+impl FigureSettings {
+    pub fn show_settings(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("figure_settings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Columns");
+                ui.add(egui::DragValue::new(&mut self.columns).range(1..=32));
+                ui.end_row();
+
+                ui.label("Scale");
+                ui.add(
+                    egui::DragValue::new(&mut self.scale)
+                        .range(0.05..=16.0)
+                        .speed(0.05)
+                        .suffix("x"),
+                );
+                ui.end_row();
+
+                ui.label("Font size");
+                ui.add(egui::DragValue::new(&mut self.font_size).range(6.0..=200.0));
+                ui.end_row();
+
+                ui.label("Format");
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.format, FigureFormat::Png, "PNG");
+                    ui.selectable_value(&mut self.format, FigureFormat::Pdf, "PDF");
+                });
+                ui.end_row();
+
+                if self.format == FigureFormat::Pdf {
+                    ui.label("DPI");
+                    ui.add(egui::DragValue::new(&mut self.dpi).range(10.0..=2400.0));
+                    ui.end_row();
+                }
+            });
+
+        ui.checkbox(&mut self.labels, "Labels");
+        ui.checkbox(&mut self.insets, "ROI insets");
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.diffs, "Difference images");
+            if self.diffs {
+                ui.label("Gain");
+                ui.add(egui::DragValue::new(&mut self.diff_gain).range(1.0..=256.0));
+            }
+        });
+        ui.checkbox(&mut self.metrics, "Metric captions");
+    }
+}
+
+/// One asset in the figure
+pub struct FigureEntry<'a> {
+    pub label: &'a str,
+    pub image: &'a egui::ColorImage,
+    pub is_reference: bool,
+    /// Difference to the reference, if this is not the reference
+    pub diff: Option<egui::ColorImage>,
+    pub metrics: Option<ImageMetrics>,
+}
+
+/// Render the figure off-screen at the resolution given by the settings
+pub fn render_figure(
+    entries: &[FigureEntry<'_>],
+    roi: Option<egui::Rect>,
+    settings: &FigureSettings,
+) -> Result<egui::ColorImage> {
+    anyhow::ensure!(!entries.is_empty(), "No images to export");
+
+    let text = TextRasterizer::new()?;
+    let scaled = |v: usize| ((v as f32 * settings.scale).round() as usize).max(1);
+    let padding = (settings.font_size * 0.75).round() as usize;
+    let line_height = (settings.font_size * 1.4).round() as usize;
+
+    let cell_width = scaled(entries.iter().map(|e| e.image.width()).max().unwrap_or(1));
+    let image_height = scaled(entries.iter().map(|e| e.image.height()).max().unwrap_or(1));
+    let roi = roi.filter(|roi| settings.insets && roi.area() >= 1.0);
+    let inset_height = roi.map_or(0, |roi| {
+        ((cell_width as f32 * roi.height() / roi.width()).round() as usize).max(1)
+    });
+    let has_diffs = settings.diffs && entries.iter().any(|e| e.diff.is_some());
+
+    // rows of a single cell, top to bottom
+    let label_height = if settings.labels { line_height } else { 0 };
+    let caption_height = if settings.metrics { line_height } else { 0 };
+    let mut cell_height = label_height + image_height + caption_height;
+    if roi.is_some() {
+        cell_height += padding + inset_height;
+    }
+    if has_diffs {
+        cell_height += padding + image_height;
+    }
+
+    let columns = settings.columns.clamp(1, entries.len());
+    let rows = entries.len().div_ceil(columns);
+    let width = columns * cell_width + (columns + 1) * padding;
+    let height = rows * cell_height + (rows + 1) * padding;
+    let mut figure = egui::ColorImage::filled([width, height], egui::Color32::WHITE);
+
+    for (i, entry) in entries.iter().enumerate() {
+        let x = padding + (i % columns) * (cell_width + padding);
+        let mut y = padding + (i / columns) * (cell_height + padding);
+
+        if settings.labels {
+            let font_size = settings.font_size;
+            text.draw(
+                &mut figure,
+                entry.label,
+                [x, y],
+                font_size,
+                egui::Color32::BLACK,
+            );
+            y += label_height;
+        }
+
+        let size = [scaled(entry.image.width()), scaled(entry.image.height())];
+        let mut image = resize(entry.image, size)?;
+        if let Some(roi) = roi {
+            let outline = egui::Rect::from_min_max(
+                (roi.min.to_vec2() * settings.scale).to_pos2(),
+                (roi.max.to_vec2() * settings.scale).to_pos2(),
+            );
+            stroke_rect(&mut image, outline, egui::Color32::YELLOW, 2);
+        }
+        blit(&mut figure, &image, [x, y]);
+        y += image_height;
+
+        if let Some(roi) = roi {
+            y += padding;
+            if let Some(crop) = export::crop(entry.image, roi) {
+                blit(
+                    &mut figure,
+                    &resize(&crop, [cell_width, inset_height])?,
+                    [x, y],
+                );
+            }
+            y += inset_height;
+        }
+
+        if has_diffs {
+            y += padding;
+            if let Some(diff) = &entry.diff {
+                let size = [scaled(diff.width()), scaled(diff.height())];
+                blit(&mut figure, &resize(diff, size)?, [x, y]);
+            }
+            y += image_height;
+        }
+
+        if settings.metrics {
+            let caption = match (entry.metrics, entry.is_reference) {
+                (Some(metrics), _) => metrics.caption(),
+                (None, true) => "Reference".to_owned(),
+                (None, false) => String::new(),
+            };
+            let font_size = settings.font_size * 0.8;
+            text.draw(
+                &mut figure,
+                &caption,
+                [x, y],
+                font_size,
+                egui::Color32::BLACK,
+            );
+        }
+    }
+
+    Ok(figure)
+}
+
+/// Encode the rendered figure in the chosen format
+pub fn encode_figure(figure: &egui::ColorImage, settings: &FigureSettings) -> Result<Vec<u8>> {
+    match settings.format {
+        FigureFormat::Png => export::encode_png(figure),
+        FigureFormat::Pdf => Ok(encode_pdf(figure, settings.dpi)),
+    }
+}
+
+/// Resize with nearest neighbour when magnifying, to keep pixels crisp
+fn resize(image: &egui::ColorImage, [width, height]: [usize; 2]) -> Result<egui::ColorImage> {
+    if image.size == [width, height] {
+        return Ok(image.clone());
+    }
+    let filter = if width >= image.width() {
+        image::imageops::FilterType::Nearest
+    } else {
+        image::imageops::FilterType::Triangle
+    };
+    let resized = image::imageops::resize(
+        &export::to_rgba_image(image)?,
+        width as u32,
+        height as u32,
+        filter,
+    );
+    Ok(export::from_rgba_image(&resized))
+}
+
+fn stroke_rect(image: &mut egui::ColorImage, rect: egui::Rect, color: egui::Color32, width: i32) {
+    let [image_width, image_height] = image.size;
+    let (x0, y0) = (rect.min.x.round() as i32, rect.min.y.round() as i32);
+    let (x1, y1) = (rect.max.x.round() as i32, rect.max.y.round() as i32);
+    let mut put = |x: i32, y: i32| {
+        if x >= 0 && y >= 0 && (x as usize) < image_width && (y as usize) < image_height {
+            image[(x as usize, y as usize)] = color;
+        }
+    };
+    for offset in 0..width {
+        for x in x0 - width..x1 + width {
+            put(x, y0 - 1 - offset);
+            put(x, y1 + offset);
+        }
+        for y in y0 - width..y1 + width {
+            put(x0 - 1 - offset, y);
+            put(x1 + offset, y);
+        }
+    }
+}
+
+/// Draws text into images with egui's default font
+pub struct TextRasterizer {
+    font: FontRef<'static>,
+}
+
+impl TextRasterizer {
+    pub fn new() -> Result<Self> {
+        let font = FontRef::try_from_slice(epaint_default_fonts::UBUNTU_LIGHT)
+            .context("Failed to load font")?;
+        Ok(Self { font })
+    }
+
+    /// Draw text with its top left corner at `pos`
+    pub fn draw(
+        &self,
+        image: &mut egui::ColorImage,
+        text: &str,
+        pos: [usize; 2],
+        size: f32,
+        color: egui::Color32,
+    ) {
+        let scale = PxScale::from(size);
+        let scaled_font = self.font.as_scaled(scale);
+        let baseline = pos[1] as f32 + scaled_font.ascent();
+        let [width, height] = image.size;
+
+        let mut x = pos[0] as f32;
+        for c in text.chars() {
+            let glyph_id = self.font.glyph_id(c);
+            let glyph = glyph_id.with_scale_and_position(scale, ab_glyph::point(x, baseline));
+            x += scaled_font.h_advance(glyph_id);
+
+            let Some(outline) = self.font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|gx, gy, coverage| {
+                let px = bounds.min.x as i32 + gx as i32;
+                let py = bounds.min.y as i32 + gy as i32;
+                if px < 0 || py < 0 || px as usize >= width || py as usize >= height {
+                    return;
+                }
+                let pixel = &mut image[(px as usize, py as usize)];
+                *pixel = pixel.lerp_to_gamma(color, coverage.clamp(0.0, 1.0));
+            });
+        }
+    }
+}
+
+/// Single page PDF with the image embedded losslessly at `dpi`
+fn encode_pdf(image: &egui::ColorImage, dpi: f32) -> Vec<u8> {
+    // composite onto white, PDF images have no alpha without a soft mask
+    let rgb: Vec<u8> = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| {
+            let [r, g, b, a] = pixel.to_array();
+            let white = 255 - a;
+            [
+                r.saturating_add(white),
+                g.saturating_add(white),
+                b.saturating_add(white),
+            ]
+        })
+        .collect();
+    let data = miniz_oxide::deflate::compress_to_vec_zlib(&rgb, 6);
+
+    let [width, height] = image.size;
+    let page_width = width as f32 * 72.0 / dpi;
+    let page_height = height as f32 * 72.0 / dpi;
+    let content = format!("q {page_width:.3} 0 0 {page_height:.3} 0 0 cm /Im0 Do Q");
+
+    let mut pdf = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+    let mut offsets = Vec::new();
+    let mut object = |pdf: &mut Vec<u8>, header: String, stream: Option<&[u8]>| {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n{header}\n", offsets.len()).as_bytes());
+        if let Some(stream) = stream {
+            pdf.extend_from_slice(b"stream\n");
+            pdf.extend_from_slice(stream);
+            pdf.extend_from_slice(b"\nendstream\n");
+        }
+        pdf.extend_from_slice(b"endobj\n");
+    };
+
+    object(
+        &mut pdf,
+        "<< /Type /Catalog /Pages 2 0 R >>".to_owned(),
+        None,
+    );
+    object(
+        &mut pdf,
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_owned(),
+        None,
+    );
+    object(
+        &mut pdf,
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {page_width:.3} {page_height:.3}] \
+             /Resources << /XObject << /Im0 4 0 R >> >> /Contents 5 0 R >>"
+        ),
+        None,
+    );
+    object(
+        &mut pdf,
+        format!(
+            "<< /Type /XObject /Subtype /Image /Width {width} /Height {height} \
+             /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /FlateDecode /Length {} >>",
+            data.len()
+        ),
+        Some(&data),
+    );
+    object(
+        &mut pdf,
+        format!("<< /Length {} >>", content.len()),
+        Some(content.as_bytes()),
+    );
+
+    let xref_offset = pdf.len();
+    let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1);
+    for offset in &offsets {
+        xref += &format!("{offset:010} 00000 n \n");
+    }
+    xref += &format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+        offsets.len() + 1
+    );
+    pdf.extend_from_slice(xref.as_bytes());
+    pdf
+}
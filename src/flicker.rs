@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlickerMode {
+    /// Cycle through the assets at a fixed interval
+    Timed,
+    /// Show the first asset, and the next one while space is held
+    Hold,
+}
+
+/// Alternates the displayed asset without touching the selection or the view
+pub struct Flicker {
+    pub active: bool,
+    pub mode: FlickerMode,
+    /// Seconds each asset is shown for in timed mode
+    pub interval: f32,
+    /// Ids of the assets to alternate between, in order
+    pub ids: Vec<String>,
+    hold_presses: usize,
+    start_time: f64,
+}
+
+impl Default for Flicker {
+    fn default() -> Self {
+        Self {
+            active: false,
+            mode: FlickerMode::Timed,
+            interval: 0.5,
+            ids: vec![],
+            hold_presses: 0,
+            start_time: 0.0,
+        }
+    }
+}
+
+impl Flicker {
+    pub fn toggle(&mut self, ctx: &egui::Context) {
+        self.active = !self.active;
+        self.start_time = ctx.input(|i| i.time);
+    }
+
+    /// Id of the asset to show this frame, `None` if flicker is not active
+    pub fn current(&mut self, ctx: &egui::Context) -> Option<&str> {
+        let n = self.ids.len();
+        if !self.active || n < 2 {
+            return None;
+        }
+
+        let index = match self.mode {
+            FlickerMode::Timed => {
+                let interval = f64::from(self.interval.max(0.02));
+                let elapsed = ctx.input(|i| i.time) - self.start_time;
+                ctx.request_repaint_after(Duration::from_secs_f64(
+                    interval - elapsed.rem_euclid(interval),
+                ));
+                (elapsed / interval) as usize % n
+            }
+            FlickerMode::Hold => {
+                let (pressed, held) = ctx.input(|i| {
+                    let pressed = i.events.iter().any(|event| {
+                        matches!(
+                            event,
+                            egui::Event::Key {
+                                key: egui::Key::Space,
+                                pressed: true,
+                                repeat: false,
+                                ..
+                            }
+                        )
+                    });
+                    (pressed, i.key_down(egui::Key::Space))
+                });
+                if pressed {
+                    self.hold_presses += 1;
+                }
+                if held {
+                    1 + self.hold_presses.saturating_sub(1) % (n - 1)
+                } else {
+                    0
+                }
+            }
+        };
+        self.ids.get(index).map(String::as_str)
+    }
+
+    /// Settings, `available` are the ids of all loaded assets
+    pub fn show_settings<'a>(
+        &mut self,
+        ui: &mut egui::Ui,
+        available: impl Iterator<Item = &'a str>,
+    ) {
+        let mut active = self.active;
+        if ui.toggle_value(&mut active, "Flicker (F)").changed() {
+            self.toggle(ui.ctx());
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Mode:");
+            ui.selectable_value(&mut self.mode, FlickerMode::Timed, "Timed");
+            ui.selectable_value(&mut self.mode, FlickerMode::Hold, "Hold space");
+        });
+        if self.mode == FlickerMode::Timed {
+            ui.add(
+                egui::Slider::new(&mut self.interval, 0.05..=2.0)
+                    .text("Interval")
+                    .suffix(" s"),
+            );
+        }
+
+        ui.separator();
+        ui.label("Assets, in order:");
+        for id in available {
+            let mut included = self.ids.iter().any(|included_id| included_id == id);
+            if ui.checkbox(&mut included, id).changed() {
+                if included {
+                    self.ids.push(id.to_owned());
+                } else {
+                    self.ids.retain(|included_id| included_id != id);
+                }
+            }
+        }
+        if self.ids.len() < 2 {
+            ui.weak("Pick at least two assets");
+        }
+    }
+}
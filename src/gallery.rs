@@ -0,0 +1,104 @@
+use crate::asset::{Asset as _, AssetEnum, format_bytes};
+use crate::selector::Selector;
+use crate::thumbnail::paint_thumbnail;
+
+/// Side of the thumbnail of a tile, in points
+const TILE_SIDE: f32 = 160.0;
+
+/// Every asset at once as a contact sheet, hover a tile for details.
+/// Clicking selects like in the selector, returns true when an asset was picked to view
+pub fn show(ui: &mut egui::Ui, items: &mut [AssetEnum], selector: &mut Selector) -> bool {
+    if items.is_empty() {
+        ui.centered_and_justified(|ui| {
+            ui.label(egui::RichText::new("📁 Nothing loaded yet").size(18.0));
+        });
+        return false;
+    }
+
+    let mut picked = false;
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        ui.horizontal_wrapped(|ui| {
+            ui.spacing_mut().item_spacing = egui::vec2(8.0, 8.0);
+            let selected = selector.selected();
+            let names: Vec<String> = items.iter().map(|item| item.get_id().to_owned()).collect();
+            for (i, item) in items.iter_mut().enumerate() {
+                let font_id = egui::TextStyle::Body.resolve(ui.style());
+                let label_height = ui.fonts(|f| f.row_height(&font_id)) + 8.0;
+                let (rect, response) = ui.allocate_exact_size(
+                    egui::vec2(TILE_SIDE, TILE_SIDE + label_height),
+                    egui::Sense::click(),
+                );
+
+                let visuals = ui.visuals();
+                let background = if selected.contains(&i) {
+                    visuals.widgets.active.bg_fill
+                } else if response.hovered() {
+                    visuals.widgets.hovered.bg_fill
+                } else {
+                    visuals.window_fill.linear_multiply(1.2)
+                };
+                ui.painter().rect_filled(rect, 4.0, background);
+                if i == selector.selected_index {
+                    let stroke = egui::Stroke::new(2.0, visuals.selection.stroke.color);
+                    ui.painter()
+                        .rect_stroke(rect, 4.0, stroke, egui::StrokeKind::Inside);
+                }
+
+                let thumbnail_rect =
+                    egui::Rect::from_min_size(rect.min, egui::Vec2::splat(TILE_SIDE)).shrink(6.0);
+                let texture = item.thumbnail().texture(ui.ctx(), response.hovered());
+                paint_thumbnail(ui, thumbnail_rect, texture);
+
+                let mut job = egui::text::LayoutJob::simple_singleline(
+                    item.get_id().to_owned(),
+                    font_id,
+                    ui.visuals().text_color(),
+                );
+                job.wrap = egui::text::TextWrapping::truncate_at_width(TILE_SIDE - 12.0);
+                let galley = ui.fonts(|f| f.layout_job(job));
+                ui.painter().galley(
+                    egui::pos2(
+                        rect.center().x - galley.size().x / 2.0,
+                        rect.bottom() - label_height + 4.0,
+                    ),
+                    galley,
+                    ui.visuals().text_color(),
+                );
+
+                let response = response.on_hover_ui(|ui| show_metadata(ui, item));
+                if response.clicked() {
+                    let modifiers = ui.input(|input| input.modifiers);
+                    selector.click(i, modifiers, &names);
+                    picked = !modifiers.shift && !modifiers.command;
+                }
+            }
+        });
+    });
+    picked
+}
+
+fn show_metadata(ui: &mut egui::Ui, item: &AssetEnum) {
+    ui.strong(item.get_id());
+    match item {
+        AssetEnum::Image(image_asset) => {
+            if image_asset.is_evicted() {
+                ui.label("Not in memory, decoded again when shown");
+            } else {
+                let [width, height] = image_asset.image.size;
+                ui.label(format!("{width}x{height} px"));
+            }
+            ui.label(image_asset.interpretation.label());
+        }
+        AssetEnum::Model(model) => {
+            ui.label(format!(
+                "{} vertices, {} triangles",
+                model.verts.len(),
+                model.indices.len() / 3
+            ));
+        }
+    }
+    ui.label(format_bytes(item.memory_usage()));
+    if let Some(path) = item.get_file_path() {
+        ui.weak(path.display().to_string());
+    }
+}
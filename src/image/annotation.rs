@@ -0,0 +1,486 @@
+use crate::asset::Asset;
+use crate::figure::TextRasterizer;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Distance in points within which a right click removes an annotation
+const PICK_DISTANCE: f32 = 6.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum AnnotationTool {
+    Arrow,
+    Rect,
+    Circle,
+    Freehand,
+    Text,
+}
+
+impl AnnotationTool {
+    pub const ALL: [Self; 5] = [
+        Self::Arrow,
+        Self::Rect,
+        Self::Circle,
+        Self::Freehand,
+        Self::Text,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Arrow => "↗ Arrow",
+            Self::Rect => "⬜ Rect",
+            Self::Circle => "⭕ Circle",
+            Self::Freehand => "✏ Draw",
+            Self::Text => "🗛 Text",
+        }
+    }
+}
+
+/// Geometry of an annotation, in pixel coordinates of the image
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Shape {
+    Arrow {
+        from: egui::Pos2,
+        to: egui::Pos2,
+    },
+    Rect {
+        rect: egui::Rect,
+    },
+    Circle {
+        center: egui::Pos2,
+        radius: f32,
+    },
+    Freehand {
+        points: Vec<egui::Pos2>,
+    },
+    Text {
+        pos: egui::Pos2,
+        text: String,
+        size: f32,
+    },
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct Annotation {
+    pub shape: Shape,
+    pub color: egui::Color32,
+    /// Stroke width in pixels of the image
+    pub width: f32,
+}
+
+impl Annotation {
+    /// Lines the annotation is drawn with, text has none
+    fn polylines(&self) -> Vec<Vec<egui::Pos2>> {
+        match &self.shape {
+            Shape::Arrow { from, to } => {
+                let direction = (*to - *from).normalized();
+                let head = (self.width * 4.0).max((*to - *from).length() * 0.15);
+                let side =
+                    |angle: f32| *to - egui::emath::Rot2::from_angle(angle) * direction * head;
+                vec![vec![*from, *to], vec![side(0.5), *to, side(-0.5)]]
+            }
+            Shape::Rect { rect } => vec![vec![
+                rect.left_top(),
+                rect.right_top(),
+                rect.right_bottom(),
+                rect.left_bottom(),
+                rect.left_top(),
+            ]],
+            Shape::Circle { center, radius } => {
+                let segments = 64;
+                vec![
+                    (0..=segments)
+                        .map(|i| {
+                            let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+                            *center + egui::Vec2::angled(angle) * *radius
+                        })
+                        .collect(),
+                ]
+            }
+            Shape::Freehand { points } => vec![points.clone()],
+            Shape::Text { .. } => vec![],
+        }
+    }
+
+    /// Distance from `pos` to the annotation, both in pixels of the image
+    fn distance(&self, pos: egui::Pos2) -> f32 {
+        if let Shape::Text {
+            pos: anchor,
+            text,
+            size,
+        } = &self.shape
+        {
+            let rect = egui::Rect::from_min_size(
+                *anchor,
+                egui::vec2(text.chars().count() as f32 * size * 0.5, *size),
+            );
+            return rect.distance_to_pos(pos);
+        }
+        self.polylines()
+            .iter()
+            .flat_map(|line| line.windows(2))
+            .map(|segment| distance_to_segment(pos, segment[0], segment[1]))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    pub fn paint(
+        &self,
+        painter: &egui::Painter,
+        to_screen: impl Fn(egui::Pos2) -> egui::Pos2,
+        zoom: f32,
+    ) {
+        let stroke = egui::Stroke::new((self.width * zoom).max(1.0), self.color);
+        for line in self.polylines() {
+            let points = line.into_iter().map(&to_screen).collect();
+            painter.add(egui::Shape::line(points, stroke));
+        }
+        if let Shape::Text { pos, text, size } = &self.shape {
+            painter.text(
+                to_screen(*pos),
+                egui::Align2::LEFT_TOP,
+                text,
+                egui::FontId::proportional((size * zoom).max(4.0)),
+                self.color,
+            );
+        }
+    }
+}
+
+/// Key the annotations of an asset are kept under, its file so that they survive renames and
+/// restarts and every pair set has its own. Assets without a file, e.g. pasted ones, use their id
+pub fn key(asset: &impl Asset) -> String {
+    asset
+        .get_file_path()
+        .map_or_else(|| asset.get_id().to_owned(), path_key)
+}
+
+/// Key of the annotations of the assets loaded from `path`
+pub fn path_key(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// Review notes drawn over the images, kept per asset [`key`]
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Annotations {
+    by_asset: BTreeMap<String, Vec<Annotation>>,
+    /// `None` leaves dragging to pan the image
+    #[serde(skip)]
+    pub tool: Option<AnnotationTool>,
+    color: egui::Color32,
+    width: f32,
+    text_size: f32,
+    /// Text placed by the next click of the text tool
+    note: String,
+    #[serde(skip)]
+    drawing: Option<Annotation>,
+    #[serde(skip)]
+    drag_start: Option<egui::Pos2>,
+}
+
+impl Default for Annotations {
+    fn default() -> Self {
+        Self {
+            by_asset: BTreeMap::new(),
+            tool: None,
+            color: egui::Color32::from_rgb(255, 60, 60),
+            width: 3.0,
+            text_size: 24.0,
+            note: String::new(),
+            drawing: None,
+            drag_start: None,
+        }
+    }
+}
+
+impl Annotations {
+    pub fn get(&self, id: &str) -> &[Annotation] {
+        self.by_asset.get(id).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn for_asset(&self, asset: &impl Asset) -> &[Annotation] {
+        self.get(&key(asset))
+    }
+
+    /// Follow an asset that was renamed, its key only changes without a file
+    pub fn rename(&mut self, old_id: &str, new_id: &str) {
+        if let Some(annotations) = self.by_asset.remove(old_id) {
+            self.by_asset.insert(new_id.to_owned(), annotations);
+        }
+    }
+
+    /// Keep only the annotations under the keys of `keys`, moved to the key each maps to,
+    /// e.g. from files to the asset labels of a project
+    pub fn rekey(&mut self, keys: &BTreeMap<String, String>) {
+        let by_asset = std::mem::take(&mut self.by_asset);
+        for (from, to) in keys {
+            if let Some(annotations) = by_asset.get(from) {
+                self.by_asset.insert(to.clone(), annotations.clone());
+            }
+        }
+    }
+
+    /// Give a copy of an asset the annotations of the original, copies of a file share them
+    pub fn copy(&mut self, from_id: &str, to_id: &str) {
+        if let Some(annotations) = self.by_asset.get(from_id).cloned() {
+            self.by_asset.insert(to_id.to_owned(), annotations);
+        }
+    }
+
+    /// Draw with the current tool, right click removes an annotation
+    pub fn handle_input(
+        &mut self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        id: &str,
+        to_image: impl Fn(egui::Pos2) -> egui::Pos2,
+        zoom: f32,
+    ) {
+        let Some(tool) = self.tool else {
+            return;
+        };
+        if response.hovered()
+            && ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z))
+        {
+            self.undo(id);
+        }
+
+        if response.secondary_clicked() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                self.remove_at(id, to_image(pos), PICK_DISTANCE / zoom);
+            }
+        }
+
+        if tool == AnnotationTool::Text {
+            if response.clicked() && !self.note.trim().is_empty() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let shape = Shape::Text {
+                        pos: to_image(pos),
+                        text: self.note.trim().to_owned(),
+                        size: self.text_size,
+                    };
+                    self.push(id, shape);
+                }
+            }
+            return;
+        }
+
+        if response.drag_started_by(egui::PointerButton::Primary) {
+            self.drag_start = response.interact_pointer_pos().map(&to_image);
+        }
+        let Some(start) = self.drag_start else {
+            return;
+        };
+        if let Some(pos) = response.interact_pointer_pos() {
+            let end = to_image(pos);
+            let shape = match (tool, self.drawing.take().map(|drawing| drawing.shape)) {
+                (AnnotationTool::Freehand, Some(Shape::Freehand { mut points })) => {
+                    if points
+                        .last()
+                        .is_none_or(|last| last.distance(end) * zoom >= 2.0)
+                    {
+                        points.push(end);
+                    }
+                    Shape::Freehand { points }
+                }
+                (AnnotationTool::Freehand, _) => Shape::Freehand {
+                    points: vec![start, end],
+                },
+                (AnnotationTool::Arrow, _) => Shape::Arrow {
+                    from: start,
+                    to: end,
+                },
+                (AnnotationTool::Rect, _) => Shape::Rect {
+                    rect: egui::Rect::from_two_pos(start, end),
+                },
+                (AnnotationTool::Circle, _) => Shape::Circle {
+                    center: start,
+                    radius: start.distance(end),
+                },
+                // placed by clicking, handled above
+                (AnnotationTool::Text, _) => return,
+            };
+            self.drawing = Some(self.annotation(shape));
+        }
+        if response.drag_stopped() {
+            self.drag_start = None;
+            // a click without movement adds nothing
+            if let Some(drawing) = self.drawing.take() {
+                if drawing
+                    .polylines()
+                    .iter()
+                    .flatten()
+                    .any(|p| p.distance(start) * zoom > 2.0)
+                {
+                    self.by_asset
+                        .entry(id.to_owned())
+                        .or_default()
+                        .push(drawing);
+                }
+            }
+        }
+    }
+
+    fn annotation(&self, shape: Shape) -> Annotation {
+        Annotation {
+            shape,
+            color: self.color,
+            width: self.width,
+        }
+    }
+
+    fn push(&mut self, id: &str, shape: Shape) {
+        let annotation = self.annotation(shape);
+        self.by_asset
+            .entry(id.to_owned())
+            .or_default()
+            .push(annotation);
+    }
+
+    fn undo(&mut self, id: &str) {
+        if let Some(annotations) = self.by_asset.get_mut(id) {
+            annotations.pop();
+        }
+    }
+
+    /// Remove the annotation closest to `pos` if it is within `max_distance`
+    fn remove_at(&mut self, id: &str, pos: egui::Pos2, max_distance: f32) {
+        let Some(annotations) = self.by_asset.get_mut(id) else {
+            return;
+        };
+        let closest = annotations
+            .iter()
+            .enumerate()
+            .map(|(i, annotation)| (i, annotation.distance(pos)))
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, distance)) = closest {
+            if distance <= max_distance {
+                annotations.remove(i);
+            }
+        }
+    }
+
+    /// The annotations of an asset and the one being drawn
+    pub fn paint(
+        &self,
+        painter: &egui::Painter,
+        id: &str,
+        to_screen: impl Fn(egui::Pos2) -> egui::Pos2,
+        zoom: f32,
+    ) {
+        for annotation in self.get(id).iter().chain(&self.drawing) {
+            annotation.paint(painter, &to_screen, zoom);
+        }
+    }
+
+    pub fn show_settings(&mut self, ui: &mut egui::Ui, id: &str) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Annotate:");
+            ui.selectable_value(&mut self.tool, None, "✋ Pan");
+            for tool in AnnotationTool::ALL {
+                ui.selectable_value(&mut self.tool, Some(tool), tool.label());
+            }
+        });
+        if self.tool.is_none() {
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.color_edit_button_srgba(&mut self.color);
+            ui.add(
+                egui::DragValue::new(&mut self.width)
+                    .range(0.5..=50.0)
+                    .speed(0.1)
+                    .suffix(" px"),
+            );
+            if self.tool == Some(AnnotationTool::Text) {
+                ui.add(
+                    egui::DragValue::new(&mut self.text_size)
+                        .range(4.0..=400.0)
+                        .suffix(" px text"),
+                );
+            }
+        });
+        if self.tool == Some(AnnotationTool::Text) {
+            ui.add(egui::TextEdit::singleline(&mut self.note).hint_text("Note, click to place"));
+        }
+        ui.horizontal(|ui| {
+            ui.label(format!("{} on this image", self.get(id).len()));
+            if ui.small_button("Undo").on_hover_text("Ctrl+Z").clicked() {
+                self.undo(id);
+            }
+            if ui.small_button("Clear").clicked() {
+                self.by_asset.remove(id);
+            }
+        });
+    }
+}
+
+fn distance_to_segment(pos: egui::Pos2, a: egui::Pos2, b: egui::Pos2) -> f32 {
+    let ab = b - a;
+    let t = ((pos - a).dot(ab) / ab.length_sq().max(f32::EPSILON)).clamp(0.0, 1.0);
+    pos.distance(a + ab * t)
+}
+
+/// Draw annotations into the image they were made on, antialiased
+pub fn burn_in(image: &mut egui::ColorImage, annotations: &[Annotation]) -> Result<()> {
+    let mut text = None;
+    for annotation in annotations {
+        let radius = annotation.width * 0.5;
+        for line in annotation.polylines() {
+            for segment in line.windows(2) {
+                draw_segment(image, segment[0], segment[1], radius, annotation.color);
+            }
+        }
+        if let Shape::Text {
+            pos,
+            text: note,
+            size,
+        } = &annotation.shape
+        {
+            let rasterizer = match &mut text {
+                Some(rasterizer) => rasterizer,
+                None => text.insert(TextRasterizer::new()?),
+            };
+            let pos = [pos.x.max(0.0) as usize, pos.y.max(0.0) as usize];
+            rasterizer.draw(image, note, pos, *size, annotation.color);
+        }
+    }
+    Ok(())
+}
+
+/// Thick line from `a` to `b` with round caps
+fn draw_segment(
+    image: &mut egui::ColorImage,
+    a: egui::Pos2,
+    b: egui::Pos2,
+    radius: f32,
+    color: egui::Color32,
+) {
+    let [width, height] = image.size;
+    let bounds = egui::Rect::from_two_pos(a, b).expand(radius + 1.0);
+    let x0 = bounds.min.x.floor().clamp(0.0, width as f32) as usize;
+    let y0 = bounds.min.y.floor().clamp(0.0, height as f32) as usize;
+    let x1 = bounds.max.x.ceil().clamp(0.0, width as f32) as usize;
+    let y1 = bounds.max.y.ceil().clamp(0.0, height as f32) as usize;
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let center = egui::pos2(x as f32 + 0.5, y as f32 + 0.5);
+            let coverage = (radius + 0.5 - distance_to_segment(center, a, b)).clamp(0.0, 1.0);
+            if coverage > 0.0 {
+                let pixel = &mut image[(x, y)];
+                *pixel = pixel.lerp_to_gamma(color, coverage);
+            }
+        }
+    }
+}
+
+/// JSON of the annotations of an asset, for review tools and scripts
+pub fn to_json(id: &str, size: [usize; 2], annotations: &[Annotation]) -> Result<Vec<u8>> {
+    let json = serde_json::json!({
+        "asset": id,
+        "size": size,
+        "annotations": annotations,
+    });
+    Ok(serde_json::to_vec_pretty(&json)?)
+}
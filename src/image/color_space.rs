@@ -0,0 +1,577 @@
+use crate::image::pixels::Pixels;
+use std::collections::BTreeMap;
+
+/// Primaries and transfer function of the values in an image
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    /// Rec.709 primaries without a transfer function, e.g. most EXRs
+    LinearSrgb,
+    DisplayP3,
+    Rec2020,
+    /// ACES AP1 primaries without a transfer function
+    AcesCg,
+}
+
+impl ColorSpace {
+    pub const ALL: [Self; 5] = [
+        Self::Srgb,
+        Self::LinearSrgb,
+        Self::DisplayP3,
+        Self::Rec2020,
+        Self::AcesCg,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Srgb => "sRGB",
+            Self::LinearSrgb => "Linear sRGB",
+            Self::DisplayP3 => "Display P3",
+            Self::Rec2020 => "Rec.2020",
+            Self::AcesCg => "ACEScg",
+        }
+    }
+
+    /// Linear RGB in these primaries to linear Rec.709, both with a D65 white.
+    /// `ACEScg` is adapted from its D60 white with the Bradford transform
+    fn to_rec709(self) -> [[f32; 3]; 3] {
+        match self {
+            Self::Srgb | Self::LinearSrgb => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            Self::DisplayP3 => [
+                [1.22494, -0.22494, 0.0],
+                [-0.042057, 1.042057, 0.0],
+                [-0.019638, -0.078636, 1.098274],
+            ],
+            Self::Rec2020 => [
+                [1.660491, -0.587641, -0.07285],
+                [-0.12455, 1.1329, -0.008349],
+                [-0.018151, -0.100579, 1.11873],
+            ],
+            Self::AcesCg => [
+                [1.705051, -0.621792, -0.083259],
+                [-0.130257, 1.140805, -0.010548],
+                [-0.024004, -0.128969, 1.152972],
+            ],
+        }
+    }
+
+    /// Encoded value to linear light, values beyond [0, 1] are extended by the same curve
+    fn decode(self, value: f32) -> f32 {
+        match self {
+            Self::Srgb | Self::DisplayP3 => {
+                if value <= 0.04045 {
+                    value / 12.92
+                } else {
+                    ((value + 0.055) / 1.055).powf(2.4)
+                }
+            }
+            Self::Rec2020 => {
+                if value < 0.081 {
+                    value / 4.5
+                } else {
+                    ((value + 0.099) / 1.099).powf(1.0 / 0.45)
+                }
+            }
+            Self::LinearSrgb | Self::AcesCg => value,
+        }
+    }
+
+    /// Linear light to an encoded value, values beyond [0, 1] are extended by the same curve
+    fn encode(self, value: f32) -> f32 {
+        match self {
+            Self::Srgb | Self::DisplayP3 => {
+                if value <= 0.0031308 {
+                    value * 12.92
+                } else {
+                    1.055 * value.powf(1.0 / 2.4) - 0.055
+                }
+            }
+            Self::Rec2020 => {
+                if value < 0.018 {
+                    value * 4.5
+                } else {
+                    1.099 * value.powf(0.45) - 0.099
+                }
+            }
+            Self::LinearSrgb | Self::AcesCg => value,
+        }
+    }
+
+    /// The pixels of `image`, encoded in this space, converted into `target`.
+    /// The result is kept as floats, colors outside of the gamut of `target` are only clipped
+    /// once quantized for display
+    pub fn convert(self, image: &Pixels, target: Self) -> Pixels {
+        if self == target {
+            return image.clone();
+        }
+        let matrix = multiply(invert(target.to_rec709()), self.to_rec709());
+        let decode: Box<dyn Fn([f32; 4]) -> [f32; 3]> = match image {
+            // 8 bit values are decoded once each
+            Pixels::Rgba8(_) => {
+                let table: Vec<f32> = (0..=255_u8)
+                    .map(|value| self.decode(f32::from(value) / 255.0))
+                    .collect();
+                Box::new(move |[r, g, b, _]| {
+                    [r, g, b].map(|value| table[(value * 255.0).round() as usize])
+                })
+            }
+            Pixels::Rgba32F(_) => {
+                Box::new(move |[r, g, b, _]| [r, g, b].map(|value| self.decode(value)))
+            }
+        };
+
+        let [width, height] = image.size();
+        let samples: Vec<f32> = image
+            .rgba()
+            .flat_map(|rgba| {
+                let linear = decode(rgba);
+                let [r, g, b] = matrix.map(|row| {
+                    target.encode(row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2])
+                });
+                [r, g, b, rgba[3]]
+            })
+            .collect();
+        image::Rgba32FImage::from_raw(width as u32, height as u32, samples)
+            .map_or_else(Pixels::default, Pixels::Rgba32F)
+    }
+
+    /// The pixels of `image`, encoded in this space, as shown on an sRGB screen
+    pub fn display(self, image: &Pixels) -> egui::ColorImage {
+        self.convert(image, Self::Srgb).quantize()
+    }
+
+    /// Color space a file declares, `None` if it declares none that is supported
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            detect_png(bytes)
+        } else if bytes.starts_with(b"\x76\x2f\x31\x01") {
+            // scene linear by convention, Rec.709 primaries unless stated otherwise
+            Some(detect_exr(bytes).unwrap_or(Self::LinearSrgb))
+        } else if bytes.starts_with(b"#?RADIANCE") || bytes.starts_with(b"#?RGBE") {
+            Some(Self::LinearSrgb)
+        } else {
+            None
+        }
+    }
+}
+
+fn multiply(a: [[f32; 3]; 3], b: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    std::array::from_fn(|row| {
+        std::array::from_fn(|column| (0..3).map(|i| a[row][i] * b[i][column]).sum())
+    })
+}
+
+fn invert(m: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let cofactor = |row: usize, column: usize| {
+        let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+        let (c0, c1) = ((column + 1) % 3, (column + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let determinant = (0..3).map(|i| m[0][i] * cofactor(0, i)).sum::<f32>();
+    std::array::from_fn(|row| std::array::from_fn(|column| cofactor(column, row) / determinant))
+}
+
+/// From the cICP, iCCP, sRGB or gAMA chunk, in the order of precedence of the PNG spec
+fn detect_png(bytes: &[u8]) -> Option<ColorSpace> {
+    let mut chunks = BTreeMap::new();
+    let mut offset = 8;
+    while let Some(header) = bytes.get(offset..offset + 8) {
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let name = [header[4], header[5], header[6], header[7]];
+        if &name == b"IDAT" || &name == b"IEND" {
+            break;
+        }
+        // lengths come from the file, a corrupt one must not overflow on 32 bit targets
+        let data_end = (offset + 8).checked_add(length)?;
+        let data = bytes.get(offset + 8..data_end)?;
+        chunks.insert(name, data);
+        offset = data_end.checked_add(4)?;
+    }
+
+    if let Some(cicp) = chunks.get(b"cICP") {
+        // primaries and transfer function, as numbered in ITU-T H.273. The SDR curves are
+        // BT.709, BT.601, sRGB and BT.2020, PQ and HLG are not supported
+        return match (*cicp.first()?, *cicp.get(1)?) {
+            (1, 8) => Some(ColorSpace::LinearSrgb),
+            (1, 1 | 6 | 13 | 14 | 15) => Some(ColorSpace::Srgb),
+            (12, 1 | 6 | 13 | 14 | 15) => Some(ColorSpace::DisplayP3),
+            (9, 1 | 6 | 13 | 14 | 15) => Some(ColorSpace::Rec2020),
+            (primaries, transfer) => {
+                log::warn!(
+                    "Unsupported cICP primaries {primaries} with transfer {transfer}, assuming sRGB"
+                );
+                None
+            }
+        };
+    }
+    if let Some(iccp) = chunks.get(b"iCCP") {
+        return detect_icc(iccp);
+    }
+    if chunks.contains_key(b"sRGB") {
+        return Some(ColorSpace::Srgb);
+    }
+    // a gamma of 1.0 is stored as 100000
+    let gamma = chunks.get(b"gAMA")?;
+    let gamma = u32::from_be_bytes(gamma.get(..4)?.try_into().ok()?);
+    (gamma == 100_000).then_some(ColorSpace::LinearSrgb)
+}
+
+/// By the description of an embedded ICC profile, or else the name the PNG gives it
+fn detect_icc(iccp: &[u8]) -> Option<ColorSpace> {
+    let name_end = iccp.iter().position(|&byte| byte == 0)?;
+    let name: String = iccp[..name_end]
+        .iter()
+        .map(|&byte| char::from(byte))
+        .collect();
+    let description = iccp
+        .get(name_end + 2..)
+        .and_then(|compressed| miniz_oxide::inflate::decompress_to_vec_zlib(compressed).ok())
+        .and_then(|profile| icc_description(&profile))
+        .unwrap_or(name);
+
+    let space = icc_color_space(&description.to_lowercase());
+    if space.is_none() {
+        log::info!("Unknown ICC profile {description}, assuming sRGB");
+    }
+    space
+}
+
+/// The supported space a profile description names, whole names only, other text in a profile
+/// such as its copyright often holds a year like 2020
+fn icc_color_space(description: &str) -> Option<ColorSpace> {
+    let names = |names: &[&str]| names.iter().any(|name| description.contains(name));
+    let linear = description.contains("linear");
+    if names(&["acescg", "aces cg", "aces ap1"]) {
+        Some(ColorSpace::AcesCg)
+    } else if linear {
+        names(&["srgb", "rec. 709", "rec.709", "bt.709"]).then_some(ColorSpace::LinearSrgb)
+    } else if names(&["display p3", "p3-d65", "p3 d65"]) {
+        Some(ColorSpace::DisplayP3)
+    } else if names(&[
+        "rec. 2020",
+        "rec.2020",
+        "rec 2020",
+        "bt.2020",
+        "bt. 2020",
+        "bt2020",
+    ]) {
+        Some(ColorSpace::Rec2020)
+    } else if names(&["srgb"]) {
+        Some(ColorSpace::Srgb)
+    } else {
+        None
+    }
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> Option<usize> {
+    let value = u32::from_be_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?);
+    Some(value as usize)
+}
+
+/// Text of the `desc` tag of an ICC profile, ASCII in version 2 profiles and the first
+/// UTF-16 record of a `mluc` in version 4 ones
+fn icc_description(profile: &[u8]) -> Option<String> {
+    // the tag table follows the 128 byte header, 12 bytes per tag
+    let count = read_u32_be(profile, 128)?.min(profile.len() / 12);
+    let (offset, size) = (0..count).find_map(|i| {
+        let entry = 132 + 12 * i;
+        if profile.get(entry..entry + 4)? != b"desc" {
+            return None;
+        }
+        Some((
+            read_u32_be(profile, entry + 4)?,
+            read_u32_be(profile, entry + 8)?,
+        ))
+    })?;
+    let tag = profile.get(offset..offset.checked_add(size)?)?;
+    match tag.get(..4)? {
+        b"desc" => {
+            let length = read_u32_be(tag, 8)?;
+            let ascii = tag.get(12..12_usize.checked_add(length)?)?;
+            Some(
+                ascii
+                    .iter()
+                    .take_while(|&&byte| byte != 0)
+                    .map(|&byte| char::from(byte))
+                    .collect(),
+            )
+        }
+        b"mluc" => {
+            if read_u32_be(tag, 8)? == 0 {
+                return None;
+            }
+            let (length, start) = (read_u32_be(tag, 20)?, read_u32_be(tag, 24)?);
+            let utf16: Vec<u16> = tag
+                .get(start..start.checked_add(length)?)?
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&utf16))
+        }
+        _ => None,
+    }
+}
+
+/// From the chromaticities attribute of the first header
+fn detect_exr(bytes: &[u8]) -> Option<ColorSpace> {
+    let mut offset = 8;
+    loop {
+        let rest = bytes.get(offset..)?;
+        let name_end = rest.iter().position(|&byte| byte == 0)?;
+        if name_end == 0 {
+            return None;
+        }
+        let type_end = name_end + 1 + rest[name_end + 1..].iter().position(|&byte| byte == 0)?;
+        let size = u32::from_le_bytes(rest.get(type_end + 1..type_end + 5)?.try_into().ok()?);
+        // sizes come from the file, a corrupt one must not overflow on 32 bit targets
+        let value_end = (type_end + 5).checked_add(size as usize)?;
+        let value = rest.get(type_end + 5..value_end)?;
+        if &rest[..name_end] == b"chromaticities" {
+            let mut chromaticities = [0.0; 8];
+            for (chromaticity, bytes) in chromaticities
+                .iter_mut()
+                .zip(value.get(..32)?.chunks_exact(4))
+            {
+                *chromaticity = f32::from_le_bytes(bytes.try_into().ok()?);
+            }
+            return exr_color_space(chromaticities);
+        }
+        offset = offset.checked_add(value_end)?;
+    }
+}
+
+/// Red, green and blue primaries and white point as x, y of the linear spaces EXRs are tagged with
+const EXR_CHROMATICITIES: [(ColorSpace, [f32; 8]); 2] = [
+    (
+        ColorSpace::LinearSrgb,
+        [0.64, 0.33, 0.30, 0.60, 0.15, 0.06, 0.3127, 0.3290],
+    ),
+    (
+        ColorSpace::AcesCg,
+        [0.713, 0.293, 0.165, 0.830, 0.128, 0.044, 0.32168, 0.33767],
+    ),
+];
+
+/// The linear space with these chromaticities, `None` if no supported space has all of them
+fn exr_color_space(chromaticities: [f32; 8]) -> Option<ColorSpace> {
+    let matching = EXR_CHROMATICITIES.iter().find(|(_, expected)| {
+        expected
+            .iter()
+            .zip(chromaticities)
+            .all(|(expected, value)| (expected - value).abs() < 0.001)
+    });
+    if matching.is_none() {
+        log::warn!("Unsupported EXR chromaticities {chromaticities:?}, assuming Rec.709");
+    }
+    matching.map(|&(space, _)| space)
+}
+
+/// Input color spaces chosen by hand and the space images are compared and shown in
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ColorManagement {
+    /// Pixels are converted into this space, metrics and differences are computed in it
+    pub working: ColorSpace,
+    /// Input color spaces overriding the detected ones, by asset id
+    overrides: BTreeMap<String, ColorSpace>,
+}
+
+impl ColorManagement {
+    /// Color space the pixels of an asset are in, as chosen or else as detected
+    pub fn input(&self, id: &str, detected: ColorSpace) -> ColorSpace {
+        self.overrides.get(id).copied().unwrap_or(detected)
+    }
+
+    /// Follow an asset that was renamed
+    pub fn rename(&mut self, old_id: &str, new_id: &str) {
+        if let Some(space) = self.overrides.remove(old_id) {
+            self.overrides.insert(new_id.to_owned(), space);
+        }
+    }
+
+    /// Give a copy of an asset the input color space of the original
+    pub fn copy(&mut self, from_id: &str, to_id: &str) {
+        if let Some(space) = self.overrides.get(from_id).copied() {
+            self.overrides.insert(to_id.to_owned(), space);
+        }
+    }
+
+    pub fn show_settings(&mut self, ui: &mut egui::Ui, id: &str, detected: ColorSpace) {
+        let input = self.input(id, detected);
+        ui.horizontal(|ui| {
+            ui.label("Color space:");
+            egui::ComboBox::from_id_salt("input_color_space")
+                .selected_text(input.label())
+                .show_ui(ui, |ui| {
+                    for space in ColorSpace::ALL {
+                        let label = if space == detected {
+                            format!("{} (detected)", space.label())
+                        } else {
+                            space.label().to_owned()
+                        };
+                        if ui.selectable_label(space == input, label).clicked() {
+                            if space == detected {
+                                self.overrides.remove(id);
+                            } else {
+                                self.overrides.insert(id.to_owned(), space);
+                            }
+                        }
+                    }
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.label("Working space:");
+            egui::ComboBox::from_id_salt("working_color_space")
+                .selected_text(self.working.label())
+                .show_ui(ui, |ui| {
+                    for space in ColorSpace::ALL {
+                        ui.selectable_value(&mut self.working, space, space.label());
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Images are converted into it, metrics and differences are computed in it. \
+                    Images shown through a colormap or as vectors keep their values as decoded",
+                );
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp() -> Pixels {
+        let mut buffer = image::RgbaImage::new(256, 1);
+        for (x, _, pixel) in buffer.enumerate_pixels_mut() {
+            *pixel = image::Rgba([x as u8, x as u8, x as u8, 255]);
+        }
+        Pixels::Rgba8(buffer)
+    }
+
+    /// The header of an EXR with only a chromaticities attribute
+    fn exr_header(chromaticities: [f32; 8]) -> Vec<u8> {
+        let mut bytes = b"\x76\x2f\x31\x01\x02\0\0\0".to_vec();
+        bytes.extend(b"chromaticities\0chromaticities\0");
+        bytes.extend(32_u32.to_le_bytes());
+        bytes.extend(chromaticities.iter().flat_map(|value| value.to_le_bytes()));
+        bytes.push(0);
+        bytes
+    }
+
+    #[test]
+    fn exrs_are_tagged_by_all_of_their_chromaticities() {
+        let rec709 = [0.64, 0.33, 0.30, 0.60, 0.15, 0.06, 0.3127, 0.3290];
+        let ap1 = [0.713, 0.293, 0.165, 0.830, 0.128, 0.044, 0.32168, 0.33767];
+        let rec2020 = [0.708, 0.292, 0.170, 0.797, 0.131, 0.046, 0.3127, 0.3290];
+        assert_eq!(
+            detect_exr(&exr_header(rec709)),
+            Some(ColorSpace::LinearSrgb)
+        );
+        assert_eq!(detect_exr(&exr_header(ap1)), Some(ColorSpace::AcesCg));
+        // its red is close to that of ACEScg, but the other primaries are not
+        assert_eq!(detect_exr(&exr_header(rec2020)), None);
+    }
+
+    /// An iCCP chunk holding a profile with a copyright and a `desc` tag
+    fn iccp(desc: &[u8]) -> Vec<u8> {
+        let copyright = b"text\0\0\0\0Copyright 2020 Some Vendor\0";
+        let mut profile = vec![0; 128];
+        profile.extend(2_u32.to_be_bytes());
+        let first = 128 + 4 + 2 * 12;
+        for (signature, offset, size) in [
+            (b"cprt", first, copyright.len()),
+            (b"desc", first + copyright.len(), desc.len()),
+        ] {
+            profile.extend(signature);
+            profile.extend((offset as u32).to_be_bytes());
+            profile.extend((size as u32).to_be_bytes());
+        }
+        profile.extend(copyright);
+        profile.extend(desc);
+
+        let mut chunk = b"ICC Profile\0\0".to_vec();
+        chunk.extend(miniz_oxide::deflate::compress_to_vec_zlib(&profile, 6));
+        chunk
+    }
+
+    fn desc_v2(text: &str) -> Vec<u8> {
+        let mut desc = b"desc\0\0\0\0".to_vec();
+        desc.extend((text.len() as u32 + 1).to_be_bytes());
+        desc.extend(text.as_bytes());
+        desc.push(0);
+        desc
+    }
+
+    fn mluc_v4(text: &str) -> Vec<u8> {
+        let utf16: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let mut mluc = b"mluc\0\0\0\0".to_vec();
+        mluc.extend(1_u32.to_be_bytes());
+        mluc.extend(12_u32.to_be_bytes());
+        mluc.extend(b"enUS");
+        mluc.extend((utf16.len() as u32).to_be_bytes());
+        mluc.extend(28_u32.to_be_bytes());
+        mluc.extend(utf16);
+        mluc
+    }
+
+    #[test]
+    fn icc_profiles_are_recognized_by_their_description() {
+        assert_eq!(
+            detect_icc(&iccp(&desc_v2("sRGB IEC61966-2.1"))),
+            Some(ColorSpace::Srgb)
+        );
+        assert_eq!(
+            detect_icc(&iccp(&desc_v2("Display P3"))),
+            Some(ColorSpace::DisplayP3)
+        );
+        assert_eq!(
+            detect_icc(&iccp(&mluc_v4("Rec. ITU-R BT.2020"))),
+            Some(ColorSpace::Rec2020)
+        );
+        assert_eq!(detect_icc(&iccp(&mluc_v4("Camera RGB"))), None);
+    }
+
+    /// A PNG signature and a cICP chunk, without a checksum
+    fn png_cicp(primaries: u8, transfer: u8) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        bytes.extend(4_u32.to_be_bytes());
+        bytes.extend(b"cICP");
+        bytes.extend([primaries, transfer, 0, 1]);
+        bytes.extend([0; 4]);
+        bytes
+    }
+
+    #[test]
+    fn hdr_cicp_transfers_are_not_decoded_as_sdr() {
+        assert_eq!(detect_png(&png_cicp(9, 14)), Some(ColorSpace::Rec2020));
+        assert_eq!(detect_png(&png_cicp(1, 8)), Some(ColorSpace::LinearSrgb));
+        // PQ and HLG
+        assert_eq!(detect_png(&png_cicp(9, 16)), None);
+        assert_eq!(detect_png(&png_cicp(12, 18)), None);
+    }
+
+    #[test]
+    fn corrupt_lengths_are_not_detected() {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend(u32::MAX.to_be_bytes());
+        png.extend(b"cICP");
+        assert_eq!(detect_png(&png), None);
+
+        let mut exr = b"\x76\x2f\x31\x01\x02\0\0\0".to_vec();
+        exr.extend(b"owner\0string\0");
+        exr.extend(u32::MAX.to_le_bytes());
+        assert_eq!(detect_exr(&exr), None);
+    }
+
+    #[test]
+    fn linear_working_spaces_keep_every_shadow_level() {
+        let linear = ColorSpace::Srgb.convert(&ramp(), ColorSpace::LinearSrgb);
+        let reds: Vec<f32> = linear.rgba().map(|[r, ..]| r).collect();
+        assert!(reds.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let shown = ColorSpace::LinearSrgb.display(&linear);
+        assert_eq!(shown.pixels, ramp().quantize().pixels);
+    }
+}
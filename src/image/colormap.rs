@@ -0,0 +1,415 @@
+use crate::image::pixels::Pixels;
+
+/// Which channel of an image is interpreted as scalar data
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize,
+)]
+pub enum ScalarChannel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    #[default]
+    Luminance,
+}
+
+impl ScalarChannel {
+    pub const ALL: [Self; 5] = [
+        Self::Red,
+        Self::Green,
+        Self::Blue,
+        Self::Alpha,
+        Self::Luminance,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Red => "R",
+            Self::Green => "G",
+            Self::Blue => "B",
+            Self::Alpha => "A",
+            Self::Luminance => "Y",
+        }
+    }
+
+    pub fn long_label(self) -> &'static str {
+        match self {
+            Self::Red => "Red",
+            Self::Green => "Green",
+            Self::Blue => "Blue",
+            Self::Alpha => "Alpha",
+            Self::Luminance => "Luminance",
+        }
+    }
+
+    /// Extract the scalar value of the RGBA of a pixel
+    pub fn value(self, [r, g, b, a]: [f32; 4]) -> f32 {
+        match self {
+            Self::Red => r,
+            Self::Green => g,
+            Self::Blue => b,
+            Self::Alpha => a,
+            Self::Luminance => 0.2126 * r + 0.7152 * g + 0.0722 * b,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
+pub enum Colormap {
+    Grayscale,
+    Viridis,
+    Magma,
+    Inferno,
+    Turbo,
+    /// Blue-white-red for signed data, centered on the middle of the range, which the automatic
+    /// range puts at 0
+    Diverging,
+    /// User defined gradient
+    Custom,
+}
+
+impl Colormap {
+    pub const ALL: [Self; 7] = [
+        Self::Grayscale,
+        Self::Viridis,
+        Self::Magma,
+        Self::Inferno,
+        Self::Turbo,
+        Self::Diverging,
+        Self::Custom,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Grayscale => "Grayscale",
+            Self::Viridis => "Viridis",
+            Self::Magma => "Magma",
+            Self::Inferno => "Inferno",
+            Self::Turbo => "Turbo",
+            Self::Diverging => "Diverging",
+            Self::Custom => "Custom",
+        }
+    }
+
+    /// Range to map the automatic `range` of the data with, diverging maps are centered on 0
+    /// so that white is 0
+    pub fn auto_range(self, (low, high): (f32, f32)) -> (f32, f32) {
+        match self {
+            Self::Diverging => {
+                let extent = low.abs().max(high.abs());
+                (-extent, extent)
+            }
+            _ => (low, high),
+        }
+    }
+
+    /// Sample the colormap at `t` in [0, 1]
+    pub fn sample(self, t: f32, custom: &Gradient) -> egui::Color32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Grayscale => rgb([t, t, t]),
+            Self::Viridis => rgb(polynomial(t, &VIRIDIS)),
+            Self::Magma => rgb(polynomial(t, &MAGMA)),
+            Self::Inferno => rgb(polynomial(t, &INFERNO)),
+            Self::Turbo => rgb(turbo(t)),
+            Self::Diverging => sample_stops(&DIVERGING, t),
+            Self::Custom => custom.sample(t),
+        }
+    }
+}
+
+/// Piecewise linear gradient between color stops
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Gradient {
+    /// (position in [0, 1], color), sorted by position
+    pub stops: Vec<(f32, egui::Color32)>,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Self {
+            stops: vec![
+                (0.0, egui::Color32::BLACK),
+                (0.5, egui::Color32::from_rgb(230, 80, 30)),
+                (1.0, egui::Color32::WHITE),
+            ],
+        }
+    }
+}
+
+impl Gradient {
+    pub fn sample(&self, t: f32) -> egui::Color32 {
+        let (Some(first), Some(last)) = (self.stops.first(), self.stops.last()) else {
+            return egui::Color32::BLACK;
+        };
+        if t <= first.0 {
+            return first.1;
+        }
+        for pair in self.stops.windows(2) {
+            let [(t0, c0), (t1, c1)] = [pair[0], pair[1]];
+            if t <= t1 {
+                let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1.0 };
+                return c0.lerp_to_gamma(c1, f);
+            }
+        }
+        last.1
+    }
+
+    pub fn sort(&mut self) {
+        self.stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+
+    /// Editor for the gradient stops
+    pub fn show_editor(&mut self, ui: &mut egui::Ui) {
+        let mut remove = None;
+        let mut editing = false;
+        for (i, (pos, color)) in self.stops.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                let response = ui.add(egui::DragValue::new(pos).range(0.0..=1.0).speed(0.01));
+                editing |= response.dragged() || response.has_focus();
+                ui.color_edit_button_srgba(color);
+                if ui.small_button("🗑").clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove {
+            if self.stops.len() > 2 {
+                self.stops.remove(i);
+            }
+        }
+        if ui.small_button("➕ Add stop").clicked() {
+            self.stops.push((1.0, egui::Color32::WHITE));
+        }
+        // sorting while a position is dragged or typed would move another stop under it
+        if !editing {
+            self.sort();
+        }
+    }
+}
+
+/// Paint a horizontal or vertical strip showing the colormap, low values at the bottom/left
+pub fn paint_colormap_strip(
+    painter: &egui::Painter,
+    rect: egui::Rect,
+    colormap: Colormap,
+    custom: &Gradient,
+    vertical: bool,
+) {
+    const SEGMENTS: usize = 64;
+    let mut mesh = egui::Mesh::default();
+    for i in 0..=SEGMENTS {
+        let t = i as f32 / SEGMENTS as f32;
+        let color = colormap.sample(t, custom);
+        let (a, b) = if vertical {
+            let y = egui::lerp(rect.bottom()..=rect.top(), t);
+            (egui::pos2(rect.left(), y), egui::pos2(rect.right(), y))
+        } else {
+            let x = egui::lerp(rect.left()..=rect.right(), t);
+            (egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom()))
+        };
+        mesh.colored_vertex(a, color);
+        mesh.colored_vertex(b, color);
+        if i > 0 {
+            let base = (2 * i) as u32;
+            mesh.add_triangle(base - 2, base - 1, base);
+            mesh.add_triangle(base - 1, base, base + 1);
+        }
+    }
+    painter.add(mesh);
+}
+
+/// Value of `channel` at the given percentiles (in [0, 100]) of the image, in the units of the
+/// values, e.g. metres for a depth map. Values that are not finite are ignored
+pub fn percentile_range(image: &Pixels, channel: ScalarChannel, low: f32, high: f32) -> (f32, f32) {
+    const BINS: usize = 1024;
+    let values = || {
+        image
+            .rgba()
+            .map(move |rgba| channel.value(rgba))
+            .filter(|value| value.is_finite())
+    };
+    let (min, max) = values().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+        (min.min(value), max.max(value))
+    });
+    if min > max {
+        return (0.0, 1.0);
+    }
+    if max <= min {
+        return (min, max);
+    }
+
+    let scale = (BINS - 1) as f32 / (max - min);
+    let mut histogram = [0usize; BINS];
+    for value in values() {
+        let bin = ((value - min) * scale).round() as usize;
+        histogram[bin.min(BINS - 1)] += 1;
+    }
+
+    let total = histogram.iter().sum::<usize>() as f32;
+    let find = |percentile: f32| {
+        let target = (percentile / 100.0).clamp(0.0, 1.0) * total;
+        let mut count = 0.0;
+        for (bin, n) in histogram.iter().enumerate() {
+            count += *n as f32;
+            if count >= target && count > 0.0 {
+                return min + bin as f32 / scale;
+            }
+        }
+        max
+    };
+
+    (find(low), find(high))
+}
+
+/// Fully resolved colormap stage, used as the cache key of mapped textures
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScalarMapping {
+    pub channel: ScalarChannel,
+    pub colormap: Colormap,
+    pub gradient: Gradient,
+    /// Values mapped to the start and end of the colormap
+    pub range: (f32, f32),
+}
+
+impl ScalarMapping {
+    /// Map the channel of `image` through the colormap
+    pub fn apply(&self, image: &Pixels) -> egui::ColorImage {
+        let (min, max) = self.range;
+        let scale = if max > min { 1.0 / (max - min) } else { 0.0 };
+
+        // Build a lookup table, finer than 8 bit output needs
+        let lut: Vec<egui::Color32> = (0..1024)
+            .map(|i| self.colormap.sample(i as f32 / 1023.0, &self.gradient))
+            .collect();
+
+        let pixels = image
+            .rgba()
+            .map(|rgba| {
+                let t = ((self.channel.value(rgba) - min) * scale).clamp(0.0, 1.0);
+                lut[(t * 1023.0).round() as usize]
+            })
+            .collect();
+
+        egui::ColorImage::new(image.size(), pixels)
+    }
+}
+
+fn rgb([r, g, b]: [f32; 3]) -> egui::Color32 {
+    let to_u8 = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    egui::Color32::from_rgb(to_u8(r), to_u8(g), to_u8(b))
+}
+
+/// Evaluate a degree 6 polynomial fit of a matplotlib colormap
+fn polynomial(t: f32, coefficients: &[[f32; 3]; 7]) -> [f32; 3] {
+    let mut out = [0.0; 3];
+    for (c, channel) in out.iter_mut().enumerate() {
+        *channel = coefficients
+            .iter()
+            .rev()
+            .fold(0.0, |acc, coefficient| acc * t + coefficient[c]);
+    }
+    out
+}
+
+/// Polynomial approximation of Google's turbo colormap
+fn turbo(t: f32) -> [f32; 3] {
+    let v4 = [1.0, t, t * t, t * t * t];
+    let v2 = [v4[2] * v4[2], v4[3] * v4[2]];
+    let dot = |a: [f32; 4], b: [f32; 2]| {
+        a[0] * v4[0] + a[1] * v4[1] + a[2] * v4[2] + a[3] * v4[3] + b[0] * v2[0] + b[1] * v2[1]
+    };
+    [
+        dot(
+            [0.135_721_4, 4.615_392_6, -42.660_324, 132.131_08],
+            [-152.942_4, 59.286_38],
+        ),
+        dot(
+            [0.091_402_61, 2.194_188_4, 4.842_966_6, -14.185_033],
+            [4.277_298_5, 2.829_566],
+        ),
+        dot(
+            [0.106_673_3, 12.641_946, -60.582_05, 110.362_77],
+            [-89.903_11, 27.348_25],
+        ),
+    ]
+}
+
+const VIRIDIS: [[f32; 3]; 7] = [
+    [0.277_727_33, 0.005_407_344_5, 0.334_099_8],
+    [0.105_093_04, 1.404_613_5, 1.384_590_2],
+    [-0.330_861_83, 0.214_847_56, 0.095_095_16],
+    [-4.634_230_6, -5.799_101, -19.332_441],
+    [6.228_27, 14.179_933, 56.690_55],
+    [4.776_385, -13.745_145, -65.353_03],
+    [-5.435_456, 4.645_852_6, 26.312_435],
+];
+
+const MAGMA: [[f32; 3]; 7] = [
+    [-0.002_136_485, -0.000_749_655, -0.005_386_128],
+    [0.251_660_54, 0.677_523_2, 2.494_026_6],
+    [8.353_717, -3.577_719_4, 0.314_467_9],
+    [-27.668_733, 14.264_731, -13.649_213],
+    [52.176_14, -27.943_606, 12.944_169],
+    [-50.768_524, 29.046_583, 4.234_153],
+    [18.655_705, -11.489_774, -5.601_961_5],
+];
+
+const INFERNO: [[f32; 3]; 7] = [
+    [0.000_218_940_37, 0.001_651_004_6, -0.019_480_899],
+    [0.106_513_42, 0.563_956_4, 3.932_712_4],
+    [11.602_493, -3.972_854, -15.942_394],
+    [-41.703_995, 17.436_4, 44.354_145],
+    [77.162_94, -33.402_36, -81.807_31],
+    [-71.319_43, 32.626_064, 73.209_52],
+    [25.131_126, -12.242_669, -23.070_326],
+];
+
+/// Moreland's cool-warm diverging map
+const DIVERGING: [[u8; 3]; 5] = [
+    [59, 76, 192],
+    [141, 176, 254],
+    [221, 221, 221],
+    [244, 154, 123],
+    [180, 4, 38],
+];
+
+/// Sample evenly spaced color stops
+fn sample_stops(stops: &[[u8; 3]], t: f32) -> egui::Color32 {
+    let scaled = t * (stops.len() - 1) as f32;
+    let i = (scaled.floor() as usize).min(stops.len() - 2);
+    let [r0, g0, b0] = stops[i];
+    let [r1, g1, b1] = stops[i + 1];
+    egui::Color32::from_rgb(r0, g0, b0)
+        .lerp_to_gamma(egui::Color32::from_rgb(r1, g1, b1), scaled - i as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_of_float_depth_are_in_its_units() {
+        let depth = image::Rgba32FImage::from_fn(100, 1, |x, _| {
+            let metres = 2.0 + x as f32 * 0.1;
+            image::Rgba([metres, metres, metres, 1.0])
+        });
+        let (min, max) = percentile_range(&depth.into(), ScalarChannel::Red, 0.0, 100.0);
+        assert!((min - 2.0).abs() < 0.01, "{min}");
+        assert!((max - 11.9).abs() < 0.01, "{max}");
+    }
+
+    #[test]
+    fn diverging_ranges_are_centered_on_zero() {
+        assert_eq!(Colormap::Diverging.auto_range((-0.2, 0.8)), (-0.8, 0.8));
+        assert_eq!(Colormap::Diverging.auto_range((-3.0, -1.0)), (-3.0, 3.0));
+        assert_eq!(Colormap::Viridis.auto_range((-0.2, 0.8)), (-0.2, 0.8));
+    }
+
+    #[test]
+    fn constant_images_have_an_empty_range() {
+        let image = image::Rgba32FImage::from_pixel(4, 4, image::Rgba([3.5, 0.0, 0.0, 1.0]));
+        let range = percentile_range(&image.into(), ScalarChannel::Red, 1.0, 99.0);
+        assert_eq!(range, (3.5, 3.5));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::image::pixels::Pixels;
+
+/// Tile sizes offered as presets, with what they are for
+const TILE_PRESETS: [(usize, &str); 3] = [(4, "BCn blocks"), (8, "JPEG blocks"), (64, "Tiles")];
+
+/// Lines between pixels when zoomed in, and between blocks or tiles at any zoom
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct GridOverlay {
+    pub pixel_grid: bool,
+    /// Zoom, in screen points per pixel, from which the pixel grid is drawn
+    pub min_zoom: f32,
+    /// Print the value of each pixel inside it when the cells are large enough
+    pub pixel_values: bool,
+    pub tile_grid: bool,
+    /// Width and height of the tiles in pixels
+    pub tile_size: [usize; 2],
+}
+
+impl Default for GridOverlay {
+    fn default() -> Self {
+        Self {
+            pixel_grid: true,
+            min_zoom: 8.0,
+            pixel_values: false,
+            tile_grid: false,
+            tile_size: [4, 4],
+        }
+    }
+}
+
+impl GridOverlay {
+    /// Draw the grids over the part of `image_rect` inside `clip`, the image is shown at `zoom`
+    pub fn paint(
+        &self,
+        painter: &egui::Painter,
+        image: &Pixels,
+        image_rect: egui::Rect,
+        clip: egui::Rect,
+        zoom: f32,
+    ) {
+        let visible = image_rect.intersect(clip);
+        if !visible.is_positive() {
+            return;
+        }
+        // range of visible pixels
+        let [width, height] = image.size();
+        let first = ((visible.min - image_rect.min) / zoom).floor();
+        let last = ((visible.max - image_rect.min) / zoom).ceil();
+        let x_range = (first.x.max(0.0) as usize)..(last.x as usize).min(width);
+        let y_range = (first.y.max(0.0) as usize)..(last.y as usize).min(height);
+        let x = |px: usize| image_rect.left() + px as f32 * zoom;
+        let y = |py: usize| image_rect.top() + py as f32 * zoom;
+
+        if self.pixel_grid && zoom >= self.min_zoom {
+            let stroke = egui::Stroke::new(
+                1.0,
+                egui::Color32::from_rgba_unmultiplied(128, 128, 128, 90),
+            );
+            for px in x_range.start..=x_range.end {
+                painter.vline(x(px), visible.y_range(), stroke);
+            }
+            for py in y_range.start..=y_range.end {
+                painter.hline(visible.x_range(), y(py), stroke);
+            }
+        }
+
+        if self.tile_grid {
+            let stroke =
+                egui::Stroke::new(1.5, egui::Color32::from_rgba_unmultiplied(255, 0, 200, 180));
+            let [tile_width, tile_height] = self.tile_size.map(|size| size.max(1));
+            // too dense to tell apart below a few points per tile
+            if tile_width as f32 * zoom >= 4.0 {
+                for px in
+                    (x_range.start.next_multiple_of(tile_width)..=x_range.end).step_by(tile_width)
+                {
+                    painter.vline(x(px), visible.y_range(), stroke);
+                }
+            }
+            if tile_height as f32 * zoom >= 4.0 {
+                for py in
+                    (y_range.start.next_multiple_of(tile_height)..=y_range.end).step_by(tile_height)
+                {
+                    painter.hline(visible.x_range(), y(py), stroke);
+                }
+            }
+        }
+
+        if self.pixel_values {
+            paint_values(painter, image, x_range, y_range, image_rect.min, zoom);
+        }
+    }
+
+    pub fn show_settings(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.pixel_grid, "Pixel grid");
+            ui.add_enabled(
+                self.pixel_grid,
+                egui::DragValue::new(&mut self.min_zoom)
+                    .range(2.0..=64.0)
+                    .prefix("from ")
+                    .suffix("x"),
+            );
+            ui.checkbox(&mut self.pixel_values, "Values")
+                .on_hover_text("Print the value of each pixel once zoomed in far enough");
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.tile_grid, "Tiles");
+            for (size, hint) in TILE_PRESETS {
+                let selected = self.tile_size == [size, size];
+                if ui
+                    .selectable_label(selected, format!("{size}x{size}"))
+                    .on_hover_text(hint)
+                    .clicked()
+                {
+                    self.tile_size = [size, size];
+                    self.tile_grid = true;
+                }
+            }
+            ui.add(egui::DragValue::new(&mut self.tile_size[0]).range(1..=4096));
+            ui.label("x");
+            ui.add(egui::DragValue::new(&mut self.tile_size[1]).range(1..=4096));
+        });
+    }
+}
+
+/// RGBA of each pixel as stored, 8 bit values as integers and floats with three decimals, the
+/// alpha only when not opaque
+fn paint_values(
+    painter: &egui::Painter,
+    image: &Pixels,
+    x_range: std::ops::Range<usize>,
+    y_range: std::ops::Range<usize>,
+    origin: egui::Pos2,
+    zoom: f32,
+) {
+    let is_float = matches!(image, Pixels::Rgba32F(_));
+    // floats are wider than 8 bit values
+    let font_size = (zoom / if is_float { 8.0 } else { 5.0 }).min(14.0);
+    // four lines of text must fit in a cell
+    if font_size < 8.0 {
+        return;
+    }
+    let font = egui::FontId::monospace(font_size);
+    for py in y_range {
+        for px in x_range.clone() {
+            let rgba = image.get(px, py);
+            let channels = if rgba[3] >= 1.0 { 3 } else { 4 };
+            let text = rgba[..channels]
+                .iter()
+                .map(|&c| {
+                    if is_float {
+                        format!("{c:.3}")
+                    } else {
+                        format!("{}", (c * 255.0).round())
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let [r, g, b, _] = rgba;
+            let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            let color = if luminance > 0.5 {
+                egui::Color32::BLACK
+            } else {
+                egui::Color32::WHITE
+            };
+            let center = origin + (egui::vec2(px as f32, py as f32) + egui::vec2(0.5, 0.5)) * zoom;
+            painter.text(
+                center,
+                egui::Align2::CENTER_CENTER,
+                text,
+                font.clone(),
+                color,
+            );
+        }
+    }
+}
@@ -1,41 +1,636 @@
-use anyhow::bail;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use crate::asset::Asset;
-use anyhow::{Context, Ok, Result};
-use egui::{self};
+use crate::asset::{Asset, dropped_file_from_path, format_bytes, read_file};
+use crate::image::color_space::ColorSpace;
+use crate::image::colormap::{ScalarChannel, percentile_range};
+use crate::image::mapping::Mapping;
+use crate::image::pixels::Pixels;
+use crate::image::tiles::TiledTexture;
+use crate::image::tiling::SeamStats;
+use crate::image::vector::{FlowSettings, NormalSettings};
+use crate::sequence::{Prefetch, Sequence};
+use crate::thumbnail::Thumbnail;
+use anyhow::{Context as _, Ok, Result, bail};
+
+/// Decodes the bytes of a file into pixels
+pub type ImageDecodeFn = fn(&[u8]) -> Result<Pixels>;
+
+/// Decodes the bytes of an animated file into its frames and the seconds each is shown for,
+/// `None` if the file is not animated, which is told without decoding it
+pub type AnimationDecodeFn = fn(&[u8]) -> Result<Option<Vec<(Pixels, f32)>>>;
+
+/// Largest width or height decoded, a 16K texture atlas is a quarter of it
+const MAX_DIMENSION: u32 = 1 << 16;
+
+/// Bytes a single decode may allocate, kept at the memory budget by the app
+static DECODE_BUDGET: AtomicU64 = AtomicU64::new(1 << 30);
+
+/// Reject files whose decoding would allocate more than `bytes`
+pub fn set_decode_budget(bytes: u64) {
+    DECODE_BUDGET.store(bytes, Ordering::Relaxed);
+}
+
+/// Next version given to the pixels of an image, unique across images
+static NEXT_PIXELS_VERSION: AtomicU64 = AtomicU64::new(0);
+
+fn next_pixels_version() -> u64 {
+    NEXT_PIXELS_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Limits of the `image` crate decoders, from the decode budget
+fn decode_limits() -> image::Limits {
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(DECODE_BUDGET.load(Ordering::Relaxed));
+    limits
+}
+
+/// Decoded frames and the seconds each is shown for, failing once they take more than `budget`
+/// bytes
+fn collect_frames(frames: image::Frames<'_>, budget: u64) -> Result<Vec<(Pixels, f32)>> {
+    let mut total = 0;
+    let mut collected = vec![];
+    for frame in frames {
+        let frame = frame.context("Failed to decode the frames")?;
+        total += frame.buffer().len() as u64;
+        if total > budget {
+            bail!(
+                "The frames take more than the memory budget of {}",
+                format_bytes(budget as usize)
+            );
+        }
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        let seconds = numerator as f32 / denominator.max(1) as f32 / 1000.0;
+        collected.push((Pixels::Rgba8(frame.into_buffer()), seconds));
+    }
+    Ok(collected)
+}
+
+/// Channel and percentiles an auto range was computed for
+type RangeKey = (ScalarChannel, f32, f32);
+
+/// How the pixels of an image are interpreted
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Interpretation {
+    #[default]
+    Color,
+    Normal(NormalSettings),
+    Flow(FlowSettings),
+}
+
+impl Interpretation {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Color => "Color",
+            Self::Normal(_) => "Normal map",
+            Self::Flow(_) => "Flow",
+        }
+    }
+
+    /// Whether the values are data rather than colors, e.g. vectors, and are kept as decoded
+    pub fn is_data(&self) -> bool {
+        !matches!(self, Self::Color)
+    }
+}
 
 pub struct ImageAsset {
     pub id: String,
+    /// Pixels in the working space, metrics and differences are computed on them
+    pub pixels: Pixels,
+    /// Changes whenever `pixels` are replaced, e.g. by a reload or another frame
+    pixels_version: u64,
+    /// `pixels` as shown on screen, quantized to 8 bit sRGB
     pub image: egui::ColorImage,
-    texture_cache: HashMap<egui::TextureFilter, egui::TextureHandle>,
+    pub interpretation: Interpretation,
+    tiles: TiledTexture,
+    /// The image passed through the last mapping stage, and its tiles
+    mapped: Option<Box<(Mapping, egui::ColorImage, TiledTexture)>>,
+    range_cache: Option<(RangeKey, (f32, f32))>,
+    /// Seams of the shown image, computed once tiling highlights them
+    seam_stats: Option<SeamStats>,
     file_path: Option<PathBuf>,
+    /// Encoded file, kept to decode the image again when it has no path
+    source: Option<Arc<[u8]>>,
+    decode: ImageDecodeFn,
+    /// Whether the pixels were freed to stay within the memory budget
+    evicted: bool,
+    /// Whether the pixels are still in the color spaces before the last change, shown until
+    /// they are decoded again
+    stale: bool,
+    /// Whether the file was handed out to be loaded again for the evicted or stale pixels
+    redecoding: bool,
+    /// Load that decodes the file handed out by `redecode`, once it was queued
+    redecode_load: Option<u64>,
+    /// Pass the image was last painted in
+    last_shown: u64,
+    /// Kept when the pixels are evicted
+    thumbnail: Thumbnail,
+    /// Frames of a sequence or video, `pixels` are the current one
+    sequence: Option<Sequence>,
+    /// Frame of the sequence in `pixels`
+    frame: usize,
+    /// Frames of a sequence of files decoded ahead of `frame`
+    prefetch: Prefetch,
+    /// Decodes the frames again when the file of an animation changes
+    decode_frames: Option<AnimationDecodeFn>,
+    /// Color space declared by the file, sRGB if it declares none
+    detected_color_space: ColorSpace,
+    /// Color space the decoded pixels are taken to be in
+    color_space: ColorSpace,
+    /// Color space `pixels` were converted into
+    working_space: ColorSpace,
 }
 
 impl ImageAsset {
-    pub fn get_texture(
+    /// An image decoded from the `bytes` of `file`
+    pub fn from_file(
+        file: &egui::DroppedFile,
+        bytes: Arc<[u8]>,
+        decode: ImageDecodeFn,
+    ) -> Result<Self> {
+        let pixels = decode(&bytes)?;
+        let color_space = ColorSpace::detect(&bytes).unwrap_or_default();
+        let image = color_space.display(&pixels);
+        Ok(Self {
+            thumbnail: Thumbnail::of_image(&image),
+            pixels,
+            pixels_version: next_pixels_version(),
+            image,
+            id: file.name.clone(),
+            interpretation: Interpretation::default(),
+            tiles: TiledTexture::default(),
+            mapped: None,
+            range_cache: None,
+            seam_stats: None,
+            file_path: file.path.clone(),
+            source: file.path.is_none().then_some(bytes),
+            decode,
+            evicted: false,
+            stale: false,
+            redecoding: false,
+            redecode_load: None,
+            last_shown: 0,
+            sequence: None,
+            frame: 0,
+            prefetch: Prefetch::default(),
+            decode_frames: None,
+            detected_color_space: color_space,
+            color_space,
+            working_space: color_space,
+        })
+    }
+
+    /// An animated GIF, PNG or WebP of `file` with its decoded `frames`, showing the first one
+    pub fn from_animation(
+        file: &egui::DroppedFile,
+        bytes: Arc<[u8]>,
+        frames: Vec<(Pixels, f32)>,
+        decode_frames: AnimationDecodeFn,
+    ) -> Result<Self> {
+        let sequence = Sequence::animation(frames);
+        let pixels = sequence.decode_frame(0)?;
+        let color_space = ColorSpace::detect(&bytes).unwrap_or_default();
+        let image = color_space.display(&pixels);
+        Ok(Self {
+            thumbnail: Thumbnail::of_image(&image),
+            pixels,
+            pixels_version: next_pixels_version(),
+            image,
+            id: file.name.clone(),
+            interpretation: Interpretation::default(),
+            tiles: TiledTexture::default(),
+            mapped: None,
+            range_cache: None,
+            seam_stats: None,
+            file_path: file.path.clone(),
+            source: file.path.is_none().then_some(bytes),
+            decode: |_| bail!("Frames are decoded by their sequence"),
+            evicted: false,
+            stale: false,
+            redecoding: false,
+            redecode_load: None,
+            last_shown: 0,
+            sequence: Some(sequence),
+            frame: 0,
+            prefetch: Prefetch::default(),
+            decode_frames: Some(decode_frames),
+            detected_color_space: color_space,
+            color_space,
+            working_space: color_space,
+        })
+    }
+
+    /// A sequence loaded from `file`, a pattern of numbered files or a video, showing its first frame.
+    /// Its frames are in `color_space`
+    pub fn from_sequence(
+        file: &egui::DroppedFile,
+        sequence: Sequence,
+        color_space: ColorSpace,
+    ) -> Result<Self> {
+        let pixels = sequence.decode_frame(0)?;
+        let image = color_space.display(&pixels);
+        Ok(Self {
+            thumbnail: Thumbnail::of_image(&image),
+            pixels,
+            pixels_version: next_pixels_version(),
+            image,
+            id: file.name.clone(),
+            interpretation: Interpretation::default(),
+            tiles: TiledTexture::default(),
+            mapped: None,
+            range_cache: None,
+            seam_stats: None,
+            file_path: file.path.clone(),
+            source: None,
+            decode: |_| bail!("Frames are decoded by their sequence"),
+            evicted: false,
+            stale: false,
+            redecoding: false,
+            redecode_load: None,
+            last_shown: 0,
+            sequence: Some(sequence),
+            frame: 0,
+            prefetch: Prefetch::default(),
+            decode_frames: None,
+            detected_color_space: color_space,
+            color_space,
+            working_space: color_space,
+        })
+    }
+
+    /// A copy with the same pixels and source, its textures are uploaded again
+    pub fn duplicate(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            pixels: self.pixels.clone(),
+            pixels_version: self.pixels_version,
+            image: self.image.clone(),
+            interpretation: self.interpretation,
+            tiles: TiledTexture::default(),
+            mapped: None,
+            range_cache: self.range_cache,
+            seam_stats: None,
+            file_path: self.file_path.clone(),
+            source: self.source.clone(),
+            decode: self.decode,
+            evicted: self.evicted,
+            stale: false,
+            redecoding: false,
+            redecode_load: None,
+            last_shown: 0,
+            thumbnail: self.thumbnail.clone(),
+            sequence: self.sequence.clone(),
+            frame: self.frame,
+            prefetch: Prefetch::default(),
+            decode_frames: self.decode_frames,
+            detected_color_space: self.detected_color_space,
+            color_space: self.color_space,
+            working_space: self.working_space,
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.sequence.as_ref().map_or(1, Sequence::len)
+    }
+
+    pub fn sequence(&self) -> Option<&Sequence> {
+        self.sequence.as_ref()
+    }
+
+    /// The frames to compare in the working space, a still image is a sequence of one
+    pub fn frames(&self) -> Sequence {
+        match &self.sequence {
+            Some(sequence) => sequence
+                .clone()
+                .converted(self.color_space, self.working_space),
+            None => Sequence::still(self.pixels.clone()),
+        }
+    }
+
+    /// Tells caches of the pixels apart, it changes whenever they are replaced
+    pub fn pixels_version(&self) -> u64 {
+        self.pixels_version
+    }
+
+    pub fn detected_color_space(&self) -> ColorSpace {
+        self.detected_color_space
+    }
+
+    /// Color space the pixels are shown and compared in
+    pub fn working_space(&self) -> ColorSpace {
+        self.working_space
+    }
+
+    /// Take the pixels to be in `input` and convert them into `working`.
+    /// Pixels that are no longer as decoded are decoded again in the background, see `redecode`
+    pub fn set_color_spaces(&mut self, input: ColorSpace, working: ColorSpace) -> Result<()> {
+        if (input, working) == (self.color_space, self.working_space) {
+            return Ok(());
+        }
+        let as_decoded = !self.stale && self.color_space == self.working_space;
+        self.color_space = input;
+        self.working_space = working;
+        self.prefetch.clear();
+        if self.evicted {
+            return Ok(());
+        }
+        let pixels = if as_decoded {
+            input.convert(&self.pixels, working)
+        } else if self.sequence.as_ref().is_none_or(Sequence::reads_files) {
+            self.stale = true;
+            return Ok(());
+        } else {
+            // the frames of an animation are in memory
+            self.decode_converted()?
+        };
+        self.set_pixels(pixels);
+        self.thumbnail = Thumbnail::of_image(&self.image);
+        Ok(())
+    }
+
+    /// Show another frame of a sequence, beyond the last one the last one is shown. Frames read
+    /// from files are decoded ahead on a worker, the shown frame stays until this one is ready.
+    /// A failure is reported once, the previous frame stays
+    pub fn set_frame(&mut self, ctx: &egui::Context, frame: usize) -> Result<()> {
+        let frame = frame.min(self.frame_count() - 1);
+        if frame == self.frame {
+            return Ok(());
+        }
+        // evicted pixels are decoded again, at this frame, once shown
+        if self.evicted {
+            self.frame = frame;
+            return Ok(());
+        }
+        let frames = self.frames();
+        let pixels = if frames.reads_files() {
+            match self.prefetch.take(ctx, &frames, frame) {
+                Some(pixels) => pixels,
+                None => return Ok(()),
+            }
+        } else {
+            frames.decode_frame(frame)
+        };
+        self.frame = frame;
+        self.set_pixels(pixels?);
+        Ok(())
+    }
+
+    /// Replace the pixels, dropping everything derived from them
+    fn set_pixels(&mut self, pixels: Pixels) {
+        self.image = self.working_space.display(&pixels);
+        self.pixels = pixels;
+        self.pixels_version = next_pixels_version();
+        self.tiles.clear();
+        self.mapped = None;
+        self.range_cache = None;
+        self.seam_stats = None;
+        self.evicted = false;
+        self.stale = false;
+    }
+
+    /// Decode the pixels and convert them into the working space
+    fn decode_converted(&self) -> Result<Pixels> {
+        let pixels = self.decode_source()?;
+        Ok(self.color_space.convert(&pixels, self.working_space))
+    }
+
+    /// Decode the pixels as they are in the file, of the current frame for a sequence
+    fn decode_source(&self) -> Result<Pixels> {
+        if let Some(sequence) = &self.sequence {
+            return sequence.decode_frame(self.frame);
+        }
+        (self.decode)(&self.source_bytes()?)
+    }
+
+    /// Encoded file, as kept or read from disk
+    fn source_bytes(&self) -> Result<Arc<[u8]>> {
+        match (&self.source, &self.file_path) {
+            (Some(bytes), _) => Ok(bytes.clone()),
+            (None, Some(path)) => Ok(read_file(path)?),
+            (None, None) => bail!("No source to decode {} from", self.id),
+        }
+    }
+
+    /// Decode the frames of an animation from its file
+    fn decode_animation(&self, decode_frames: AnimationDecodeFn) -> Result<Sequence> {
+        let frames = decode_frames(&self.source_bytes()?)?
+            .filter(|frames| !frames.is_empty())
+            .context("The file is no longer animated")?;
+        Ok(Sequence::animation(frames))
+    }
+
+    /// Paint the `source` region of the image, in pixels, into `dest` on screen.
+    /// With a mapping the mapped image is painted, it is recomputed when the mapping changes
+    pub fn paint(
         &mut self,
-        ctx: &egui::Context,
+        painter: &egui::Painter,
         filter_mode: egui::TextureFilter,
-    ) -> &egui::TextureHandle {
-        self.texture_cache.entry(filter_mode).or_insert_with(|| {
-            let opt = egui::TextureOptions {
-                magnification: filter_mode,
-                minification: filter_mode,
+        mapping: Option<&Mapping>,
+        source: egui::Rect,
+        dest: egui::Rect,
+    ) {
+        self.last_shown = painter.ctx().cumulative_pass_nr();
+        let Some(mapping) = mapping else {
+            self.tiles
+                .paint(painter, &self.image, filter_mode, source, dest);
+            return;
+        };
+
+        if self
+            .mapped
+            .as_ref()
+            .is_none_or(|mapped| mapped.0 != *mapping)
+        {
+            self.mapped = None;
+        }
+        let (_, mapped_image, mapped_tiles) = &mut **self.mapped.get_or_insert_with(|| {
+            Box::new((
+                mapping.clone(),
+                mapping.apply(&self.pixels),
+                TiledTexture::default(),
+            ))
+        });
+        mapped_tiles.paint(painter, mapped_image, filter_mode, source, dest);
+    }
+
+    /// Range of `channel` between the `low` and `high` percentiles, cached per asset
+    pub fn percentile_range(&mut self, channel: ScalarChannel, low: f32, high: f32) -> (f32, f32) {
+        let key = (channel, low, high);
+        match self.range_cache {
+            Some((cached_key, range)) if cached_key == key => range,
+            _ => {
+                let range = percentile_range(&self.pixels, channel, low, high);
+                self.range_cache = Some((key, range));
+                range
+            }
+        }
+    }
+
+    /// How well the opposite edges of the shown image continue into each other, cached per asset
+    pub fn seam_stats(&mut self) -> Option<&SeamStats> {
+        if self.seam_stats.is_none() {
+            self.seam_stats = SeamStats::of_image(&self.image);
+        }
+        self.seam_stats.as_ref()
+    }
+
+    /// Decode a format supported by the `image` crate, in 8 bits if the file has 8 bits per
+    /// channel and as floats otherwise, e.g. for EXRs and HDRs
+    pub fn image_from_bytes(bytes: &[u8], format: image::ImageFormat) -> Result<Pixels> {
+        let mut reader = image::ImageReader::with_format(std::io::Cursor::new(bytes), format);
+        // the default allocation limit rejects large scans, allow as much as the memory budget
+        reader.limits(decode_limits());
+        let image = reader.decode().context("Failed to load image")?;
+        Ok(Pixels::from(image))
+    }
+
+    /// Decode the frames of an animated GIF, PNG or WebP, `None` for a still PNG or WebP
+    pub fn frames_from_bytes(
+        bytes: &[u8],
+        format: image::ImageFormat,
+    ) -> Result<Option<Vec<(Pixels, f32)>>> {
+        use image::codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder};
+        use image::{AnimationDecoder as _, ImageDecoder as _};
+
+        let cursor = std::io::Cursor::new(bytes);
+        let frames = match format {
+            image::ImageFormat::Gif => {
+                if !gif_is_animated(bytes) {
+                    return Ok(None);
+                }
+                let mut decoder = GifDecoder::new(cursor)?;
+                decoder.set_limits(decode_limits())?;
+                decoder.into_frames()
+            }
+            image::ImageFormat::Png => {
+                let decoder = PngDecoder::with_limits(cursor, decode_limits())?;
+                if !decoder.is_apng()? {
+                    return Ok(None);
+                }
+                decoder.apng()?.into_frames()
+            }
+            image::ImageFormat::WebP => {
+                let mut decoder = WebPDecoder::new(cursor)?;
+                if !decoder.has_animation() {
+                    return Ok(None);
+                }
+                decoder.set_limits(decode_limits())?;
+                decoder.into_frames()
+            }
+            _ => return Ok(None),
+        };
+        // the limits apply to each frame, all of them together must fit in the budget too
+        collect_frames(frames, DECODE_BUDGET.load(Ordering::Relaxed)).map(Some)
+    }
+
+    /// Decode the image again if its pixels were evicted
+    pub fn ensure_decoded(&mut self) -> Result<()> {
+        if !self.evicted && !self.stale {
+            return Ok(());
+        }
+        // a failure is reported once, the image stays empty
+        self.evicted = false;
+        self.stale = false;
+        if let (Some(sequence), Some(decode_frames)) = (&self.sequence, self.decode_frames) {
+            if sequence.is_evicted() {
+                self.sequence = Some(self.decode_animation(decode_frames)?);
+            }
+        }
+        let pixels = self.decode_converted()?;
+        self.set_pixels(pixels);
+        Ok(())
+    }
+
+    /// Start decoding the evicted or stale pixels again without blocking, call it until
+    /// `is_decoded`. Frames of file sequences are decoded by the prefetch. For anything else
+    /// the file to load again is returned, once, and is swapped in with `take_reloaded`.
+    /// A failure is reported once, the image stays empty or as it was
+    pub fn redecode(&mut self, ctx: &egui::Context) -> Result<Option<egui::DroppedFile>> {
+        if self.is_decoded() {
+            return Ok(None);
+        }
+        if self.sequence.as_ref().is_some_and(Sequence::reads_files) {
+            let frames = self.frames();
+            if let Some(pixels) = self.prefetch.take(ctx, &frames, self.frame) {
+                self.evicted = false;
+                self.stale = false;
+                self.set_pixels(pixels?);
+            }
+            return Ok(None);
+        }
+        if std::mem::replace(&mut self.redecoding, true) {
+            return Ok(None);
+        }
+        Ok(Some(match (&self.file_path, &self.source) {
+            (Some(path), _) => dropped_file_from_path(path),
+            (None, Some(bytes)) => egui::DroppedFile {
+                name: self.id.clone(),
+                bytes: Some(bytes.clone()),
                 ..Default::default()
-            };
-            ctx.load_texture(&self.id, self.image.clone(), opt)
-        })
+            },
+            (None, None) => {
+                self.stop_redecode();
+                bail!("No source to decode {} from", self.id);
+            }
+        }))
+    }
+
+    /// Give up on the file handed out by `redecode`, the image stays empty or as it was
+    pub fn stop_redecode(&mut self) {
+        if self.redecoding {
+            self.redecoding = false;
+            self.redecode_load = None;
+            self.evicted = false;
+            self.stale = false;
+        }
+    }
+
+    /// Free the textures and derived images, and the pixels if they can be decoded again.
+    /// The frames of an animation are freed too, they are decoded again from its file
+    pub fn evict(&mut self) {
+        self.tiles.clear();
+        self.mapped = None;
+        self.prefetch.clear();
+        let has_source = self.source.is_some() || self.file_path.is_some();
+        if has_source || self.sequence.is_some() {
+            self.pixels = Pixels::default();
+            self.pixels_version = next_pixels_version();
+            self.image = egui::ColorImage::default();
+            self.seam_stats = None;
+            self.evicted = true;
+        }
+        if let Some(sequence) = self.sequence.as_mut().filter(|_| has_source) {
+            if self.decode_frames.is_some() {
+                sequence.evict();
+            }
+        }
+    }
+
+    /// Remember the load queued for the file handed out by `redecode`, its result is swapped
+    /// into this image and no other
+    pub fn set_redecode_load(&mut self, load: u64) {
+        self.redecode_load = Some(load);
+    }
+
+    /// Whether `load` decodes this image again
+    pub fn is_redecoded_by(&self, load: u64) -> bool {
+        self.redecode_load == Some(load)
+    }
+
+    pub fn is_evicted(&self) -> bool {
+        self.evicted
     }
 
-    pub fn image_from_bytes(bytes: &[u8]) -> Result<egui::ColorImage> {
-        let image = image::load_from_memory(bytes).context("Failed to load image")?;
-        let size = [image.width() as usize, image.height() as usize];
-        let image_buffer = image.to_rgba8();
-        let pixels = image_buffer.as_flat_samples();
-        let color_image = egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
-        Ok(color_image)
+    /// Whether the pixels are in memory and in the current color spaces
+    pub fn is_decoded(&self) -> bool {
+        !self.evicted && !self.stale
+    }
+
+    pub fn last_shown(&self) -> u64 {
+        self.last_shown
     }
 
     pub fn image_size(&self) -> egui::Vec2 {
@@ -43,23 +638,303 @@ impl ImageAsset {
     }
 }
 
-impl Asset for ImageAsset {
-    fn from_dropped_file(ctx: &egui::Context, file: &egui::DroppedFile) -> Result<Self>
-    where
-        Self: Sized,
-    {
-        let bytes = file.bytes.as_ref().context("No file data")?;
-        let color_image = Self::image_from_bytes(bytes)?;
-
-        Ok(ImageAsset {
-            image: color_image,
-            id: file.name.clone(),
-            texture_cache: HashMap::new(),
-            file_path: file.path.clone(),
-        })
+/// Whether a GIF holds more than one image, by walking its blocks without decoding them
+fn gif_is_animated(bytes: &[u8]) -> bool {
+    /// Offset past a chain of data sub-blocks, each prefixed with its length
+    fn skip_sub_blocks(bytes: &[u8], mut offset: usize) -> Option<usize> {
+        loop {
+            let length = usize::from(*bytes.get(offset)?);
+            offset += 1 + length;
+            if length == 0 {
+                return Some(offset);
+            }
+        }
+    }
+    /// Size of a color table, from the packed fields of a descriptor
+    fn color_table(packed: u8) -> usize {
+        if packed & 0x80 == 0 {
+            0
+        } else {
+            3 << ((packed & 0x07) + 1)
+        }
+    }
+
+    let Some(&screen_packed) = bytes.get(10) else {
+        return false;
+    };
+    let mut offset = 13 + color_table(screen_packed);
+    let mut images = 0;
+    while let Some(&introducer) = bytes.get(offset) {
+        offset = match introducer {
+            // extension, a label and sub-blocks
+            0x21 => match skip_sub_blocks(bytes, offset + 2) {
+                Some(offset) => offset,
+                None => break,
+            },
+            // image descriptor, a local color table, the LZW code size and sub-blocks
+            0x2c => {
+                images += 1;
+                if images > 1 {
+                    return true;
+                }
+                let Some(&packed) = bytes.get(offset + 9) else {
+                    break;
+                };
+                match skip_sub_blocks(bytes, offset + 11 + color_table(packed)) {
+                    Some(offset) => offset,
+                    None => break,
+                }
+            }
+            _ => break,
+        };
     }
+    false
+}
 
+impl Asset for ImageAsset {
     fn get_id(&self) -> &str {
-        return &self.id;
+        &self.id
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+
+    fn get_file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+
+    fn memory_usage(&self) -> usize {
+        let mapped = self.mapped.as_ref().map_or(0, |mapped| {
+            mapped.1.pixels.len() * 4 + mapped.2.cpu_bytes() + mapped.2.gpu_bytes()
+        });
+        self.pixels.memory_usage()
+            + self.image.pixels.len() * 4
+            + self.tiles.cpu_bytes()
+            + self.tiles.gpu_bytes()
+            + mapped
+            + self.source.as_ref().map_or(0, |source| source.len())
+            + self.thumbnail.memory_usage()
+            + self.sequence.as_ref().map_or(0, Sequence::memory_usage)
+    }
+
+    fn take_reloaded(&mut self, reloaded: Self) -> Result<()> {
+        let (input, working) = (self.color_space, self.working_space);
+        *self = Self {
+            id: std::mem::take(&mut self.id),
+            interpretation: self.interpretation,
+            last_shown: self.last_shown,
+            ..reloaded
+        };
+        // the reloaded pixels are as decoded, at the first frame
+        self.set_color_spaces(input, working)
+    }
+
+    fn thumbnail(&mut self) -> &mut Thumbnail {
+        &mut self.thumbnail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::AssetEnum;
+    use crate::registry::AssetRegistry;
+
+    fn gif(frames: usize) -> Vec<u8> {
+        use image::codecs::gif::GifEncoder;
+
+        let mut bytes = vec![];
+        {
+            let mut encoder = GifEncoder::new(&mut bytes);
+            for i in 0..frames {
+                let buffer =
+                    image::RgbaImage::from_pixel(4, 3, image::Rgba([i as u8 * 80, 0, 0, 255]));
+                let frame = image::Frame::from_parts(
+                    buffer,
+                    0,
+                    0,
+                    image::Delay::from_numer_denom_ms(100, 1),
+                );
+                assert!(encoder.encode_frame(frame).is_ok());
+            }
+        }
+        bytes
+    }
+
+    fn load(bytes: Vec<u8>, name: &str) -> ImageAsset {
+        let file = egui::DroppedFile {
+            name: name.to_owned(),
+            bytes: Some(bytes.into()),
+            ..Default::default()
+        };
+        match AssetRegistry::default().load(&file) {
+            std::result::Result::Ok(AssetEnum::Image(image_asset)) => image_asset,
+            std::result::Result::Ok(AssetEnum::Model(_)) => panic!("loaded {name} as a model"),
+            Err(e) => panic!("failed to load {name}: {e:#}"),
+        }
+    }
+
+    #[test]
+    fn still_gifs_are_told_apart_without_decoding() {
+        assert!(!gif_is_animated(&gif(1)));
+        assert!(gif_is_animated(&gif(3)));
+        assert!(!gif_is_animated(b"GIF89a"));
+        assert!(matches!(
+            ImageAsset::frames_from_bytes(&gif(1), image::ImageFormat::Gif),
+            std::result::Result::Ok(None)
+        ));
+    }
+
+    #[test]
+    fn images_larger_than_the_limits_are_rejected() {
+        let mut bytes = std::io::Cursor::new(vec![]);
+        let wide = image::GrayImage::new(MAX_DIMENSION + 1, 1);
+        assert!(wide.write_to(&mut bytes, image::ImageFormat::Png).is_ok());
+        let decoded = ImageAsset::image_from_bytes(bytes.get_ref(), image::ImageFormat::Png);
+        assert!(decoded.is_err());
+    }
+
+    #[test]
+    fn frames_must_fit_in_the_budget_together() -> Result<()> {
+        use image::AnimationDecoder as _;
+        use image::codecs::gif::GifDecoder;
+
+        let frames = || -> Result<image::Frames<'static>> {
+            Ok(GifDecoder::new(std::io::Cursor::new(gif(3)))?.into_frames())
+        };
+        // three frames of 4x3 RGBA
+        assert_eq!(collect_frames(frames()?, 144)?.len(), 3);
+        assert!(collect_frames(frames()?, 143).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn animated_gifs_load_their_frames_and_delays() {
+        let asset = load(gif(3), "spin.gif");
+        assert_eq!(asset.frame_count(), 3);
+        let delays = asset
+            .sequence()
+            .and_then(|sequence| sequence.delays.clone());
+        assert_eq!(delays.as_deref(), Some(&[0.1, 0.1, 0.1][..]));
+    }
+
+    #[test]
+    fn gifs_with_a_corrupt_trailing_frame_load_as_a_still() {
+        let mut bytes = gif(2);
+        // cut into the image data of the second frame
+        bytes.truncate(bytes.len() - 6);
+        let file = egui::DroppedFile {
+            name: "broken.gif".to_owned(),
+            bytes: Some(bytes.into()),
+            ..Default::default()
+        };
+        let mut warnings = vec![];
+        let loaded = AssetRegistry::default().load_with_warnings(&file, &mut warnings);
+        let std::result::Result::Ok(AssetEnum::Image(asset)) = loaded else {
+            panic!("failed to load broken.gif");
+        };
+        assert_eq!(asset.frame_count(), 1);
+        assert_eq!(asset.image.size, [4, 3]);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn reloaded_images_keep_their_id_and_color_spaces() {
+        let mut asset = load(gif(1), "turntable.gif");
+        asset.id = "before".to_owned();
+        assert!(
+            asset
+                .set_color_spaces(ColorSpace::Srgb, ColorSpace::LinearSrgb)
+                .is_ok()
+        );
+
+        assert!(asset.take_reloaded(load(gif(3), "turntable.gif")).is_ok());
+        assert_eq!(asset.id, "before");
+        assert_eq!(asset.working_space(), ColorSpace::LinearSrgb);
+        assert_eq!(asset.frame_count(), 3);
+    }
+
+    #[test]
+    fn evicted_images_hand_out_their_file_once() {
+        let ctx = egui::Context::default();
+        let mut asset = load(gif(3), "spin.gif");
+        asset.evict();
+        let Some(file) = asset.redecode(&ctx).ok().flatten() else {
+            panic!("no file to decode spin.gif from");
+        };
+        assert!(matches!(
+            asset.redecode(&ctx),
+            std::result::Result::Ok(None)
+        ));
+        asset.set_redecode_load(7);
+        assert!(asset.is_redecoded_by(7));
+        assert!(!asset.is_redecoded_by(8));
+
+        let std::result::Result::Ok(AssetEnum::Image(reloaded)) =
+            AssetRegistry::default().load(&file)
+        else {
+            panic!("failed to load spin.gif again");
+        };
+        assert!(asset.take_reloaded(reloaded).is_ok());
+        assert!(!asset.is_evicted());
+        assert!(!asset.is_redecoded_by(7));
+        assert_eq!(asset.image.size, [4, 3]);
+    }
+
+    #[test]
+    fn images_converted_again_keep_their_pixels_until_decoded() {
+        let ctx = egui::Context::default();
+        let mut asset = load(gif(1), "still.gif");
+        assert!(
+            asset
+                .set_color_spaces(ColorSpace::Srgb, ColorSpace::LinearSrgb)
+                .is_ok()
+        );
+        assert!(asset.is_decoded());
+
+        // converted pixels are not converted again, the file is handed out to decode them
+        assert!(
+            asset
+                .set_color_spaces(ColorSpace::DisplayP3, ColorSpace::LinearSrgb)
+                .is_ok()
+        );
+        assert!(!asset.is_decoded());
+        assert_eq!(asset.image.size, [4, 3]);
+        let Some(file) = asset.redecode(&ctx).ok().flatten() else {
+            panic!("no file to decode still.gif from");
+        };
+
+        let std::result::Result::Ok(AssetEnum::Image(reloaded)) =
+            AssetRegistry::default().load(&file)
+        else {
+            panic!("failed to load still.gif again");
+        };
+        assert!(asset.take_reloaded(reloaded).is_ok());
+        assert!(asset.is_decoded());
+        assert_eq!(asset.working_space(), ColorSpace::LinearSrgb);
+    }
+
+    #[test]
+    fn replaced_pixels_get_a_new_version() {
+        let mut asset = load(gif(3), "spin.gif");
+        let first = asset.pixels_version();
+        assert!(asset.set_frame(&egui::Context::default(), 1).is_ok());
+        assert_ne!(asset.pixels_version(), first);
+        assert_eq!(asset.duplicate().pixels_version(), asset.pixels_version());
+    }
+
+    #[test]
+    fn evicted_animations_free_and_decode_their_frames_again() {
+        let mut asset = load(gif(3), "spin.gif");
+        let loaded = asset.memory_usage();
+        asset.evict();
+        assert!(asset.memory_usage() < loaded);
+        assert_eq!(asset.frame_count(), 3);
+
+        assert!(asset.ensure_decoded().is_ok());
+        assert!(asset.set_frame(&egui::Context::default(), 2).is_ok());
+        assert_eq!(asset.image.size, [4, 3]);
+        assert_eq!(asset.image[(0, 0)].r(), 160);
     }
 }
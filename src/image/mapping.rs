@@ -0,0 +1,21 @@
+use crate::image::colormap::ScalarMapping;
+use crate::image::pixels::Pixels;
+use crate::image::vector::{FlowSettings, NormalSettings};
+
+/// CPU stage turning the stored pixels into the displayed ones, from the decoded values
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mapping {
+    Scalar(ScalarMapping),
+    Normal(NormalSettings, [f32; 3]),
+    FlowWheel(FlowSettings),
+}
+
+impl Mapping {
+    pub fn apply(&self, image: &Pixels) -> egui::ColorImage {
+        match self {
+            Self::Scalar(mapping) => mapping.apply(image),
+            Self::Normal(settings, light) => settings.display_image(image, *light),
+            Self::FlowWheel(settings) => settings.display_image(image),
+        }
+    }
+}
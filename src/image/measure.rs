@@ -0,0 +1,466 @@
+use crate::image::colormap::ScalarChannel;
+use crate::image::pixels::Pixels;
+
+/// Color of the ruler and the measured area
+const MEASURE_COLOR: egui::Color32 = egui::Color32::from_rgb(0, 200, 255);
+
+/// Height of the line profile plot, in points
+const PROFILE_HEIGHT: f32 = 160.0;
+
+/// Versions of pixels whose measurements are kept, e.g. of the selected images
+const CACHED_MEASUREMENTS: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MeasureTool {
+    Ruler,
+    Rect,
+    Lasso,
+}
+
+impl MeasureTool {
+    pub const ALL: [Self; 3] = [Self::Ruler, Self::Rect, Self::Lasso];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Ruler => "📏 Ruler",
+            Self::Rect => "⬚ Area",
+            Self::Lasso => "➰ Lasso",
+        }
+    }
+}
+
+/// Region the statistics are computed over, in pixel coordinates
+#[derive(Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Area {
+    Rect(egui::Rect),
+    Lasso(Vec<egui::Pos2>),
+}
+
+impl Area {
+    fn bounds(&self) -> egui::Rect {
+        match self {
+            Self::Rect(rect) => *rect,
+            Self::Lasso(points) => egui::Rect::from_points(points),
+        }
+    }
+
+    /// Whether the point is inside, lassos use the even-odd rule
+    fn contains(&self, pos: egui::Pos2) -> bool {
+        match self {
+            Self::Rect(rect) => rect.contains(pos),
+            Self::Lasso(points) => {
+                let mut inside = false;
+                let mut previous = points.last().copied().unwrap_or(pos);
+                for &point in points {
+                    if (point.y > pos.y) != (previous.y > pos.y)
+                        && pos.x
+                            < (previous.x - point.x) * (pos.y - point.y) / (previous.y - point.y)
+                                + point.x
+                    {
+                        inside = !inside;
+                    }
+                    previous = point;
+                }
+                inside
+            }
+        }
+    }
+
+    fn outline(&self) -> Vec<egui::Pos2> {
+        match self {
+            Self::Rect(rect) => vec![
+                rect.left_top(),
+                rect.right_top(),
+                rect.right_bottom(),
+                rect.left_bottom(),
+                rect.left_top(),
+            ],
+            Self::Lasso(points) => points.iter().chain(points.first()).copied().collect(),
+        }
+    }
+}
+
+/// Statistics per RGBA channel of the pixels in an area, values in the working space
+#[derive(Clone)]
+pub struct AreaStats {
+    pub count: usize,
+    pub mean: [f32; 4],
+    pub variance: [f32; 4],
+    pub min: [f32; 4],
+    pub max: [f32; 4],
+}
+
+/// Statistics of the pixels whose centers are inside `area`, `None` if there are none
+pub fn area_stats(image: &Pixels, area: &Area) -> Option<AreaStats> {
+    let [width, height] = image.size();
+    let bounds = area.bounds();
+    let x0 = bounds.min.x.floor().clamp(0.0, width as f32) as usize;
+    let y0 = bounds.min.y.floor().clamp(0.0, height as f32) as usize;
+    let x1 = bounds.max.x.ceil().clamp(0.0, width as f32) as usize;
+    let y1 = bounds.max.y.ceil().clamp(0.0, height as f32) as usize;
+
+    let mut count = 0;
+    let mut sum = [0.0f64; 4];
+    let mut square_sum = [0.0f64; 4];
+    let mut min = [f32::INFINITY; 4];
+    let mut max = [f32::NEG_INFINITY; 4];
+    for y in y0..y1 {
+        for x in x0..x1 {
+            if !area.contains(egui::pos2(x as f32 + 0.5, y as f32 + 0.5)) {
+                continue;
+            }
+            count += 1;
+            for (c, value) in image.get(x, y).into_iter().enumerate() {
+                sum[c] += f64::from(value);
+                square_sum[c] += f64::from(value * value);
+                min[c] = min[c].min(value);
+                max[c] = max[c].max(value);
+            }
+        }
+    }
+    if count == 0 {
+        return None;
+    }
+
+    let n = count as f64;
+    let mean = sum.map(|sum| sum / n);
+    Some(AreaStats {
+        count,
+        mean: mean.map(|mean| mean as f32),
+        variance: std::array::from_fn(|c| (square_sum[c] / n - mean[c] * mean[c]).max(0.0) as f32),
+        min,
+        max,
+    })
+}
+
+/// RGBA in the working space sampled bilinearly every pixel along the line from `from` to `to`
+pub fn profile(image: &Pixels, from: egui::Pos2, to: egui::Pos2) -> Vec<[f32; 4]> {
+    let [width, height] = image.size();
+    if width == 0 || height == 0 {
+        return vec![];
+    }
+    let pixel = |x: usize, y: usize| image.get(x.min(width - 1), y.min(height - 1));
+    let samples = from.distance(to).ceil() as usize + 1;
+    (0..samples)
+        .map(|i| {
+            let t = if samples > 1 {
+                i as f32 / (samples - 1) as f32
+            } else {
+                0.0
+            };
+            // pixel centers are at half coordinates
+            let pos = from.lerp(to, t) - egui::vec2(0.5, 0.5);
+            let x = pos.x.clamp(0.0, (width - 1) as f32);
+            let y = pos.y.clamp(0.0, (height - 1) as f32);
+            let (fx, fy) = (x.fract(), y.fract());
+            let (x, y) = (x as usize, y as usize);
+            let [a, b, c, d] = [
+                pixel(x, y),
+                pixel(x + 1, y),
+                pixel(x, y + 1),
+                pixel(x + 1, y + 1),
+            ];
+            std::array::from_fn(|ch| {
+                let top = a[ch] + (b[ch] - a[ch]) * fx;
+                let bottom = c[ch] + (d[ch] - c[ch]) * fx;
+                top + (bottom - top) * fy
+            })
+        })
+        .collect()
+}
+
+/// End points of the ruler, in pixel coordinates
+type Line = (egui::Pos2, egui::Pos2);
+
+/// Measurements by the version of the pixels they were computed on, with the area or line they
+/// were computed for. Only those of the current area and line are kept
+#[derive(Clone, Default)]
+struct MeasureCache {
+    stats: Vec<(u64, Area, Option<AreaStats>)>,
+    profiles: Vec<(u64, Line, Vec<[f32; 4]>)>,
+}
+
+/// Index of the entry for `version` in `cache`, computed with `compute` if there is none.
+/// Entries for another `key` are dropped, the oldest ones beyond `CACHED_MEASUREMENTS` too
+fn cached<K: PartialEq + Clone, V>(
+    cache: &mut Vec<(u64, K, V)>,
+    version: u64,
+    key: &K,
+    compute: impl FnOnce() -> V,
+) -> usize {
+    cache.retain(|(_, cached, _)| cached == key);
+    if let Some(index) = cache.iter().position(|(cached, ..)| *cached == version) {
+        return index;
+    }
+    if cache.len() >= CACHED_MEASUREMENTS {
+        cache.remove(0);
+    }
+    cache.push((version, key.clone(), compute()));
+    cache.len() - 1
+}
+
+/// Ruler and area, shared by all images like the region of interest
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Measure {
+    /// `None` leaves dragging to pan the image
+    #[serde(skip)]
+    pub tool: Option<MeasureTool>,
+    /// End points of the ruler, in pixel coordinates
+    pub line: Option<(egui::Pos2, egui::Pos2)>,
+    pub area: Option<Area>,
+    /// Channel plotted along the ruler
+    pub channel: ScalarChannel,
+    #[serde(skip)]
+    drag_start: Option<egui::Pos2>,
+    #[serde(skip)]
+    cache: MeasureCache,
+}
+
+impl Measure {
+    /// Statistics of the area over `image`, whose pixels are at `version`. They are computed
+    /// again only once the pixels or the area change, lassos over large images are slow
+    pub fn stats_of(&mut self, version: u64, image: &Pixels) -> Option<&AreaStats> {
+        let area = self.area.as_ref()?;
+        let stats = &mut self.cache.stats;
+        let index = cached(stats, version, area, || area_stats(image, area));
+        stats[index].2.as_ref()
+    }
+
+    /// Profile along the ruler over `image`, whose pixels are at `version`, cached like
+    /// `stats_of`
+    pub fn profile_of(&mut self, version: u64, image: &Pixels) -> &[[f32; 4]] {
+        let Some((from, to)) = self.line else {
+            return &[];
+        };
+        let profiles = &mut self.cache.profiles;
+        let index = cached(profiles, version, &(from, to), || profile(image, from, to));
+        &profiles[index].2
+    }
+
+    /// Drag with the current tool to measure
+    pub fn handle_input(
+        &mut self,
+        response: &egui::Response,
+        to_image: impl Fn(egui::Pos2) -> egui::Pos2,
+        zoom: f32,
+    ) {
+        let Some(tool) = self.tool else {
+            return;
+        };
+        if response.drag_started_by(egui::PointerButton::Primary) {
+            self.drag_start = response.interact_pointer_pos().map(&to_image);
+            if tool == MeasureTool::Lasso {
+                self.area = self.drag_start.map(|start| Area::Lasso(vec![start]));
+            }
+        }
+        let Some(start) = self.drag_start else {
+            return;
+        };
+
+        if let Some(pos) = response.interact_pointer_pos() {
+            let end = to_image(pos);
+            match (tool, &mut self.area) {
+                (MeasureTool::Ruler, _) => {
+                    // hold shift to snap to pixel centers
+                    let snap =
+                        |p: egui::Pos2| (p - egui::vec2(0.5, 0.5)).round() + egui::vec2(0.5, 0.5);
+                    self.line = Some(if response.ctx.input(|i| i.modifiers.shift) {
+                        (snap(start), snap(end))
+                    } else {
+                        (start, end)
+                    });
+                }
+                (MeasureTool::Rect, _) => {
+                    self.area = Some(Area::Rect(egui::Rect::from_two_pos(
+                        start.round(),
+                        end.round(),
+                    )));
+                }
+                (MeasureTool::Lasso, Some(Area::Lasso(points))) => {
+                    if points
+                        .last()
+                        .is_none_or(|last| last.distance(end) * zoom >= 3.0)
+                    {
+                        points.push(end);
+                    }
+                }
+                (MeasureTool::Lasso, _) => {}
+            }
+        }
+
+        if response.drag_stopped() {
+            self.drag_start = None;
+            let degenerate = match &self.area {
+                Some(Area::Rect(rect)) => rect.area() < 1.0,
+                Some(Area::Lasso(points)) => points.len() < 3,
+                None => false,
+            };
+            if tool != MeasureTool::Ruler && degenerate {
+                self.area = None;
+            }
+        }
+    }
+
+    /// Outline the area and draw the ruler with its length
+    pub fn paint(&self, painter: &egui::Painter, to_screen: impl Fn(egui::Pos2) -> egui::Pos2) {
+        let stroke = egui::Stroke::new(1.5, MEASURE_COLOR);
+        if let Some(area) = &self.area {
+            let outline = area.outline().into_iter().map(&to_screen).collect();
+            painter.add(egui::Shape::line(outline, stroke));
+        }
+        if let Some((from, to)) = self.line {
+            let (a, b) = (to_screen(from), to_screen(to));
+            painter.line_segment([a, b], stroke);
+            painter.circle_filled(a, 3.0, MEASURE_COLOR);
+            painter.circle_filled(b, 3.0, MEASURE_COLOR);
+            let galley = painter.layout_no_wrap(
+                format!("{:.1} px", from.distance(to)),
+                egui::FontId::monospace(12.0),
+                egui::Color32::WHITE,
+            );
+            let pos = a.lerp(b, 0.5) + egui::vec2(6.0, 6.0);
+            let background = egui::Rect::from_min_size(pos, galley.size()).expand(2.0);
+            painter.rect_filled(background, 2.0, egui::Color32::from_black_alpha(160));
+            painter.galley(pos, galley, egui::Color32::WHITE);
+        }
+    }
+
+    /// Tool picker, and the measurements of the shown image, whose pixels are at `version`
+    pub fn show_settings(&mut self, ui: &mut egui::Ui, version: u64, image: &Pixels) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Measure:");
+            ui.selectable_value(&mut self.tool, None, "✋ Pan");
+            for tool in MeasureTool::ALL {
+                ui.selectable_value(&mut self.tool, Some(tool), tool.label());
+            }
+        });
+        if let Some((from, to)) = self.line {
+            ui.horizontal(|ui| {
+                let delta = to - from;
+                ui.label(format!(
+                    "Length: {:.1} px ({:.1}, {:.1})",
+                    delta.length(),
+                    delta.x,
+                    delta.y
+                ));
+                if ui.small_button("Clear").clicked() {
+                    self.line = None;
+                }
+            });
+        }
+        if self.area.is_some() {
+            if let Some(stats) = self.stats_of(version, image) {
+                show_stats(ui, stats);
+            }
+            if ui.small_button("Clear area").clicked() {
+                self.area = None;
+            }
+        }
+    }
+}
+
+/// Mean ± standard deviation and range of each channel
+pub fn show_stats(ui: &mut egui::Ui, stats: &AreaStats) {
+    ui.label(format!("{} pixels", stats.count));
+    egui::Grid::new(ui.next_auto_id())
+        .num_columns(3)
+        .show(ui, |ui| {
+            for (c, name) in ["R", "G", "B", "A"].into_iter().enumerate() {
+                ui.monospace(name);
+                ui.monospace(format!(
+                    "{:.4} ± {:.4}",
+                    stats.mean[c],
+                    stats.variance[c].sqrt()
+                ));
+                ui.monospace(format!("{:.4}..{:.4}", stats.min[c], stats.max[c]));
+                ui.end_row();
+            }
+        });
+}
+
+/// Values of `channel` along the ruler, a line per image in the colors of `colors`.
+/// Hover to read the values at a distance
+pub fn show_profile(
+    ui: &mut egui::Ui,
+    channel: ScalarChannel,
+    series: &[(String, Vec<[f32; 4]>)],
+    colors: &[egui::Color32],
+) {
+    let (response, painter) = ui.allocate_painter(
+        egui::vec2(ui.available_width().max(200.0), PROFILE_HEIGHT),
+        egui::Sense::hover(),
+    );
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(120));
+    let samples = series
+        .iter()
+        .map(|(_, values)| values.len())
+        .max()
+        .unwrap_or(0);
+    if samples < 2 {
+        return;
+    }
+
+    // [0, 1], extended to the values of the series, e.g. HDR values above 1
+    let (low, high) = series
+        .iter()
+        .flat_map(|(_, values)| values.iter().map(|rgba| channel.value(*rgba)))
+        .filter(|value| value.is_finite())
+        .fold((0.0_f32, 1.0_f32), |(low, high), value| {
+            (low.min(value), high.max(value))
+        });
+    let plot = rect.shrink(6.0);
+    let x = |i: usize| plot.left() + i as f32 / (samples - 1) as f32 * plot.width();
+    let y = |value: f32| {
+        let t = (value - low) / (high - low);
+        plot.bottom() - t.clamp(0.0, 1.0) * plot.height()
+    };
+    let grid = egui::Stroke::new(1.0, egui::Color32::from_white_alpha(20));
+    for t in [0.25, 0.5, 0.75] {
+        painter.hline(plot.x_range(), y(egui::lerp(low..=high, t)), grid);
+    }
+    let label_color = egui::Color32::from_white_alpha(120);
+    for (value, align) in [
+        (high, egui::Align2::LEFT_TOP),
+        (low, egui::Align2::LEFT_BOTTOM),
+    ] {
+        painter.text(
+            egui::pos2(plot.left(), y(value)),
+            align,
+            format!("{value:.3}"),
+            egui::FontId::monospace(10.0),
+            label_color,
+        );
+    }
+    for (i, (_, values)) in series.iter().enumerate() {
+        let points = values
+            .iter()
+            .enumerate()
+            .map(|(s, rgba)| egui::pos2(x(s), y(channel.value(*rgba))))
+            .collect();
+        let stroke = egui::Stroke::new(1.5, colors[i % colors.len()]);
+        painter.add(egui::Shape::line(points, stroke));
+    }
+
+    if let Some(pointer) = response.hover_pos() {
+        let sample = (((pointer.x - plot.left()) / plot.width() * (samples - 1) as f32)
+            .round()
+            .max(0.0) as usize)
+            .min(samples - 1);
+        painter.vline(
+            x(sample),
+            rect.y_range(),
+            egui::Stroke::new(1.0, egui::Color32::WHITE),
+        );
+        response.on_hover_ui_at_pointer(|ui| {
+            ui.label(format!("{sample} px"));
+            for (i, (id, values)) in series.iter().enumerate() {
+                if let Some(rgba) = values.get(sample) {
+                    let text = format!("{id}: {:.4}", channel.value(*rgba));
+                    ui.colored_label(colors[i % colors.len()], text);
+                }
+            }
+        });
+    }
+}
@@ -1,2 +1,13 @@
+pub mod annotation;
+pub mod color_space;
+pub mod colormap;
+pub mod grid;
+#[expect(clippy::module_inception)]
 pub mod image;
+pub mod mapping;
+pub mod measure;
+pub mod pixels;
+pub mod tiles;
+pub mod tiling;
+pub mod vector;
 pub mod viewer;
@@ -0,0 +1,115 @@
+/// Pixels as decoded or converted, before they are quantized for the screen.
+///
+/// Files with 8 bits per channel stay in 8 bits until converted, files with more precision and
+/// every conversion are kept as floats, so linear working spaces do not band in the shadows
+#[derive(Clone)]
+pub enum Pixels {
+    Rgba8(image::RgbaImage),
+    Rgba32F(image::Rgba32FImage),
+}
+
+impl Default for Pixels {
+    fn default() -> Self {
+        Self::Rgba8(image::RgbaImage::new(0, 0))
+    }
+}
+
+impl From<image::RgbaImage> for Pixels {
+    fn from(buffer: image::RgbaImage) -> Self {
+        Self::Rgba8(buffer)
+    }
+}
+
+impl From<image::Rgba32FImage> for Pixels {
+    fn from(buffer: image::Rgba32FImage) -> Self {
+        Self::Rgba32F(buffer)
+    }
+}
+
+impl From<image::DynamicImage> for Pixels {
+    /// Keeps 8 bit images in 8 bits and everything else, e.g. 16 bit PNGs or EXRs, as floats
+    fn from(image: image::DynamicImage) -> Self {
+        match image {
+            image::DynamicImage::ImageRgba8(buffer) => Self::Rgba8(buffer),
+            image::DynamicImage::ImageLuma8(_)
+            | image::DynamicImage::ImageLumaA8(_)
+            | image::DynamicImage::ImageRgb8(_) => Self::Rgba8(image.to_rgba8()),
+            image::DynamicImage::ImageRgba32F(buffer) => Self::Rgba32F(buffer),
+            _ => Self::Rgba32F(image.to_rgba32f()),
+        }
+    }
+}
+
+impl From<egui::ColorImage> for Pixels {
+    /// Unpremultiplied 8 bit pixels, as decoders returned before pixels were kept as floats
+    fn from(image: egui::ColorImage) -> Self {
+        let [width, height] = image.size;
+        let rgba: Vec<u8> = image
+            .pixels
+            .iter()
+            .flat_map(|pixel| pixel.to_srgba_unmultiplied())
+            .collect();
+        image::RgbaImage::from_raw(width as u32, height as u32, rgba)
+            .map_or_else(Self::default, Self::Rgba8)
+    }
+}
+
+impl Pixels {
+    pub fn size(&self) -> [usize; 2] {
+        let (width, height) = match self {
+            Self::Rgba8(buffer) => buffer.dimensions(),
+            Self::Rgba32F(buffer) => buffer.dimensions(),
+        };
+        [width as usize, height as usize]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size().contains(&0)
+    }
+
+    /// Bytes held in RAM
+    pub fn memory_usage(&self) -> usize {
+        match self {
+            Self::Rgba8(buffer) => buffer.as_raw().len(),
+            Self::Rgba32F(buffer) => buffer.as_raw().len() * size_of::<f32>(),
+        }
+    }
+
+    /// Unpremultiplied RGBA of every pixel in row order, in [0, 1] for 8 bit pixels and
+    /// possibly beyond for floats
+    pub fn rgba(&self) -> Box<dyn Iterator<Item = [f32; 4]> + '_> {
+        match self {
+            Self::Rgba8(buffer) => Box::new(
+                buffer
+                    .pixels()
+                    .map(|pixel| pixel.0.map(|c| f32::from(c) / 255.0)),
+            ),
+            Self::Rgba32F(buffer) => Box::new(buffer.pixels().map(|pixel| pixel.0)),
+        }
+    }
+
+    /// Unpremultiplied RGBA of the pixel at `x`, `y`, which has to be inside the image
+    pub fn get(&self, x: usize, y: usize) -> [f32; 4] {
+        let (x, y) = (x as u32, y as u32);
+        match self {
+            Self::Rgba8(buffer) => buffer.get_pixel(x, y).0.map(|c| f32::from(c) / 255.0),
+            Self::Rgba32F(buffer) => buffer.get_pixel(x, y).0,
+        }
+    }
+
+    /// With 8 bits per channel, values beyond [0, 1] are clipped
+    pub fn quantize(&self) -> egui::ColorImage {
+        let size = self.size();
+        match self {
+            Self::Rgba8(buffer) => egui::ColorImage::from_rgba_unmultiplied(size, buffer.as_raw()),
+            Self::Rgba32F(buffer) => {
+                let rgba: Vec<u8> = buffer
+                    .as_raw()
+                    .iter()
+                    .map(|&c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+                    .collect();
+                egui::ColorImage::from_rgba_unmultiplied(size, &rgba)
+            }
+        }
+    }
+}
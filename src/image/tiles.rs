@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+/// Side of a tile in pixels, small enough for any GPU
+const TILE_SIZE: usize = 1024;
+
+/// Tiles that were not drawn for this many passes are freed
+const TILE_LIFETIME: u64 = 120;
+
+/// Filter, pyramid level and tile column and row
+type TileKey = (egui::TextureFilter, usize, usize, usize);
+
+/// An image uploaded as tiles of a mip pyramid, only the tiles that are drawn are resident
+#[derive(Default)]
+pub struct TiledTexture {
+    /// Downsampled levels, the first has half the resolution of the source image
+    levels: Vec<egui::ColorImage>,
+    /// Textures and the pass they were last drawn in
+    tiles: HashMap<TileKey, (egui::TextureHandle, u64)>,
+}
+
+impl TiledTexture {
+    pub fn clear(&mut self) {
+        self.levels.clear();
+        self.tiles.clear();
+    }
+
+    /// Bytes of the downsampled levels in RAM
+    pub fn cpu_bytes(&self) -> usize {
+        self.levels.iter().map(|level| level.pixels.len() * 4).sum()
+    }
+
+    /// Bytes of the resident tiles on the GPU
+    pub fn gpu_bytes(&self) -> usize {
+        self.tiles
+            .values()
+            .map(|(texture, _)| texture.size().iter().product::<usize>() * 4)
+            .sum()
+    }
+
+    /// Paint the `source` region of `image`, in pixels, into `dest` on screen
+    pub fn paint(
+        &mut self,
+        painter: &egui::Painter,
+        image: &egui::ColorImage,
+        filter: egui::TextureFilter,
+        source: egui::Rect,
+        dest: egui::Rect,
+    ) {
+        let image_rect = egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(image.width() as f32, image.height() as f32),
+        );
+        let clip = painter.clip_rect().intersect(dest);
+        if !clip.is_positive() || !source.is_positive() {
+            return;
+        }
+
+        // pick the level with at least one texel per screen pixel
+        let ctx = painter.ctx();
+        let texels_per_pixel = source.width() / (dest.width() * ctx.pixels_per_point());
+        let max_level = image.width().max(image.height()).max(1).ilog2() as usize;
+        let level = if texels_per_pixel > 1.0 {
+            (texels_per_pixel.log2().floor() as usize).min(max_level)
+        } else {
+            0
+        };
+        self.build_levels(image, level);
+        let level_image = match level {
+            0 => image,
+            _ => &self.levels[level - 1],
+        };
+        let level_scale = egui::vec2(
+            level_image.width() as f32 / image_rect.width(),
+            level_image.height() as f32 / image_rect.height(),
+        );
+
+        // part of the source that is on screen, in image pixels
+        let to_source =
+            |pos: egui::Pos2| source.min + (pos - dest.min) * (source.size() / dest.size());
+        let to_screen =
+            |pos: egui::Pos2| dest.min + (pos - source.min) * (dest.size() / source.size());
+        let visible = egui::Rect::from_min_max(to_source(clip.min), to_source(clip.max))
+            .intersect(source)
+            .intersect(image_rect);
+        if !visible.is_positive() {
+            return;
+        }
+        let columns = level_image.width().div_ceil(TILE_SIZE);
+        let rows = level_image.height().div_ceil(TILE_SIZE);
+        let first_tile = |pixel: f32| (pixel.max(0.0) as usize) / TILE_SIZE;
+        let end_tile =
+            |pixel: f32, count: usize| ((pixel.ceil() as usize).div_ceil(TILE_SIZE)).min(count);
+        let (x_tiles, y_tiles) = (
+            first_tile(visible.min.x * level_scale.x)
+                ..end_tile(visible.max.x * level_scale.x, columns),
+            first_tile(visible.min.y * level_scale.y)
+                ..end_tile(visible.max.y * level_scale.y, rows),
+        );
+
+        let pass = ctx.cumulative_pass_nr();
+        for row in y_tiles {
+            for column in x_tiles.clone() {
+                let x0 = column * TILE_SIZE;
+                let y0 = row * TILE_SIZE;
+                let x1 = (x0 + TILE_SIZE).min(level_image.width());
+                let y1 = (y0 + TILE_SIZE).min(level_image.height());
+
+                // a one pixel border from the neighbours hides seams when filtering
+                let (px0, py0) = (x0.saturating_sub(1), y0.saturating_sub(1));
+                let (px1, py1) = (
+                    (x1 + 1).min(level_image.width()),
+                    (y1 + 1).min(level_image.height()),
+                );
+
+                let (texture, last_used) = self
+                    .tiles
+                    .entry((filter, level, column, row))
+                    .or_insert_with(|| {
+                        let tile = level_image.region_by_pixels([px0, py0], [px1 - px0, py1 - py0]);
+                        let options = egui::TextureOptions {
+                            magnification: filter,
+                            minification: filter,
+                            ..Default::default()
+                        };
+                        let name = format!("tile {level} {column} {row}");
+                        (ctx.load_texture(name, tile, options), pass)
+                    });
+                *last_used = pass;
+
+                let padded = egui::vec2((px1 - px0) as f32, (py1 - py0) as f32);
+                let uv = egui::Rect::from_min_max(
+                    (egui::vec2((x0 - px0) as f32, (y0 - py0) as f32) / padded).to_pos2(),
+                    (egui::vec2((x1 - px0) as f32, (y1 - py0) as f32) / padded).to_pos2(),
+                );
+                let tile_rect = egui::Rect::from_min_max(
+                    (egui::vec2(x0 as f32, y0 as f32) / level_scale).to_pos2(),
+                    (egui::vec2(x1 as f32, y1 as f32) / level_scale).to_pos2(),
+                );
+                let screen_rect =
+                    egui::Rect::from_min_max(to_screen(tile_rect.min), to_screen(tile_rect.max));
+                painter.image(texture.id(), screen_rect, uv, egui::Color32::WHITE);
+            }
+        }
+
+        self.tiles
+            .retain(|_, (_, last_used)| pass - *last_used < TILE_LIFETIME);
+    }
+
+    /// Downsample the pyramid up to `level`
+    fn build_levels(&mut self, image: &egui::ColorImage, level: usize) {
+        while self.levels.len() < level {
+            let previous = self.levels.last().unwrap_or(image);
+            let next = downsample(previous);
+            self.levels.push(next);
+        }
+    }
+}
+
+/// Halve the resolution by averaging 2x2 blocks
+fn downsample(image: &egui::ColorImage) -> egui::ColorImage {
+    let [width, height] = image.size;
+    let size = [width.div_ceil(2).max(1), height.div_ceil(2).max(1)];
+    let mut pixels = Vec::with_capacity(size[0] * size[1]);
+    for y in 0..size[1] {
+        for x in 0..size[0] {
+            let mut sum = [0_u32; 4];
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let pixel = image[((2 * x + dx).min(width - 1), (2 * y + dy).min(height - 1))];
+                for (channel, value) in sum.iter_mut().zip(pixel.to_array()) {
+                    *channel += u32::from(value);
+                }
+            }
+            let [r, g, b, a] = sum.map(|channel| ((channel + 2) / 4) as u8);
+            pixels.push(egui::Color32::from_rgba_premultiplied(r, g, b, a));
+        }
+    }
+    egui::ColorImage::new(size, pixels)
+}
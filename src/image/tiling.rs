@@ -0,0 +1,253 @@
+use crate::image::image::ImageAsset;
+use crate::image::mapping::Mapping;
+
+/// Screen points per seam segment, steps along a seam are merged below it
+const SEAM_SEGMENT: f32 = 2.0;
+/// Tiles painted at most, zoomed far out an infinite tiling is limited to those around the image
+const MAX_TILES: f32 = 4096.0;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum TilingMode {
+    #[default]
+    Off,
+    /// The image and its eight neighbours
+    Grid3x3,
+    /// Repeated over the whole viewer
+    Infinite,
+}
+
+impl TilingMode {
+    pub const ALL: [Self; 3] = [Self::Off, Self::Grid3x3, Self::Infinite];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Grid3x3 => "3x3",
+            Self::Infinite => "Infinite",
+        }
+    }
+}
+
+/// How well the opposite edges of an image continue into each other.
+/// Steps are mean absolute RGB differences in [0, 1]
+pub struct SeamStats {
+    /// Step from the right column to the left one, per row
+    pub vertical: Vec<f32>,
+    /// Step from the bottom row to the top one, per column
+    pub horizontal: Vec<f32>,
+    /// Mean step between the columns next to the left and right edges
+    pub vertical_interior: f32,
+    /// Mean step between the rows next to the top and bottom edges
+    pub horizontal_interior: f32,
+}
+
+impl SeamStats {
+    pub fn of_image(image: &egui::ColorImage) -> Option<Self> {
+        let [width, height] = image.size;
+        if width < 3 || height < 3 {
+            return None;
+        }
+        let step = |a: (usize, usize), b: (usize, usize)| {
+            let (a, b) = (
+                image[a].to_srgba_unmultiplied(),
+                image[b].to_srgba_unmultiplied(),
+            );
+            (0..3)
+                .map(|c| (f32::from(a[c]) - f32::from(b[c])).abs())
+                .sum::<f32>()
+                / (3.0 * 255.0)
+        };
+        let mean = |values: &mut dyn Iterator<Item = f32>, count: usize| {
+            values.sum::<f32>() / count as f32
+        };
+
+        let vertical = (0..height).map(|y| step((width - 1, y), (0, y))).collect();
+        let horizontal = (0..width).map(|x| step((x, height - 1), (x, 0))).collect();
+        let vertical_interior = mean(
+            &mut (0..height)
+                .flat_map(|y| [step((0, y), (1, y)), step((width - 2, y), (width - 1, y))]),
+            2 * height,
+        );
+        let horizontal_interior = mean(
+            &mut (0..width)
+                .flat_map(|x| [step((x, 0), (x, 1)), step((x, height - 2), (x, height - 1))]),
+            2 * width,
+        );
+        Some(Self {
+            vertical,
+            horizontal,
+            vertical_interior,
+            horizontal_interior,
+        })
+    }
+
+    /// Mean step across the seam and how many times the steps next to it that is
+    fn summary(seam: &[f32], interior: f32) -> (f32, f32) {
+        let mean = seam.iter().sum::<f32>() / seam.len().max(1) as f32;
+        (mean, mean / interior.max(1.0 / 255.0))
+    }
+
+    /// 0 for steps like the ones next to the seam, 1 for steps at least three times as large
+    fn strength(step: f32, interior: f32) -> f32 {
+        ((step / interior.max(1.0 / 255.0) - 1.0) / 2.0).clamp(0.0, 1.0)
+    }
+}
+
+/// Repeats the image to check that it tiles
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Tiling {
+    pub mode: TilingMode,
+    /// Shift the image by half its size, so the seams run through the middle
+    pub offset_half: bool,
+    /// Mark where the opposite edges do not continue into each other
+    pub highlight_seams: bool,
+}
+
+impl Tiling {
+    /// Whether the image is not simply painted once
+    pub fn is_active(&self) -> bool {
+        self.mode != TilingMode::Off || self.offset_half || self.highlight_seams
+    }
+
+    /// Paint the repeated image, `image_rect` is where the image is on screen without tiling
+    pub fn paint(
+        &self,
+        painter: &egui::Painter,
+        asset: &mut ImageAsset,
+        filter_mode: egui::TextureFilter,
+        mapping: Option<&Mapping>,
+        image_rect: egui::Rect,
+    ) {
+        let [width, height] = asset.image.size;
+        let tile = image_rect.size();
+        if width == 0 || height == 0 || !tile.x.is_finite() || tile.min_elem() <= 0.0 {
+            return;
+        }
+        let region = match self.mode {
+            TilingMode::Off => image_rect,
+            TilingMode::Grid3x3 => image_rect.expand2(tile),
+            TilingMode::Infinite => painter.clip_rect(),
+        };
+        let painter = painter.with_clip_rect(region.intersect(painter.clip_rect()));
+        let clip = painter.clip_rect();
+        if !clip.is_positive() {
+            return;
+        }
+
+        // whole pixels, so the pixel grid still lines up
+        let shift = if self.offset_half {
+            egui::vec2((width / 2) as f32, (height / 2) as f32) * (tile.x / width as f32)
+        } else {
+            egui::Vec2::ZERO
+        };
+        let origin = image_rect.min + shift;
+        let mut first = ((clip.min - origin) / tile).floor();
+        let mut last = ((clip.max - origin) / tile).ceil();
+        let count = last - first;
+        if count.x * count.y > MAX_TILES {
+            // the tiles around the image itself, or around the closest visible tile to it
+            let half = (MAX_TILES.sqrt() / 2.0).floor();
+            let center = egui::Vec2::ZERO
+                .max(first)
+                .min(last - egui::Vec2::splat(1.0));
+            first = first.max(center - egui::Vec2::splat(half));
+            last = last.min(center + egui::Vec2::splat(half));
+        }
+        let full_image = egui::Rect::from_min_size(egui::Pos2::ZERO, asset.image_size());
+        for row in first.y as i64..last.y as i64 {
+            for column in first.x as i64..last.x as i64 {
+                let min = origin + egui::vec2(column as f32, row as f32) * tile;
+                let tile_rect = egui::Rect::from_min_size(min, tile);
+                asset.paint(&painter, filter_mode, mapping, full_image, tile_rect);
+            }
+        }
+
+        if !self.highlight_seams {
+            return;
+        }
+        let Some(stats) = asset.seam_stats() else {
+            return;
+        };
+        let zoom = tile.x / width as f32;
+        for column in first.x as i64..=last.x as i64 {
+            let x = origin.x + column as f32 * tile.x;
+            for row in first.y as i64..last.y as i64 {
+                let top = origin.y + row as f32 * tile.y;
+                paint_seam(
+                    &painter,
+                    &stats.vertical,
+                    stats.vertical_interior,
+                    zoom,
+                    |t| egui::pos2(x, top + t),
+                );
+            }
+        }
+        for row in first.y as i64..=last.y as i64 {
+            let y = origin.y + row as f32 * tile.y;
+            for column in first.x as i64..last.x as i64 {
+                let left = origin.x + column as f32 * tile.x;
+                paint_seam(
+                    &painter,
+                    &stats.horizontal,
+                    stats.horizontal_interior,
+                    zoom,
+                    |t| egui::pos2(left + t, y),
+                );
+            }
+        }
+    }
+
+    pub fn show_settings(&mut self, ui: &mut egui::Ui, asset: &mut ImageAsset) {
+        ui.horizontal(|ui| {
+            ui.label("Tiling:");
+            for mode in TilingMode::ALL {
+                ui.selectable_value(&mut self.mode, mode, mode.label());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.offset_half, "Offset by half")
+                .on_hover_text("Move the edges into the middle of the image to expose seams");
+            ui.checkbox(&mut self.highlight_seams, "Seams")
+                .on_hover_text("Mark where the opposite edges do not continue into each other");
+        });
+        if !self.highlight_seams {
+            return;
+        }
+        if let Some(stats) = asset.seam_stats() {
+            let (left_right, left_right_ratio) =
+                SeamStats::summary(&stats.vertical, stats.vertical_interior);
+            let (top_bottom, top_bottom_ratio) =
+                SeamStats::summary(&stats.horizontal, stats.horizontal_interior);
+            ui.label(format!(
+                "Left-right seam: {left_right:.4} ({left_right_ratio:.1}x the steps next to it)"
+            ));
+            ui.label(format!(
+                "Top-bottom seam: {top_bottom:.4} ({top_bottom_ratio:.1}x the steps next to it)"
+            ));
+        }
+    }
+}
+
+/// Mark a seam along a tile edge, `at` gives the point at a distance along the edge.
+/// Steps along it are merged so segments are at least a few points long
+fn paint_seam(
+    painter: &egui::Painter,
+    steps: &[f32],
+    interior: f32,
+    zoom: f32,
+    at: impl Fn(f32) -> egui::Pos2,
+) {
+    let merge = (SEAM_SEGMENT / zoom).ceil().max(1.0) as usize;
+    for (i, chunk) in steps.chunks(merge).enumerate() {
+        let step = chunk.iter().copied().fold(0.0, f32::max);
+        let strength = SeamStats::strength(step, interior);
+        if strength <= 0.0 {
+            continue;
+        }
+        let start = (i * merge) as f32 * zoom;
+        let end = start + chunk.len() as f32 * zoom;
+        let color = egui::Color32::from_rgba_unmultiplied(255, 40, 40, (strength * 255.0) as u8);
+        painter.line_segment([at(start), at(end)], egui::Stroke::new(3.0, color));
+    }
+}
@@ -0,0 +1,364 @@
+use crate::image::pixels::Pixels;
+use std::f32::consts::PI;
+
+/// Orientation of the green channel of a tangent-space normal map
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NormalConvention {
+    /// Green points up (+Y), as used by OpenGL, Blender and Unity
+    OpenGl,
+    /// Green points down (-Y), as used by DirectX and Unreal
+    DirectX,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NormalSpace {
+    Tangent,
+    World,
+}
+
+/// How a normal map is displayed in the viewer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NormalView {
+    /// Decoded normals, re-encoded with the OpenGL convention
+    Encoded,
+    /// Lambert shading of a flat surface using the normals
+    Lit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NormalSettings {
+    pub convention: NormalConvention,
+    pub space: NormalSpace,
+    /// Ignore the blue channel and reconstruct Z from X and Y
+    pub reconstruct_z: bool,
+    pub view: NormalView,
+}
+
+impl Default for NormalSettings {
+    fn default() -> Self {
+        Self {
+            convention: NormalConvention::OpenGl,
+            space: NormalSpace::Tangent,
+            reconstruct_z: false,
+            view: NormalView::Encoded,
+        }
+    }
+}
+
+impl NormalSettings {
+    /// Decode the RGBA of a pixel, as stored in the file, into a unit normal, with +Y pointing
+    /// up in the image
+    pub fn decode(&self, rgba: [f32; 4]) -> [f32; 3] {
+        let [r, g, b, _] = rgba.map(|c| c * 2.0 - 1.0);
+
+        let x = r;
+        let y = match self.convention {
+            NormalConvention::OpenGl => g,
+            NormalConvention::DirectX => -g,
+        };
+        let z = if self.reconstruct_z {
+            (1.0 - x * x - y * y).max(0.0).sqrt()
+        } else {
+            b
+        };
+
+        normalize([x, y, z])
+    }
+
+    /// Texture shown in the viewer, `light` is only used by [`NormalView::Lit`]
+    pub fn display_image(&self, image: &Pixels, light: [f32; 3]) -> egui::ColorImage {
+        let pixels = image
+            .rgba()
+            .map(|rgba| {
+                let n = self.decode(rgba);
+                match self.view {
+                    NormalView::Encoded => {
+                        let [r, g, b] = n.map(|c| ((c * 0.5 + 0.5) * 255.0).round() as u8);
+                        egui::Color32::from_rgb(r, g, b)
+                    }
+                    NormalView::Lit => gray(lambert(n, light)),
+                }
+            })
+            .collect();
+        egui::ColorImage::new(image.size(), pixels)
+    }
+
+    /// Render a sphere shaded with the normal map wrapped around it
+    pub fn lit_sphere(&self, image: &Pixels, light: [f32; 3], size: usize) -> egui::ColorImage {
+        let [width, height] = image.size();
+        let mut out = egui::ColorImage::filled([size, size], egui::Color32::TRANSPARENT);
+        if width == 0 || height == 0 {
+            return out;
+        }
+
+        for py in 0..size {
+            for px in 0..size {
+                let x = (px as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let y = 1.0 - (py as f32 + 0.5) / size as f32 * 2.0;
+                let r2 = x * x + y * y;
+                if r2 > 1.0 {
+                    continue;
+                }
+                let z = (1.0 - r2).sqrt();
+
+                // spherical uv, the visible hemisphere spans the full texture width
+                let phi = x.atan2(z);
+                let u = phi / PI + 0.5;
+                let v = y.asin() / PI + 0.5;
+                let tx = ((u * width as f32) as usize).min(width - 1);
+                let ty = (((1.0 - v) * height as f32) as usize).min(height - 1);
+                let n = self.decode(image.get(tx, ty));
+
+                let normal = match self.space {
+                    NormalSpace::World => n,
+                    NormalSpace::Tangent => {
+                        let surface = [x, y, z];
+                        let tangent = [phi.cos(), 0.0, -phi.sin()];
+                        let bitangent = cross(surface, tangent);
+                        normalize(std::array::from_fn(|i| {
+                            tangent[i] * n[0] + bitangent[i] * n[1] + surface[i] * n[2]
+                        }))
+                    }
+                };
+
+                out[(px, py)] = gray(lambert(normal, light));
+            }
+        }
+        out
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FlowStyle {
+    /// Hue encodes direction and saturation encodes magnitude
+    ColorWheel,
+    /// Arrows drawn on top of the image
+    Arrows,
+}
+
+/// How the motion vectors of a flow image are stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FlowEncoding {
+    /// Centered on 0.5, e.g. in 8 bit images
+    MidGray,
+    /// Signed values as they are, e.g. pixel displacements in an EXR
+    Signed,
+}
+
+/// Motion vectors stored in the red and green channels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlowSettings {
+    pub style: FlowStyle,
+    pub encoding: FlowEncoding,
+    /// Displacement in pixels represented by a channel value of 1.0
+    pub scale: f32,
+    /// Magnitude shown at full saturation in the color wheel
+    pub max_magnitude: f32,
+    /// Distance between arrows, in screen points
+    pub arrow_spacing: f32,
+}
+
+impl Default for FlowSettings {
+    fn default() -> Self {
+        Self {
+            style: FlowStyle::ColorWheel,
+            encoding: FlowEncoding::MidGray,
+            scale: 16.0,
+            max_magnitude: 16.0,
+            arrow_spacing: 24.0,
+        }
+    }
+}
+
+impl FlowSettings {
+    /// Settings for the flow in `image`: float images with values outside of [0, 1] hold
+    /// signed displacements, anything else is centered on 0.5
+    pub fn for_pixels(image: &Pixels) -> Self {
+        let signed = matches!(image, Pixels::Rgba32F(_))
+            && image
+                .rgba()
+                .any(|[r, g, _, _]| !(0.0..=1.0).contains(&r) || !(0.0..=1.0).contains(&g));
+        Self {
+            encoding: if signed {
+                FlowEncoding::Signed
+            } else {
+                FlowEncoding::MidGray
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Decode the RGBA of a pixel, as stored in the file, into a displacement in pixels, with
+    /// +Y pointing down
+    pub fn decode(&self, [r, g, _, _]: [f32; 4]) -> egui::Vec2 {
+        match self.encoding {
+            FlowEncoding::MidGray => egui::vec2(r * 2.0 - 1.0, g * 2.0 - 1.0) * self.scale,
+            FlowEncoding::Signed => egui::vec2(r, g) * self.scale,
+        }
+    }
+
+    pub fn wheel_color(&self, flow: egui::Vec2) -> egui::Color32 {
+        let hue = (flow.y.atan2(flow.x) / (2.0 * PI)).rem_euclid(1.0);
+        let saturation = (flow.length() / self.max_magnitude.max(f32::EPSILON)).min(1.0);
+        egui::ecolor::Hsva::new(hue, saturation, 1.0, 1.0).into()
+    }
+
+    pub fn display_image(&self, image: &Pixels) -> egui::ColorImage {
+        let pixels = image
+            .rgba()
+            .map(|rgba| self.wheel_color(self.decode(rgba)))
+            .collect();
+        egui::ColorImage::new(image.size(), pixels)
+    }
+}
+
+/// Direction towards a light given as azimuth and elevation in degrees
+pub fn light_direction(azimuth: f32, elevation: f32) -> [f32; 3] {
+    let (azimuth, elevation) = (azimuth.to_radians(), elevation.to_radians());
+    [
+        elevation.cos() * azimuth.cos(),
+        elevation.cos() * azimuth.sin(),
+        elevation.sin(),
+    ]
+}
+
+fn lambert(n: [f32; 3], light: [f32; 3]) -> f32 {
+    let n_dot_l = n[0] * light[0] + n[1] * light[1] + n[2] * light[2];
+    0.1 + 0.9 * n_dot_l.max(0.0)
+}
+
+fn gray(value: f32) -> egui::Color32 {
+    let v = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    egui::Color32::from_gray(v)
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if length > 0.0 {
+        v.map(|c| c / length)
+    } else {
+        [0.0, 0.0, 1.0]
+    }
+}
+
+impl NormalSettings {
+    pub fn show_settings(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Convention:");
+            ui.selectable_value(&mut self.convention, NormalConvention::OpenGl, "OpenGL");
+            ui.selectable_value(&mut self.convention, NormalConvention::DirectX, "DirectX");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Space:");
+            ui.selectable_value(&mut self.space, NormalSpace::Tangent, "Tangent");
+            ui.selectable_value(&mut self.space, NormalSpace::World, "World");
+        });
+        ui.horizontal(|ui| {
+            ui.label("View:");
+            ui.selectable_value(&mut self.view, NormalView::Encoded, "Normals");
+            ui.selectable_value(&mut self.view, NormalView::Lit, "Lit");
+        });
+        ui.checkbox(&mut self.reconstruct_z, "Reconstruct Z from XY");
+    }
+}
+
+impl FlowSettings {
+    pub fn show_settings(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Style:");
+            ui.selectable_value(&mut self.style, FlowStyle::ColorWheel, "Color wheel");
+            ui.selectable_value(&mut self.style, FlowStyle::Arrows, "Arrows");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Stored as:");
+            ui.selectable_value(&mut self.encoding, FlowEncoding::MidGray, "Mid-gray");
+            ui.selectable_value(&mut self.encoding, FlowEncoding::Signed, "Signed");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Scale (px):");
+            ui.add(egui::DragValue::new(&mut self.scale).speed(0.1));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Max magnitude (px):");
+            ui.add(
+                egui::DragValue::new(&mut self.max_magnitude)
+                    .range(0.01..=f32::MAX)
+                    .speed(0.1),
+            );
+        });
+        if self.style == FlowStyle::Arrows {
+            ui.horizontal(|ui| {
+                ui.label("Arrow spacing:");
+                ui.add(egui::DragValue::new(&mut self.arrow_spacing).range(8.0..=256.0));
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lit_sphere_maps_the_texture_center_to_the_sphere_center() {
+        // world space normals facing the light on the left half, away from it on the right
+        let mut image = egui::ColorImage::filled([4, 1], egui::Color32::from_rgb(255, 128, 128));
+        image[(2, 0)] = egui::Color32::from_rgb(0, 128, 128);
+        image[(3, 0)] = egui::Color32::from_rgb(0, 128, 128);
+        let settings = NormalSettings {
+            space: NormalSpace::World,
+            ..Default::default()
+        };
+        let sphere = settings.lit_sphere(&Pixels::from(image), [1.0, 0.0, 0.0], 16);
+
+        let left_of_center = sphere[(7, 8)].r();
+        let right_of_center = sphere[(8, 8)].r();
+        assert!(left_of_center > 200, "{left_of_center}");
+        assert!(right_of_center < 50, "{right_of_center}");
+        // the limbs sample the texture edges, not its center
+        assert!(sphere[(0, 8)].r() > 200);
+        assert!(sphere[(15, 8)].r() < 50);
+    }
+
+    #[test]
+    fn flow_decodes_around_mid_gray() {
+        let settings = FlowSettings::default();
+        let flow = settings.decode([1.0, 0.0, 0.5, 1.0]);
+        assert!((flow.x - settings.scale).abs() < 1e-3);
+        assert!((flow.y + settings.scale).abs() < 1e-3);
+    }
+
+    #[test]
+    fn float_flow_decodes_signed_displacements() {
+        let image = image::Rgba32FImage::from_pixel(2, 1, image::Rgba([-3.0, 0.5, 0.0, 1.0]));
+        let settings = FlowSettings {
+            scale: 1.0,
+            ..FlowSettings::for_pixels(&Pixels::from(image))
+        };
+        assert_eq!(settings.encoding, FlowEncoding::Signed);
+        assert_eq!(
+            settings.decode([-3.0, 0.5, 0.0, 1.0]),
+            egui::vec2(-3.0, 0.5)
+        );
+
+        // 8 bit flow stays centered on mid-gray
+        let encoded = egui::ColorImage::filled([1, 1], egui::Color32::from_rgb(255, 0, 128));
+        let settings = FlowSettings::for_pixels(&Pixels::from(encoded));
+        assert_eq!(settings.encoding, FlowEncoding::MidGray);
+    }
+
+    #[test]
+    fn linear_normals_are_decoded_from_their_stored_values() {
+        let image = image::Rgba32FImage::from_pixel(1, 1, image::Rgba([0.5, 0.5, 1.0, 1.0]));
+        let shown = NormalSettings::default().display_image(&Pixels::from(image), [0.0; 3]);
+        assert_eq!(shown[(0, 0)], egui::Color32::from_rgb(128, 128, 255));
+    }
+}
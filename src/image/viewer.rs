@@ -1,17 +1,205 @@
-use crate::image::image::ImageAsset;
+use crate::image::annotation::{self, Annotations};
+use crate::image::color_space::ColorManagement;
+use crate::image::colormap::{
+    Colormap, Gradient, ScalarChannel, ScalarMapping, paint_colormap_strip,
+};
+use crate::image::grid::GridOverlay;
+use crate::image::image::{ImageAsset, Interpretation};
+use crate::image::mapping::Mapping;
+use crate::image::measure::Measure;
+use crate::image::tiling::Tiling;
+use crate::image::vector::{FlowSettings, FlowStyle, NormalSettings, light_direction};
+use crate::viewer::ViewerWidget;
 
+/// Asset, version of its pixels, normal settings and light the lit sphere preview was rendered for
+type SphereKey = (String, u64, NormalSettings, (f32, f32));
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
 pub struct ImageViewerWidget {
     filter_mode: egui::TextureFilter,
+    scalar: ScalarDisplay,
+    /// Light used to shade normal maps, azimuth and elevation in degrees
+    light: (f32, f32),
+    #[serde(skip)]
+    sphere_preview: Option<(SphereKey, egui::TextureHandle)>,
+    /// Region of interest in pixel coordinates, shared by all images
+    roi: Option<egui::Rect>,
+    #[serde(skip)]
+    roi_drag_start: Option<egui::Pos2>,
+    show_inset: bool,
+    /// Review notes of every image, saved with the session and projects
+    annotations: Annotations,
+    /// Ruler and area statistics, shared by all images
+    measure: Measure,
+    grid: GridOverlay,
+    /// Repeats the image to check that textures tile
+    tiling: Tiling,
+    /// Input color spaces and the working space, saved with projects
+    color: ColorManagement,
     state: ImageViewerState,
 }
 
+/// Settings of the colormap stage, applied when a single channel is displayed
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct ScalarDisplay {
+    /// `None` shows the image in color
+    channel: Option<ScalarChannel>,
+    colormap: Colormap,
+    custom_gradient: Gradient,
+    auto_range: bool,
+    /// Lower and upper percentile used for the auto range
+    percentiles: (f32, f32),
+    range: (f32, f32),
+    show_colorbar: bool,
+}
+
+impl Default for ScalarDisplay {
+    fn default() -> Self {
+        Self {
+            channel: None,
+            colormap: Colormap::Viridis,
+            custom_gradient: Gradient::default(),
+            auto_range: true,
+            percentiles: (1.0, 99.0),
+            range: (0.0, 1.0),
+            show_colorbar: true,
+        }
+    }
+}
+
+impl ScalarDisplay {
+    /// Resolve the mapping for an asset, updates the range if it is automatic
+    fn mapping(&mut self, asset: &mut ImageAsset) -> Option<ScalarMapping> {
+        let channel = self.channel?;
+        if self.auto_range {
+            let (low, high) = self.percentiles;
+            self.range = self
+                .colormap
+                .auto_range(asset.percentile_range(channel, low, high));
+        }
+        Some(ScalarMapping {
+            channel,
+            colormap: self.colormap,
+            gradient: self.custom_gradient.clone(),
+            range: self.range,
+        })
+    }
+
+    /// Draw the colorbar legend in the bottom left corner of `viewer_rect`
+    fn draw_colorbar(&self, painter: &egui::Painter, viewer_rect: egui::Rect) {
+        let margin = 12.0;
+        let bar_size = egui::vec2(14.0, 160.0);
+        let bar_rect = egui::Rect::from_min_size(
+            egui::pos2(
+                viewer_rect.left() + margin,
+                viewer_rect.bottom() - margin - bar_size.y,
+            ),
+            bar_size,
+        );
+
+        // background behind bar and labels
+        let background = bar_rect
+            .expand2(egui::vec2(6.0, 10.0))
+            .with_max_x(bar_rect.right() + 56.0);
+        painter.rect_filled(background, 4.0, egui::Color32::from_black_alpha(160));
+
+        paint_colormap_strip(
+            painter,
+            bar_rect,
+            self.colormap,
+            &self.custom_gradient,
+            true,
+        );
+        painter.rect_stroke(
+            bar_rect,
+            0.0,
+            egui::Stroke::new(1.0, egui::Color32::GRAY),
+            egui::StrokeKind::Outside,
+        );
+
+        let (min, max) = self.range;
+        for t in [0.0, 0.5, 1.0] {
+            let y = egui::lerp(bar_rect.bottom()..=bar_rect.top(), t);
+            painter.text(
+                egui::pos2(bar_rect.right() + 6.0, y),
+                egui::Align2::LEFT_CENTER,
+                format!("{:.3}", egui::lerp(min..=max, t)),
+                egui::FontId::monospace(11.0),
+                egui::Color32::WHITE,
+            );
+        }
+    }
+
+    fn show_settings(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Channel:");
+            ui.selectable_value(&mut self.channel, None, "RGB");
+            for channel in ScalarChannel::ALL {
+                ui.selectable_value(&mut self.channel, Some(channel), channel.label());
+            }
+        });
+
+        if self.channel.is_none() {
+            return;
+        }
+
+        egui::ComboBox::from_label("Colormap")
+            .selected_text(self.colormap.label())
+            .show_ui(ui, |ui| {
+                for colormap in Colormap::ALL {
+                    ui.selectable_value(&mut self.colormap, colormap, colormap.label());
+                }
+            });
+
+        ui.checkbox(&mut self.auto_range, "Auto range");
+        ui.horizontal(|ui| {
+            if self.auto_range {
+                ui.label("Percentiles:");
+                let (low, high) = &mut self.percentiles;
+                ui.add(egui::DragValue::new(low).range(0.0..=100.0).speed(0.1));
+                ui.add(egui::DragValue::new(high).range(0.0..=100.0).speed(0.1));
+            } else {
+                ui.label("Range:");
+                // data in metres or beyond 1 is dragged in steps of its range
+                let (min, max) = &mut self.range;
+                let speed = ((*max - *min).abs() * 0.005).max(0.001);
+                ui.add(egui::DragValue::new(min).speed(speed));
+                ui.add(egui::DragValue::new(max).speed(speed));
+            }
+        });
+
+        ui.checkbox(&mut self.show_colorbar, "Colorbar");
+
+        if self.colormap == Colormap::Custom {
+            ui.collapsing("Gradient", |ui| self.custom_gradient.show_editor(ui));
+        }
+    }
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
 struct ImageViewerState {
     zoom: f32,
     pan_offset: egui::Vec2,
+    #[serde(skip)]
     image_size: egui::Vec2,
+    #[serde(skip)]
     viewer_rect: egui::Rect,
 }
 
+impl Default for ImageViewerState {
+    fn default() -> Self {
+        Self {
+            zoom: 1.0,
+            pan_offset: egui::Vec2::ZERO,
+            image_size: egui::Vec2::ZERO,
+            viewer_rect: egui::Rect::NOTHING,
+        }
+    }
+}
+
 impl ImageViewerState {
     pub fn pan(&mut self, delta: egui::Vec2) {
         self.pan_offset += delta;
@@ -33,6 +221,15 @@ impl ImageViewerState {
         egui::Rect::from_center_size(self.viewer_rect.center() + self.pan_offset, zoomed_size)
     }
 
+    pub fn screen_to_image(&self, pos: egui::Pos2) -> egui::Pos2 {
+        let image_rect = self.get_image_rect();
+        ((pos - image_rect.min) / self.zoom).to_pos2()
+    }
+
+    pub fn image_to_screen(&self, pos: egui::Pos2) -> egui::Pos2 {
+        self.get_image_rect().min + pos.to_vec2() * self.zoom
+    }
+
     pub fn get_zoom_percent(&self) -> f32 {
         self.zoom * 100.0
     }
@@ -42,33 +239,220 @@ impl Default for ImageViewerWidget {
     fn default() -> Self {
         Self {
             filter_mode: egui::TextureFilter::Nearest,
-            state: ImageViewerState {
-                zoom: 1.0,
-                pan_offset: egui::Vec2::ZERO,
-                image_size: egui::Vec2::ZERO,
-                viewer_rect: egui::Rect::NOTHING,
-            },
+            scalar: ScalarDisplay::default(),
+            light: (135.0, 45.0),
+            sphere_preview: None,
+            roi: None,
+            roi_drag_start: None,
+            show_inset: true,
+            annotations: Annotations::default(),
+            measure: Measure::default(),
+            grid: GridOverlay::default(),
+            tiling: Tiling::default(),
+            color: ColorManagement::default(),
+            state: ImageViewerState::default(),
         }
     }
 }
 
 impl ImageViewerWidget {
-    /// Draw the image given the current state
-    fn draw_image(
+    pub fn roi(&self) -> Option<egui::Rect> {
+        self.roi
+    }
+
+    pub fn annotations(&self) -> &Annotations {
+        &self.annotations
+    }
+
+    pub fn annotations_mut(&mut self) -> &mut Annotations {
+        &mut self.annotations
+    }
+
+    pub fn color(&self) -> &ColorManagement {
+        &self.color
+    }
+
+    pub fn color_mut(&mut self) -> &mut ColorManagement {
+        &mut self.color
+    }
+
+    /// Whether the asset is shown as data, through a colormap or as vectors, whose values are
+    /// used as decoded rather than converted into the working space
+    pub fn shows_data(&self, asset: &ImageAsset) -> bool {
+        asset.interpretation.is_data() || self.scalar.channel.is_some()
+    }
+
+    pub fn measure(&self) -> &Measure {
+        &self.measure
+    }
+
+    pub fn measure_mut(&mut self) -> &mut Measure {
+        &mut self.measure
+    }
+
+    /// The pixels of the asset as they are shown, after the mapping stage
+    pub fn displayed_image(&mut self, asset: &mut ImageAsset) -> egui::ColorImage {
+        match self.mapping(asset) {
+            Some(mapping) => mapping.apply(&asset.pixels),
+            None => asset.image.clone(),
+        }
+    }
+
+    /// Shift + drag selects a region of interest, returns true while selecting
+    fn handle_roi_drag(&mut self, ui: &egui::Ui, response: &egui::Response) -> bool {
+        if response.drag_started() && ui.input(|i| i.modifiers.shift) {
+            self.roi_drag_start = response
+                .interact_pointer_pos()
+                .map(|pos| self.state.screen_to_image(pos).round());
+        }
+
+        let Some(start) = self.roi_drag_start else {
+            return false;
+        };
+
+        if let Some(pos) = response.interact_pointer_pos() {
+            let end = self.state.screen_to_image(pos).round();
+            self.roi = Some(egui::Rect::from_two_pos(start, end));
+        }
+
+        if response.drag_stopped() {
+            self.roi_drag_start = None;
+            // a click without movement clears the region
+            if self.roi.is_some_and(|roi| roi.area() < 1.0) {
+                self.roi = None;
+            }
+        }
+        true
+    }
+
+    /// Outline the region of interest and show it magnified in the bottom right corner
+    fn draw_roi(&self, painter: &egui::Painter, asset: &mut ImageAsset, mapping: Option<&Mapping>) {
+        let Some(roi) = self.roi else {
+            return;
+        };
+        let stroke = egui::Stroke::new(1.5, egui::Color32::YELLOW);
+        let roi_on_screen = egui::Rect::from_min_max(
+            self.state.image_to_screen(roi.min),
+            self.state.image_to_screen(roi.max),
+        );
+        painter.rect_stroke(roi_on_screen, 0.0, stroke, egui::StrokeKind::Outside);
+
+        if !self.show_inset || self.roi_drag_start.is_some() || roi.area() < 1.0 {
+            return;
+        }
+
+        let margin = 12.0;
+        let viewer_rect = self.state.viewer_rect;
+        let max_side = viewer_rect.size().min_elem() * 0.35;
+        let inset_size = roi.size() * (max_side / roi.size().max_elem());
+        let inset_rect = egui::Rect::from_min_size(
+            viewer_rect.max - inset_size - egui::vec2(margin, margin),
+            inset_size,
+        );
+
+        painter.rect_filled(inset_rect, 0.0, egui::Color32::BLACK);
+        asset.paint(painter, self.filter_mode, mapping, roi, inset_rect);
+        painter.rect_stroke(inset_rect, 0.0, stroke, egui::StrokeKind::Outside);
+    }
+
+    fn light_direction(&self) -> [f32; 3] {
+        light_direction(self.light.0, self.light.1)
+    }
+
+    /// Mapping stage for the asset given its interpretation, `None` shows the raw image
+    fn mapping(&mut self, asset: &mut ImageAsset) -> Option<Mapping> {
+        match asset.interpretation {
+            Interpretation::Color => self.scalar.mapping(asset).map(Mapping::Scalar),
+            Interpretation::Normal(settings) => {
+                Some(Mapping::Normal(settings, self.light_direction()))
+            }
+            Interpretation::Flow(settings) => {
+                (settings.style == FlowStyle::ColorWheel).then_some(Mapping::FlowWheel(settings))
+            }
+        }
+    }
+
+    /// Draw flow vectors as arrows on a regular grid of screen positions
+    fn draw_flow_arrows(
         &self,
         painter: &egui::Painter,
-        image_rect: egui::Rect,
-        texture: &egui::TextureHandle,
+        asset: &ImageAsset,
+        settings: &FlowSettings,
     ) {
-        painter.image(
-            texture.id(),
-            image_rect,
-            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
-            egui::Color32::WHITE,
+        let image_rect = self.state.get_image_rect();
+        let visible = image_rect.intersect(self.state.viewer_rect);
+        let [width, height] = asset.pixels.size();
+        // evicted and failed images are empty
+        if !visible.is_positive() || width == 0 || height == 0 {
+            return;
+        }
+
+        // arrows of max magnitude span one grid cell
+        let spacing = settings.arrow_spacing.max(8.0);
+        let arrow_scale = spacing / settings.max_magnitude.max(f32::EPSILON);
+        let stroke = egui::Stroke::new(1.5, egui::Color32::YELLOW);
+
+        let mut y = visible.top() + spacing * 0.5;
+        while y < visible.bottom() {
+            let mut x = visible.left() + spacing * 0.5;
+            while x < visible.right() {
+                let pixel = (egui::pos2(x, y) - image_rect.min) / self.state.zoom;
+                let px = (pixel.x as usize).min(width - 1);
+                let py = (pixel.y as usize).min(height - 1);
+                let flow = settings.decode(asset.pixels.get(px, py));
+                painter.arrow(egui::pos2(x, y), flow * arrow_scale, stroke);
+                x += spacing;
+            }
+            y += spacing;
+        }
+    }
+
+    fn show_sphere_preview(
+        &mut self,
+        ui: &mut egui::Ui,
+        asset: &ImageAsset,
+        settings: NormalSettings,
+    ) {
+        ui.horizontal(|ui| {
+            ui.label("Light:");
+            ui.add(
+                egui::DragValue::new(&mut self.light.0)
+                    .range(0.0..=360.0)
+                    .suffix("°"),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.light.1)
+                    .range(0.0..=90.0)
+                    .suffix("°"),
+            );
+        });
+
+        let key = (
+            asset.id.clone(),
+            asset.pixels_version(),
+            settings,
+            self.light,
         );
+        if self
+            .sphere_preview
+            .as_ref()
+            .is_none_or(|(cached_key, _)| *cached_key != key)
+        {
+            let image = settings.lit_sphere(&asset.pixels, self.light_direction(), 160);
+            let texture = ui
+                .ctx()
+                .load_texture("lit_sphere", image, egui::TextureOptions::LINEAR);
+            self.sphere_preview = Some((key, texture));
+        }
+
+        if let Some((_, texture)) = &self.sphere_preview {
+            ui.image((texture.id(), egui::vec2(160.0, 160.0)));
+        }
     }
+}
 
-    pub fn show_viewer(&mut self, ui: &mut egui::Ui, asset: &mut ImageAsset) {
+impl ViewerWidget<ImageAsset> for ImageViewerWidget {
+    fn show_viewer(&mut self, ui: &mut egui::Ui, asset: &mut ImageAsset) {
         // Allocate space for viewer
         let (response, painter) =
             ui.allocate_painter(ui.available_size(), egui::Sense::click_and_drag());
@@ -88,18 +472,80 @@ impl ImageViewerWidget {
             }
         }
 
-        // Handle drag to pan
-        if response.dragged() {
-            self.state.pan(response.drag_delta());
+        // Handle drag to pan, unless a region of interest is being selected. While annotating
+        // or measuring the left button draws and the other buttons pan
+        let state = &self.state;
+        let annotation_key = annotation::key(asset);
+        if self.annotations.tool.is_some() || self.measure.tool.is_some() {
+            let to_image = |pos| state.screen_to_image(pos);
+            self.annotations
+                .handle_input(ui, &response, &annotation_key, to_image, state.zoom);
+            self.measure.handle_input(&response, to_image, state.zoom);
+            if response.dragged_by(egui::PointerButton::Middle)
+                || response.dragged_by(egui::PointerButton::Secondary)
+            {
+                self.state.pan(response.drag_delta());
+            }
+        } else {
+            let selecting_roi = self.handle_roi_drag(ui, &response);
+            if response.dragged() && !selecting_roi {
+                self.state.pan(response.drag_delta());
+            }
         }
 
-        // Draw Image
-        let texture = asset.get_texture(ui.ctx(), self.filter_mode);
+        // Draw Image, through a mapping stage if the pixels are not shown as is
+        let mapping = self.mapping(asset);
         let image_rect = self.state.get_image_rect();
-        self.draw_image(&painter, image_rect, &texture);
+        if self.tiling.is_active() {
+            self.tiling.paint(
+                &painter,
+                asset,
+                self.filter_mode,
+                mapping.as_ref(),
+                image_rect,
+            );
+        } else {
+            let full_image = egui::Rect::from_min_size(egui::Pos2::ZERO, self.state.image_size);
+            asset.paint(
+                &painter,
+                self.filter_mode,
+                mapping.as_ref(),
+                full_image,
+                image_rect,
+            );
+        }
+        self.grid.paint(
+            &painter,
+            &asset.pixels,
+            image_rect,
+            response.rect,
+            self.state.zoom,
+        );
+        self.draw_roi(&painter, asset, mapping.as_ref());
+        let state = &self.state;
+        self.measure
+            .paint(&painter, |pos| state.image_to_screen(pos));
+        self.annotations.paint(
+            &painter,
+            &annotation_key,
+            |pos| state.image_to_screen(pos),
+            state.zoom,
+        );
+
+        match asset.interpretation {
+            Interpretation::Color => {
+                if self.scalar.channel.is_some() && self.scalar.show_colorbar {
+                    self.scalar.draw_colorbar(&painter, response.rect);
+                }
+            }
+            Interpretation::Flow(settings) if settings.style == FlowStyle::Arrows => {
+                self.draw_flow_arrows(&painter, asset, &settings);
+            }
+            _ => {}
+        }
     }
 
-    pub fn show_info(&mut self, ui: &mut egui::Ui) {
+    fn show_info(&mut self, ui: &mut egui::Ui, asset: &mut ImageAsset) {
         ui.label(egui::RichText::new(format!(
             "({}, {}) [{:.2}%]",
             self.state.image_size.x as u32,
@@ -116,11 +562,104 @@ impl ImageViewerWidget {
             );
             ui.selectable_value(&mut self.filter_mode, egui::TextureFilter::Linear, "Linear");
         });
+        self.grid.show_settings(ui);
+        self.tiling.show_settings(ui, asset);
+        self.color
+            .show_settings(ui, &asset.id, asset.detected_color_space());
+
+        if let Some(roi) = self.roi {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "ROI: ({}, {}) {}x{}",
+                    roi.min.x,
+                    roi.min.y,
+                    roi.width(),
+                    roi.height()
+                ));
+                ui.checkbox(&mut self.show_inset, "Inset");
+                if ui.small_button("Clear").clicked() {
+                    self.roi = None;
+                }
+            });
+        }
+
+        // one tool at a time takes the left button
+        let (annotation_tool, measure_tool) = (self.annotations.tool, self.measure.tool);
+        self.annotations.show_settings(ui, &annotation::key(asset));
+        self.measure
+            .show_settings(ui, asset.pixels_version(), &asset.pixels);
+        if self.annotations.tool != annotation_tool && self.annotations.tool.is_some() {
+            self.measure.tool = None;
+        } else if self.measure.tool != measure_tool && self.measure.tool.is_some() {
+            self.annotations.tool = None;
+        }
+
+        let mut interpretation = asset.interpretation;
+        ui.horizontal(|ui| {
+            ui.label("Interpret as:");
+            let options = [
+                Interpretation::Color,
+                Interpretation::Normal(NormalSettings::default()),
+                Interpretation::Flow(FlowSettings::default()),
+            ];
+            for option in options {
+                let selected =
+                    std::mem::discriminant(&option) == std::mem::discriminant(&interpretation);
+                if ui.selectable_label(selected, option.label()).clicked() && !selected {
+                    interpretation = match option {
+                        // float flow may hold signed displacements
+                        Interpretation::Flow(_) => {
+                            Interpretation::Flow(FlowSettings::for_pixels(&asset.pixels))
+                        }
+                        option => option,
+                    };
+                }
+            }
+        });
+
+        match &mut interpretation {
+            Interpretation::Color => self.scalar.show_settings(ui),
+            Interpretation::Normal(settings) => {
+                settings.show_settings(ui);
+                self.show_sphere_preview(ui, asset, *settings);
+            }
+            Interpretation::Flow(settings) => settings.show_settings(ui),
+        }
+        asset.interpretation = interpretation;
     }
 
-    pub fn show_help(&mut self, ui: &mut egui::Ui) {
+    fn show_help(&mut self, ui: &mut egui::Ui) {
         ui.add(egui::Label::new("Image Viewer Help:"));
         ui.add(egui::Label::new("- Scroll to zoom in/out"));
         ui.add(egui::Label::new("- Click and drag to pan the image"));
+        ui.add(egui::Label::new(
+            "- Shift + drag to select a region of interest, shared by all images",
+        ));
+        ui.add(egui::Label::new(
+            "- Pick a channel in the info window to view it through a colormap",
+        ));
+        ui.add(egui::Label::new(
+            "- Interpret images as normal maps or flow fields in the info window",
+        ));
+        ui.add(egui::Label::new(
+            "- Pick an annotation tool in the info window to draw notes on the image, \
+            right click removes one and middle drag pans",
+        ));
+        ui.add(egui::Label::new(
+            "- Zoom in to see the pixel grid, pixel values and block or tile boundaries \
+            are toggled in the info window",
+        ));
+        ui.add(egui::Label::new(
+            "- Color spaces are read from the files and can be overridden in the info window, \
+            images are compared in the working space",
+        ));
+        ui.add(egui::Label::new(
+            "- Measure lengths, profiles and area statistics with the tools in the info window, \
+            shift snaps the ruler to pixel centers",
+        ));
+        ui.add(egui::Label::new(
+            "- Tile textures 3x3 or over the whole viewer in the info window, offset them by half \
+            and highlight the seams to check that they repeat without visible edges",
+        ));
     }
 }
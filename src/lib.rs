@@ -2,10 +2,29 @@
 
 mod app;
 mod asset;
+mod clipboard;
+mod diagnostics;
+mod dialog;
+mod export;
+mod figure;
+mod flicker;
+mod gallery;
 mod image;
+mod loader;
+mod metrics;
 mod model_asset;
 mod model_viewer;
+mod pairing;
+mod project;
+mod registry;
 mod selector;
+mod sequence;
+mod thumbnail;
+mod timeline;
 mod viewer;
+mod watcher;
 
 pub use app::App;
+pub use image::pixels::Pixels;
+pub use model_asset::MeshData;
+pub use registry::{AssetLoader, AssetRegistry, Decoder};
@@ -0,0 +1,243 @@
+use crate::asset::{Asset as _, AssetEnum};
+use crate::registry::AssetRegistry;
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+
+/// Id of a load, its decoded asset and the warnings of decoding it
+type JobResult = (u64, Result<AssetEnum>, Vec<String>);
+
+struct Job {
+    id: u64,
+    file: egui::DroppedFile,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Job {
+    fn run(self, ctx: &egui::Context, registry: &AssetRegistry, results: &mpsc::Sender<JobResult>) {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut warnings = vec![];
+        let result = registry.load_with_warnings(&self.file, &mut warnings);
+        if results.send((self.id, result, warnings)).is_ok() {
+            ctx.request_repaint();
+        }
+    }
+}
+
+struct PendingLoad {
+    id: u64,
+    name: String,
+    /// Id the asset gets once loaded, e.g. its label in a project
+    label: Option<String>,
+    select: bool,
+    cancelled: Arc<AtomicBool>,
+    result: Option<Result<AssetEnum>>,
+    warnings: Vec<String>,
+}
+
+/// A finished load, successful or not
+pub struct Loaded {
+    /// Id returned by `Loader::load`
+    pub id: u64,
+    pub name: String,
+    pub result: Result<AssetEnum>,
+    /// What went wrong without failing the load
+    pub warnings: Vec<String>,
+    /// Whether the asset should be selected once added
+    pub select: bool,
+}
+
+/// Decodes assets off the UI thread natively, on a pool of worker threads.
+///
+/// The web build has no threads: it decodes one file per frame on the page's thread, so the
+/// page repaints and loads can be cancelled between files, but it is unresponsive while a file
+/// decodes
+pub struct Loader {
+    registry: Arc<AssetRegistry>,
+    pending: Vec<PendingLoad>,
+    next_id: u64,
+    result_sender: mpsc::Sender<JobResult>,
+    result_receiver: mpsc::Receiver<JobResult>,
+    #[cfg(not(target_arch = "wasm32"))]
+    job_sender: Option<mpsc::Sender<Job>>,
+    /// Jobs decoded by `poll`, one per frame
+    #[cfg(target_arch = "wasm32")]
+    queue: std::collections::VecDeque<(egui::Context, Job)>,
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Self::new(Arc::new(AssetRegistry::default()))
+    }
+}
+
+impl Loader {
+    pub fn new(registry: Arc<AssetRegistry>) -> Self {
+        let (result_sender, result_receiver) = mpsc::channel();
+        Self {
+            registry,
+            pending: vec![],
+            next_id: 0,
+            result_sender,
+            result_receiver,
+            #[cfg(not(target_arch = "wasm32"))]
+            job_sender: None,
+            #[cfg(target_arch = "wasm32")]
+            queue: Default::default(),
+        }
+    }
+
+    /// Queue a file, `label` replaces the id of the loaded asset. Returns the id of the load,
+    /// which its `Loaded` carries
+    pub fn load(
+        &mut self,
+        ctx: &egui::Context,
+        file: egui::DroppedFile,
+        label: Option<String>,
+        select: bool,
+    ) -> u64 {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.push(PendingLoad {
+            id,
+            name: label.clone().unwrap_or_else(|| file.name.clone()),
+            label,
+            select,
+            cancelled: cancelled.clone(),
+            result: None,
+            warnings: vec![],
+        });
+        self.spawn(
+            ctx,
+            Job {
+                id,
+                file,
+                cancelled,
+            },
+        );
+        id
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn spawn(&mut self, ctx: &egui::Context, job: Job) {
+        let job_sender = self.job_sender.get_or_insert_with(|| {
+            let (job_sender, job_receiver) = mpsc::channel::<Job>();
+            let job_receiver = Arc::new(std::sync::Mutex::new(job_receiver));
+            let workers = std::thread::available_parallelism().map_or(2, |n| n.get().min(4));
+            for _ in 0..workers {
+                let job_receiver = job_receiver.clone();
+                let result_sender = self.result_sender.clone();
+                let registry = self.registry.clone();
+                let ctx = ctx.clone();
+                std::thread::spawn(move || {
+                    loop {
+                        let job = match job_receiver.lock() {
+                            Ok(job_receiver) => job_receiver.recv(),
+                            Err(_) => return,
+                        };
+                        let Ok(job) = job else {
+                            return;
+                        };
+                        job.run(&ctx, &registry, &result_sender);
+                    }
+                });
+            }
+            job_sender
+        });
+        if let Err(mpsc::SendError(job)) = job_sender.send(job) {
+            // the workers are gone, load on this thread instead
+            job.run(ctx, &self.registry, &self.result_sender);
+        }
+    }
+
+    /// The web build has no threads, `poll` decodes the queued files one per frame
+    #[cfg(target_arch = "wasm32")]
+    fn spawn(&mut self, ctx: &egui::Context, job: Job) {
+        self.queue.push_back((ctx.clone(), job));
+        ctx.request_repaint();
+    }
+
+    /// Finished loads, in the order they were queued. On the web this decodes the next queued
+    /// file first
+    pub fn poll(&mut self) -> Vec<Loaded> {
+        #[cfg(target_arch = "wasm32")]
+        while let Some((ctx, job)) = self.queue.pop_front() {
+            if !job.cancelled.load(Ordering::Relaxed) {
+                // requests the repaint that decodes the next one
+                job.run(&ctx, &self.registry, &self.result_sender);
+                break;
+            }
+        }
+
+        while let Ok((id, result, warnings)) = self.result_receiver.try_recv() {
+            if let Some(pending) = self.pending.iter_mut().find(|pending| pending.id == id) {
+                pending.result = Some(result);
+                pending.warnings = warnings;
+            }
+        }
+
+        let finished = self
+            .pending
+            .iter()
+            .take_while(|pending| pending.result.is_some())
+            .count();
+        self.pending
+            .drain(..finished)
+            .filter_map(|pending| {
+                let result = pending.result?.map(|mut asset| {
+                    if let Some(label) = pending.label {
+                        asset.set_id(label);
+                    }
+                    asset
+                });
+                Some(Loaded {
+                    id: pending.id,
+                    name: pending.name,
+                    result,
+                    warnings: pending.warnings,
+                    select: pending.select,
+                })
+            })
+            .collect()
+    }
+
+    pub fn is_loading(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Names of the files that are still loading
+    pub fn pending_names(&self) -> impl Iterator<Item = &str> {
+        self.pending.iter().map(|pending| pending.name.as_str())
+    }
+
+    /// Stop a pending load, by its position in `pending_names`
+    pub fn cancel(&mut self, index: usize) {
+        if index < self.pending.len() {
+            let pending = self.pending.remove(index);
+            pending.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Stop the pending loads that give their asset one of `labels` as id
+    pub fn cancel_labeled(&mut self, labels: &[String]) {
+        self.pending.retain(|pending| {
+            let labeled = pending
+                .label
+                .as_ref()
+                .is_some_and(|label| labels.contains(label));
+            if labeled {
+                pending.cancelled.store(true, Ordering::Relaxed);
+            }
+            !labeled
+        });
+    }
+
+    pub fn cancel_all(&mut self) {
+        for pending in self.pending.drain(..) {
+            pending.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+}
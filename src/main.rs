@@ -20,7 +20,14 @@ fn main() -> eframe::Result {
     eframe::run_native(
         "TexComp",
         native_options,
-        Box::new(|cc| Ok(Box::new(tex_comp::App::new(cc)))),
+        Box::new(|cc| {
+            let mut app = tex_comp::App::new(cc);
+            app.open_paths(
+                &cc.egui_ctx,
+                std::env::args_os().skip(1).map(std::path::PathBuf::from),
+            );
+            Ok(Box::new(app))
+        }),
     )
 }
 
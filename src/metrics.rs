@@ -0,0 +1,107 @@
+use crate::image::pixels::Pixels;
+
+/// Error metrics of an image against a reference, computed on RGB in the working space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageMetrics {
+    pub mae: f32,
+    pub mse: f32,
+    /// Peak signal to noise ratio in dB, infinite for identical images
+    pub psnr: f32,
+}
+
+impl ImageMetrics {
+    /// Compare two images of the same size, `None` if the sizes differ
+    pub fn compute(image: &Pixels, reference: &Pixels) -> Option<Self> {
+        if image.size() != reference.size() || image.is_empty() {
+            return None;
+        }
+
+        let mut abs_sum = 0.0f64;
+        let mut square_sum = 0.0f64;
+        for (a, b) in image.rgba().zip(reference.rgba()) {
+            for (ca, cb) in a.into_iter().zip(b).take(3) {
+                let d = f64::from(ca - cb);
+                abs_sum += d.abs();
+                square_sum += d * d;
+            }
+        }
+
+        let [width, height] = image.size();
+        let count = (width * height * 3) as f64;
+        let mse = square_sum / count;
+        Some(Self {
+            mae: (abs_sum / count) as f32,
+            mse: mse as f32,
+            psnr: (-10.0 * mse.log10()) as f32,
+        })
+    }
+
+    pub fn caption(&self) -> String {
+        format!(
+            "PSNR {:.2} dB  MSE {:.5}  MAE {:.4}",
+            self.psnr, self.mse, self.mae
+        )
+    }
+}
+
+/// One of the metrics, to sort and tabulate by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Metric {
+    #[default]
+    Psnr,
+    Mse,
+    Mae,
+}
+
+impl Metric {
+    pub const ALL: [Self; 3] = [Self::Psnr, Self::Mse, Self::Mae];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Psnr => "PSNR",
+            Self::Mse => "MSE",
+            Self::Mae => "MAE",
+        }
+    }
+
+    pub fn value(self, metrics: &ImageMetrics) -> f32 {
+        match self {
+            Self::Psnr => metrics.psnr,
+            Self::Mse => metrics.mse,
+            Self::Mae => metrics.mae,
+        }
+    }
+
+    pub fn format(self, metrics: &ImageMetrics) -> String {
+        match self {
+            Self::Psnr => format!("{:.2} dB", metrics.psnr),
+            Self::Mse => format!("{:.5}", metrics.mse),
+            Self::Mae => format!("{:.4}", metrics.mae),
+        }
+    }
+
+    /// Whether higher values are closer to the reference
+    pub fn higher_is_better(self) -> bool {
+        self == Self::Psnr
+    }
+}
+
+/// Absolute per channel difference scaled by `gain`, `None` if the sizes differ
+pub fn diff_image(image: &Pixels, reference: &Pixels, gain: f32) -> Option<egui::ColorImage> {
+    if image.size() != reference.size() {
+        return None;
+    }
+
+    let pixels = image
+        .rgba()
+        .zip(reference.rgba())
+        .map(|(a, b)| {
+            let [r, g, b] = std::array::from_fn(|i| {
+                let d = (a[i] - b[i]).abs() * gain;
+                (d.clamp(0.0, 1.0) * 255.0).round() as u8
+            });
+            egui::Color32::from_rgb(r, g, b)
+        })
+        .collect();
+    Some(egui::ColorImage::new(image.size(), pixels))
+}
@@ -1,34 +1,57 @@
 use crate::asset::Asset;
-use anyhow::{Context, Ok, bail};
-use three_d::{self, CpuGeometry};
-use three_d_asset;
+use crate::thumbnail::Thumbnail;
+use anyhow::{Context as _, Ok, bail};
+use std::path::{Path, PathBuf};
+use three_d::CpuGeometry;
 use three_d_asset::Model;
 use three_d_asset::io::RawAssets;
 
+/// Triangle mesh decoded from a file
+pub struct MeshData {
+    pub positions: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+/// Decodes the bytes of a file into a mesh
+pub type MeshDecodeFn = fn(&[u8]) -> anyhow::Result<MeshData>;
+
+#[derive(Clone)]
 pub struct MeshModel {
     name: String,
+    file_path: Option<PathBuf>,
     pub verts: Vec<[f32; 3]>,
     pub indices: Vec<u32>,
+    thumbnail: Thumbnail,
 }
 
-impl Asset for MeshModel {
-    fn from_dropped_file(ctx: &egui::Context, file: &egui::DroppedFile) -> anyhow::Result<Self>
-    where
-        Self: Sized,
-    {
-        let name = file.name.clone();
-        let bytes = file.bytes.as_ref().context("No bytes in dropped file")?;
+impl MeshModel {
+    /// A mesh decoded from the `bytes` of `file`
+    pub fn from_file(
+        file: &egui::DroppedFile,
+        bytes: &[u8],
+        decode: MeshDecodeFn,
+    ) -> anyhow::Result<Self> {
+        let mesh = decode(bytes)?;
+        Ok(Self {
+            name: file.name.clone(),
+            file_path: file.path.clone(),
+            thumbnail: Thumbnail::of_mesh(&mesh.positions, &mesh.indices),
+            verts: mesh.positions,
+            indices: mesh.indices,
+        })
+    }
 
+    /// Decode the first mesh of a format supported by three-d-asset, given by `extension`
+    pub fn mesh_from_bytes(bytes: &[u8], extension: &str) -> anyhow::Result<MeshData> {
+        let name = format!("model.{extension}");
         let mut raws = RawAssets::new();
         raws.insert(&name, bytes.to_vec());
 
         let model = raws
-            .deserialize::<Model>(&file.name)
-            .context("Failed to Deserialize")?;
+            .deserialize::<Model>(&name)
+            .with_context(|| format!("Failed to deserialize the {extension} file"))?;
 
-        // let model = three_d_asset::io::deserialize::<Model>(bytes.to_vec())
-        // .context("Failed to deserialize")?;
-        let prim = model.geometries.get(0).context("No geometry in model")?;
+        let prim = model.geometries.first().context("No geometry in model")?;
         let geo = &prim.geometry;
 
         let CpuGeometry::Triangles(mesh) = geo else {
@@ -44,14 +67,37 @@ impl Asset for MeshModel {
 
         let indices = mesh.indices.to_u32().context("Require Indices")?;
 
-        Ok(MeshModel {
-            name,
-            verts: positions,
-            indices: indices,
-        })
+        Ok(MeshData { positions, indices })
     }
+}
 
+impl Asset for MeshModel {
     fn get_id(&self) -> &str {
         &self.name
     }
+
+    fn set_id(&mut self, id: String) {
+        self.name = id;
+    }
+
+    fn get_file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.verts.len() * size_of::<[f32; 3]>()
+            + self.indices.len() * size_of::<u32>()
+            + self.thumbnail.memory_usage()
+    }
+
+    fn take_reloaded(&mut self, reloaded: Self) -> anyhow::Result<()> {
+        self.verts = reloaded.verts;
+        self.indices = reloaded.indices;
+        self.thumbnail = reloaded.thumbnail;
+        Ok(())
+    }
+
+    fn thumbnail(&mut self) -> &mut Thumbnail {
+        &mut self.thumbnail
+    }
 }
@@ -0,0 +1,594 @@
+use crate::asset::{AssetEnum, dropped_file_from_path};
+use crate::image::color_space::ColorSpace;
+use crate::image::pixels::Pixels;
+use crate::loader::Loader;
+use crate::metrics::{ImageMetrics, Metric};
+use crate::registry::AssetRegistry;
+use anyhow::Context as _;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The files of the folders that share a key, e.g. `gt/0001.png` and `ours/0001.exr`
+pub struct PairSet {
+    pub key: String,
+    /// File in each folder, `None` where a folder has no file with the key
+    pub files: Vec<Option<PathBuf>>,
+    /// Metrics of each folder's image against the first folder's, once computed
+    pub metrics: Option<Vec<Option<ImageMetrics>>>,
+}
+
+/// Decodes the sets one after the other to fill in the summary
+struct Summary {
+    loader: Loader,
+    /// Set being decoded
+    set: usize,
+    /// Folders of the files queued for `set`, in order
+    queued: Vec<usize>,
+    /// Loads of `set` that finished so far
+    received: usize,
+    /// Decoded images of `set` by folder
+    images: Vec<Option<Pixels>>,
+}
+
+/// Folders compared file by file, e.g. `gt/`, `ours/` and `baseline/`
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Pairing {
+    /// The first folder is the reference the others are measured against
+    pub folders: Vec<PathBuf>,
+    /// Regex matched against the file names, its first capture group or else the match is the key.
+    /// Files are paired by stem when empty
+    pub pattern: String,
+    /// Set the navigator is at
+    pub current: usize,
+    /// Metric the summary lists
+    metric: Metric,
+    #[serde(skip)]
+    pub sets: Vec<PairSet>,
+    /// Set whose files are the loaded assets, until something else is opened
+    #[serde(skip)]
+    pub shown: Option<usize>,
+    /// Folder the summary is sorted by, by key when `None`
+    #[serde(skip)]
+    sort_column: Option<usize>,
+    #[serde(skip)]
+    sort_descending: bool,
+    #[serde(skip)]
+    summary: Option<Summary>,
+    #[serde(skip)]
+    folder_input: String,
+    #[serde(skip)]
+    error: Option<String>,
+}
+
+impl Pairing {
+    pub fn add_folder(&mut self, folder: PathBuf) {
+        if !self.folders.contains(&folder) {
+            self.folders.push(folder);
+            self.forget_sets();
+        }
+    }
+
+    /// The sets refer to folders by position, they are stale once the folders change
+    fn forget_sets(&mut self) {
+        self.stop_summary();
+        self.sets.clear();
+        self.shown = None;
+    }
+
+    /// Name of each folder, the id its image gets when a set is opened
+    pub fn labels(&self) -> Vec<String> {
+        let names: Vec<String> = self
+            .folders
+            .iter()
+            .map(|folder| {
+                folder.file_name().map_or_else(
+                    || folder.display().to_string(),
+                    |name| name.to_string_lossy().into_owned(),
+                )
+            })
+            .collect();
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                if names.iter().filter(|other| *other == name).count() > 1 {
+                    format!("{name} ({})", i + 1)
+                } else {
+                    name.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// List the folders and pair their files by key, sorted by key
+    pub fn scan(&mut self, registry: &AssetRegistry) -> anyhow::Result<()> {
+        self.forget_sets();
+        self.error = None;
+        let pattern = self.pattern.trim();
+        let regex = if pattern.is_empty() {
+            None
+        } else {
+            Some(regex_lite::Regex::new(pattern).context("Invalid pattern")?)
+        };
+
+        let count = self.folders.len();
+        let mut sets: BTreeMap<String, Vec<Option<PathBuf>>> = BTreeMap::new();
+        for (i, folder) in self.folders.iter().enumerate() {
+            let entries = std::fs::read_dir(folder)
+                .with_context(|| format!("Failed to list {}", folder.display()))?;
+            let mut files: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && registry.supports_extension(path))
+                .collect();
+            files.sort();
+
+            for path in files {
+                let Some(key) = pair_key(&path, regex.as_ref()) else {
+                    continue;
+                };
+                // the first file with a key wins when several in a folder share it
+                let files = sets.entry(key).or_insert_with(|| vec![None; count]);
+                if files[i].is_none() {
+                    files[i] = Some(path);
+                }
+            }
+        }
+
+        self.sets = sets
+            .into_iter()
+            .map(|(key, files)| PairSet {
+                key,
+                files,
+                metrics: None,
+            })
+            .collect();
+        self.current = self.current.min(self.sets.len().saturating_sub(1));
+        Ok(())
+    }
+
+    /// Move the navigator to a set, returns its files with the ids they get
+    pub fn show_set(&mut self, index: usize) -> Vec<(String, PathBuf)> {
+        let Some(set) = self.sets.get(index) else {
+            return vec![];
+        };
+        let files = self
+            .labels()
+            .into_iter()
+            .zip(&set.files)
+            .filter_map(|(label, file)| Some((label, file.clone()?)))
+            .collect();
+        self.current = index;
+        self.shown = Some(index);
+        files
+    }
+
+    /// The shown set, if its metrics are still to be computed
+    pub fn needs_metrics(&self) -> Option<usize> {
+        let index = self.shown?;
+        let set = self.sets.get(index)?;
+        set.metrics.is_none().then_some(index)
+    }
+
+    pub fn set_metrics(&mut self, index: usize, metrics: Vec<Option<ImageMetrics>>) {
+        if let Some(set) = self.sets.get_mut(index) {
+            set.metrics = Some(metrics);
+        }
+    }
+
+    /// Decode the sets without metrics in the background, one set at a time
+    fn start_summary(&mut self, ctx: &egui::Context, registry: Arc<AssetRegistry>) {
+        self.summary = Some(Summary {
+            loader: Loader::new(registry),
+            set: 0,
+            queued: vec![],
+            received: 0,
+            images: vec![],
+        });
+        self.queue_next_set(ctx);
+    }
+
+    fn queue_next_set(&mut self, ctx: &egui::Context) {
+        let next = self.sets.iter().position(|set| set.metrics.is_none());
+        let (Some(summary), Some(next)) = (&mut self.summary, next) else {
+            self.summary = None;
+            return;
+        };
+        summary.set = next;
+        summary.queued.clear();
+        summary.received = 0;
+        summary.images = vec![None; self.folders.len()];
+        for (folder, file) in self.sets[next].files.iter().enumerate() {
+            if let Some(path) = file {
+                summary
+                    .loader
+                    .load(ctx, dropped_file_from_path(path), None, false);
+                summary.queued.push(folder);
+            }
+        }
+    }
+
+    fn stop_summary(&mut self) {
+        if let Some(mut summary) = self.summary.take() {
+            summary.loader.cancel_all();
+        }
+    }
+
+    /// Take the images decoded for the summary, and measure a set once all of them arrived.
+    /// They are compared in the `working` color space
+    pub fn poll_summary(&mut self, ctx: &egui::Context, working: ColorSpace) {
+        let Some(summary) = &mut self.summary else {
+            return;
+        };
+        for loaded in summary.loader.poll() {
+            let folder = summary.queued.get(summary.received).copied();
+            summary.received += 1;
+            // files that fail to load are left out of the summary
+            if let (Some(folder), Ok(AssetEnum::Image(mut image_asset))) = (folder, loaded.result) {
+                let input = image_asset.detected_color_space();
+                if image_asset.set_color_spaces(input, working).is_ok() {
+                    summary.images[folder] = Some(image_asset.pixels);
+                }
+            }
+        }
+        if summary.loader.is_loading() {
+            return;
+        }
+
+        let images: Vec<Option<&Pixels>> = summary.images.iter().map(Option::as_ref).collect();
+        let metrics = metrics_against_first(&images);
+        let set = summary.set;
+        self.set_metrics(set, metrics);
+        self.queue_next_set(ctx);
+    }
+
+    /// Folders, pattern, navigator and summary, returns a set to open
+    pub fn show(&mut self, ui: &mut egui::Ui, registry: &Arc<AssetRegistry>) -> Option<usize> {
+        let mut open = None;
+        self.show_folders(ui);
+        ui.horizontal(|ui| {
+            ui.label("Pattern:");
+            ui.add(egui::TextEdit::singleline(&mut self.pattern).hint_text("paired by file stem"))
+                .on_hover_text(
+                    "A regex matched against the file names, \
+                     its first capture group or else the match pairs files, e.g. (\\d+)",
+                );
+            let can_scan = self.folders.len() >= 2;
+            if ui
+                .add_enabled(can_scan, egui::Button::new("Scan"))
+                .clicked()
+            {
+                match self.scan(registry) {
+                    Ok(()) => open = Some(self.current),
+                    Err(e) => self.error = Some(format!("{e:#}")),
+                }
+            }
+        });
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+        if self.sets.is_empty() {
+            ui.weak("Drop or add two or more folders and scan them to pair their files");
+            return open;
+        }
+
+        ui.separator();
+        open = open.or(self.show_navigator(ui));
+        ui.separator();
+        open.or(self.show_summary(ui, registry))
+    }
+
+    fn show_folders(&mut self, ui: &mut egui::Ui) {
+        ui.label("Folders, the first one is the reference:");
+        let mut removed = None;
+        for (i, (folder, label)) in self.folders.iter().zip(self.labels()).enumerate() {
+            ui.horizontal(|ui| {
+                if ui.small_button("🗑").clicked() {
+                    removed = Some(i);
+                }
+                ui.strong(label);
+                ui.weak(folder.display().to_string());
+            });
+        }
+        if let Some(i) = removed {
+            self.folders.remove(i);
+            self.forget_sets();
+        }
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.folder_input);
+            let folder = PathBuf::from(self.folder_input.trim());
+            let can_add = folder.is_dir() && !self.folders.contains(&folder);
+            if ui.add_enabled(can_add, egui::Button::new("Add")).clicked() {
+                self.add_folder(folder);
+                self.folder_input.clear();
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui.button("Browse…").clicked() {
+                if let Some(folder) = crate::dialog::pick_folder() {
+                    self.add_folder(folder);
+                }
+            }
+        });
+    }
+
+    /// Step through the sets, with the metrics of the current one
+    fn show_navigator(&self, ui: &mut egui::Ui) -> Option<usize> {
+        let set = self.sets.get(self.current)?;
+        let mut open = None;
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(self.current > 0, egui::Button::new("◀"))
+                .clicked()
+            {
+                open = Some(self.current - 1);
+            }
+            ui.label(format!("{} / {}", self.current + 1, self.sets.len()));
+            if ui
+                .add_enabled(self.current + 1 < self.sets.len(), egui::Button::new("▶"))
+                .clicked()
+            {
+                open = Some(self.current + 1);
+            }
+            ui.strong(&set.key);
+            if self.shown != Some(self.current) && ui.button("Show").clicked() {
+                open = Some(self.current);
+            }
+        });
+
+        egui::Grid::new("pair_set").show(ui, |ui| {
+            for (i, label) in self.labels().iter().enumerate() {
+                ui.strong(label);
+                let metrics = set.metrics.as_ref().map(|metrics| metrics[i]);
+                match (&set.files[i], metrics) {
+                    (None, _) => ui.weak("missing"),
+                    (Some(_), _) if i == 0 => ui.weak("reference"),
+                    (Some(_), Some(Some(metrics))) => ui.label(metrics.caption()),
+                    (Some(_), Some(None)) => ui.weak("not comparable, different size or failed"),
+                    (Some(_), None) => ui.spinner(),
+                };
+                ui.end_row();
+            }
+        });
+        open
+    }
+
+    /// Metric per set and folder, click a header to sort, worst first, and a row to open it
+    fn show_summary(&mut self, ui: &mut egui::Ui, registry: &Arc<AssetRegistry>) -> Option<usize> {
+        ui.horizontal(|ui| {
+            ui.strong("Summary");
+            egui::ComboBox::from_id_salt("pair_metric")
+                .selected_text(self.metric.label())
+                .show_ui(ui, |ui| {
+                    for metric in Metric::ALL {
+                        ui.selectable_value(&mut self.metric, metric, metric.label());
+                    }
+                });
+            let done = self.sets.iter().filter(|set| set.metrics.is_some()).count();
+            if self.summary.is_some() {
+                ui.spinner();
+                ui.label(format!("{done} / {}", self.sets.len()));
+                if ui.button("Cancel").clicked() {
+                    self.stop_summary();
+                }
+            } else if done < self.sets.len() && ui.button("Compute all").clicked() {
+                self.start_summary(ui.ctx(), registry.clone());
+            }
+        });
+
+        let labels = self.labels();
+        let order = self.sorted();
+        let mut open = None;
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                egui::Grid::new("pair_summary")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        let columns = std::iter::once((None, "Key")).chain(
+                            labels
+                                .iter()
+                                .enumerate()
+                                .skip(1)
+                                .map(|(i, label)| (Some(i), label.as_str())),
+                        );
+                        for (column, label) in columns {
+                            let sorted = self.sort_column == column;
+                            let text = match (sorted, self.sort_descending) {
+                                (false, _) => label.to_owned(),
+                                (true, false) => format!("{label} ⬆"),
+                                (true, true) => format!("{label} ⬇"),
+                            };
+                            if ui.selectable_label(sorted, text).clicked() {
+                                self.sort_by(column);
+                            }
+                        }
+                        ui.end_row();
+
+                        for index in order {
+                            let set = &self.sets[index];
+                            if ui
+                                .selectable_label(self.shown == Some(index), &set.key)
+                                .clicked()
+                            {
+                                open = Some(index);
+                            }
+                            for folder in 1..labels.len() {
+                                match self.value(index, folder) {
+                                    Some(metrics) => ui.label(self.metric.format(&metrics)),
+                                    None if set.files[folder].is_none() => ui.weak("missing"),
+                                    None => ui.weak("–"),
+                                };
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        open
+    }
+
+    /// Sort by the key or a folder, worst first on the first click and flipped on the next
+    fn sort_by(&mut self, column: Option<usize>) {
+        if self.sort_column == column {
+            self.sort_descending = !self.sort_descending;
+        } else {
+            self.sort_column = column;
+            self.sort_descending = column.is_some() && !self.metric.higher_is_better();
+        }
+    }
+
+    fn value(&self, set: usize, folder: usize) -> Option<ImageMetrics> {
+        let metrics = self.sets.get(set)?.metrics.as_ref()?;
+        metrics.get(folder).copied().flatten()
+    }
+
+    /// Indices of the sets in the order of the summary, sets without a value last
+    fn sorted(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.sets.len()).collect();
+        let Some(column) = self.sort_column else {
+            if self.sort_descending {
+                order.reverse();
+            }
+            return order;
+        };
+        let value = |set: usize| {
+            self.value(set, column)
+                .map(|metrics| self.metric.value(&metrics))
+        };
+        order.sort_by(|&a, &b| match (value(a), value(b)) {
+            (Some(a), Some(b)) if self.sort_descending => b.total_cmp(&a),
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        order
+    }
+}
+
+/// Key of a file, the first capture group of `regex` or else its match, or the stem without one.
+/// `None` when the regex does not match
+fn pair_key(path: &Path, regex: Option<&regex_lite::Regex>) -> Option<String> {
+    let Some(regex) = regex else {
+        return path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned());
+    };
+    let name = path.file_name()?.to_string_lossy();
+    let captures = regex.captures(&name)?;
+    let key = captures.get(1).or_else(|| captures.get(0))?;
+    Some(key.as_str().to_owned())
+}
+
+/// Metrics of each image against the first one.
+/// `None` for the first one, missing images and images of another size
+pub fn metrics_against_first(images: &[Option<&Pixels>]) -> Vec<Option<ImageMetrics>> {
+    let reference = images.first().copied().flatten();
+    images
+        .iter()
+        .enumerate()
+        .map(|(i, image)| {
+            if i == 0 {
+                return None;
+            }
+            ImageMetrics::compute((*image)?, reference?)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(psnr: f32) -> ImageMetrics {
+        ImageMetrics {
+            mae: 0.0,
+            mse: 0.0,
+            psnr,
+        }
+    }
+
+    #[test]
+    fn keys_are_stems_captures_or_matches() -> anyhow::Result<()> {
+        let path = Path::new("/ours/img_0012_denoised.png");
+        assert_eq!(pair_key(path, None).as_deref(), Some("img_0012_denoised"));
+
+        let captured = regex_lite::Regex::new(r"img_(\d+)")?;
+        assert_eq!(pair_key(path, Some(&captured)).as_deref(), Some("0012"));
+
+        // without a capture group the whole match is the key
+        let matched = regex_lite::Regex::new(r"\d+")?;
+        assert_eq!(pair_key(path, Some(&matched)).as_deref(), Some("0012"));
+
+        let unmatched = regex_lite::Regex::new(r"frame_\d+")?;
+        assert_eq!(pair_key(path, Some(&unmatched)), None);
+        Ok(())
+    }
+
+    #[test]
+    fn files_missing_from_a_folder_leave_a_gap() -> anyhow::Result<()> {
+        let root = std::env::temp_dir().join(format!("texcomp_pairing_{}", std::process::id()));
+        let (gt, ours) = (root.join("gt"), root.join("ours"));
+        for (folder, files) in [(&gt, ["1.png", "2.png"]), (&ours, ["1.png", "3.png"])] {
+            std::fs::create_dir_all(folder)?;
+            for file in files {
+                std::fs::write(folder.join(file), [])?;
+            }
+        }
+        std::fs::write(ours.join("notes.txt"), [])?;
+
+        let mut pairing = Pairing::default();
+        pairing.add_folder(gt.clone());
+        pairing.add_folder(ours.clone());
+        let scanned = pairing.scan(&AssetRegistry::default());
+        std::fs::remove_dir_all(&root)?;
+        scanned?;
+
+        let sets: Vec<(&str, Vec<bool>)> = pairing
+            .sets
+            .iter()
+            .map(|set| {
+                let present = set.files.iter().map(Option::is_some).collect();
+                (set.key.as_str(), present)
+            })
+            .collect();
+        assert_eq!(
+            sets,
+            [
+                ("1", vec![true, true]),
+                ("2", vec![true, false]),
+                ("3", vec![false, true]),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sets_without_a_value_are_sorted_last() {
+        let sets = [Some(30.0), None, Some(20.0), Some(40.0)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, psnr)| PairSet {
+                key: i.to_string(),
+                files: vec![None, None],
+                metrics: Some(vec![None, psnr.map(metrics)]),
+            })
+            .collect();
+        let mut pairing = Pairing {
+            sets,
+            ..Default::default()
+        };
+
+        assert_eq!(pairing.sorted(), [0, 1, 2, 3]);
+        // worst PSNR first
+        pairing.sort_by(Some(1));
+        assert_eq!(pairing.sorted(), [2, 0, 3, 1]);
+        pairing.sort_by(Some(1));
+        assert_eq!(pairing.sorted(), [3, 0, 2, 1]);
+        pairing.sort_by(None);
+        pairing.sort_by(None);
+        assert_eq!(pairing.sorted(), [3, 2, 1, 0]);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::image::annotation;
+use crate::image::viewer::ImageViewerWidget;
+use crate::selector::Grouping;
+use anyhow::{Context as _, Result};
+use std::path::{Component, Path, PathBuf};
+
+pub const PROJECT_EXTENSION: &str = "texcomp";
+
+pub fn is_project_file(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(PROJECT_EXTENSION))
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ProjectAsset {
+    /// Relative to the directory of the project file, unless on another drive
+    pub path: PathBuf,
+    pub label: String,
+}
+
+/// A saved comparison, stored as JSON in a `.texcomp` file
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Project {
+    pub version: u32,
+    pub assets: Vec<ProjectAsset>,
+    pub selected_index: usize,
+    /// Label of the reference image
+    pub reference: Option<String>,
+    /// Groups of the assets in the selector
+    pub grouping: Grouping,
+    /// View settings, including the region of interest. Annotations are kept by asset label
+    pub image_viewer: ImageViewerWidget,
+}
+
+impl Default for Project {
+    fn default() -> Self {
+        Self {
+            version: 1,
+            assets: vec![],
+            selected_index: 0,
+            reference: None,
+            grouping: Grouping::default(),
+            image_viewer: ImageViewerWidget::default(),
+        }
+    }
+}
+
+impl Project {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bytes).context("Invalid project file")
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).context("Failed to serialize project")
+    }
+
+    /// Store asset paths relative to the directory the project is saved in, and annotations by
+    /// asset label instead of by file
+    pub fn make_relative(&mut self, project_dir: &Path) {
+        let labels = self
+            .assets
+            .iter()
+            .map(|asset| (annotation::path_key(&asset.path), asset.label.clone()))
+            .collect();
+        self.image_viewer.annotations_mut().rekey(&labels);
+        for asset in &mut self.assets {
+            asset.path = relative_path(project_dir, &asset.path);
+        }
+    }
+
+    /// Make asset paths absolute, given the directory the project was loaded from, and key
+    /// annotations by the files the assets are loaded from
+    pub fn resolve(&mut self, project_dir: &Path) {
+        for asset in &mut self.assets {
+            asset.path = normalize(&project_dir.join(&asset.path));
+        }
+        let keys = self
+            .assets
+            .iter()
+            .map(|asset| (asset.label.clone(), annotation::path_key(&asset.path)))
+            .collect();
+        self.image_viewer.annotations_mut().rekey(&keys);
+    }
+}
+
+/// Absolute `path` without `.` and `..` components, so that a file has one path however it is
+/// reached. Links are not followed
+fn normalize(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Path of `target` relative to the directory `base`, both are made absolute first.
+/// An empty `base` is the current directory
+fn relative_path(base: &Path, target: &Path) -> PathBuf {
+    let base = if base.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        base
+    };
+    let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let (base, target) = (absolute(base), absolute(target));
+
+    let base_components: Vec<Component<'_>> = base.components().collect();
+    let target_components: Vec<Component<'_>> = target.components().collect();
+    let common = base_components
+        .iter()
+        .zip(&target_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    // different roots, e.g. another drive on windows
+    if common == 0 {
+        return target;
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in common..base_components.len() {
+        relative.push("..");
+    }
+    for component in &target_components[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_path_in_and_next_to_the_project() {
+        let base = Path::new("/work/project");
+        assert_eq!(
+            relative_path(base, Path::new("/work/project/renders/a.png")),
+            Path::new("renders/a.png")
+        );
+        assert_eq!(
+            relative_path(base, Path::new("/work/gt/a.png")),
+            Path::new("../gt/a.png")
+        );
+    }
+
+    #[test]
+    fn relative_path_from_an_empty_base_is_from_the_current_directory() -> std::io::Result<()> {
+        let target = std::env::current_dir()?.join("renders/a.png");
+        assert_eq!(
+            relative_path(Path::new(""), &target),
+            Path::new("renders/a.png")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn annotations_follow_their_assets_to_another_folder() -> Result<()> {
+        let annotated =
+            |project: &Project, key: &str| !project.image_viewer.annotations().get(key).is_empty();
+        let mut project = Project::from_bytes(
+            br#"{
+                "assets": [{ "path": "../gt/a.png", "label": "gt" }],
+                "image_viewer": { "annotations": { "by_asset": { "gt": [{
+                    "shape": { "type": "text", "pos": { "x": 1.0, "y": 2.0 }, "text": "noise", "size": 24.0 },
+                    "color": [255, 0, 0, 255],
+                    "width": 3.0
+                }] } } }
+            }"#,
+        )?;
+
+        project.resolve(Path::new("/work/project"));
+        assert_eq!(project.assets[0].path, Path::new("/work/gt/a.png"));
+        assert!(annotated(&project, "/work/gt/a.png"));
+
+        project.make_relative(Path::new("/work/project"));
+        let mut moved = Project::from_bytes(&project.to_bytes()?)?;
+        moved.resolve(Path::new("/home/review/project"));
+        assert_eq!(moved.assets[0].path, Path::new("/home/review/gt/a.png"));
+        assert!(annotated(&moved, "/home/review/gt/a.png"));
+        assert!(!annotated(&moved, "/work/gt/a.png"));
+        Ok(())
+    }
+
+    #[test]
+    fn project_files_are_recognized_by_extension() {
+        assert!(is_project_file("comparison.texcomp"));
+        assert!(is_project_file(Path::new("/a/B.TEXCOMP")));
+        assert!(!is_project_file("comparison.png"));
+        assert!(!is_project_file(""));
+    }
+}
@@ -0,0 +1,391 @@
+use crate::asset::{AssetEnum, LoadError, dropped_file_from_path, file_bytes};
+use crate::image::color_space::ColorSpace;
+use crate::image::image::{AnimationDecodeFn, ImageAsset, ImageDecodeFn};
+use crate::model_asset::{MeshDecodeFn, MeshModel};
+use crate::sequence::{self, Sequence};
+use anyhow::{Context as _, Result, bail};
+use image::ImageFormat;
+use std::path::Path;
+
+/// How the bytes of a format are turned into an asset
+#[derive(Clone, Copy)]
+pub enum Decoder {
+    /// Decoded into pixels, shown as an image
+    Image(ImageDecodeFn),
+    /// Decoded into frames if animated, else into pixels like an image
+    Animation(ImageDecodeFn, AnimationDecodeFn),
+    /// Decoded into a triangle mesh, shown as a model
+    Mesh(MeshDecodeFn),
+    /// Decoded into frames by ffmpeg, only from files on disk
+    Video,
+}
+
+/// A file format, how to recognize it and how to decode it
+#[derive(Clone, Copy)]
+pub struct AssetLoader {
+    /// Shown in the list of supported formats
+    pub name: &'static str,
+    /// Lowercase and without the dot, file names are matched case-insensitively
+    pub extensions: &'static [&'static str],
+    /// Only known for files dropped on the web
+    pub mime_types: &'static [&'static str],
+    /// Prefixes that files of this format start with. Prefixes of up to `WEAK_MAGIC_LEN` bytes,
+    /// e.g. `BM`, are common by chance and only used when no extension matches
+    pub magic: &'static [&'static [u8]],
+    pub decoder: Decoder,
+}
+
+/// The formats assets can be loaded from.
+/// Files are recognized by their content first, then by extension, short prefixes and MIME type
+#[derive(Clone)]
+pub struct AssetRegistry {
+    loaders: Vec<AssetLoader>,
+}
+
+/// Length of the magic prefixes that an extension takes precedence over
+pub const WEAK_MAGIC_LEN: usize = 2;
+
+const BUILTIN_LOADERS: &[AssetLoader] = &[
+    AssetLoader {
+        name: "PNG",
+        extensions: &["png"],
+        mime_types: &["image/png"],
+        magic: &[b"\x89PNG\r\n\x1a\n"],
+        decoder: Decoder::Animation(
+            |bytes| ImageAsset::image_from_bytes(bytes, ImageFormat::Png),
+            |bytes| ImageAsset::frames_from_bytes(bytes, ImageFormat::Png),
+        ),
+    },
+    AssetLoader {
+        name: "JPEG",
+        extensions: &["jpg", "jpeg"],
+        mime_types: &["image/jpeg"],
+        magic: &[b"\xff\xd8\xff"],
+        decoder: Decoder::Image(|bytes| ImageAsset::image_from_bytes(bytes, ImageFormat::Jpeg)),
+    },
+    AssetLoader {
+        name: "BMP",
+        extensions: &["bmp"],
+        mime_types: &["image/bmp"],
+        magic: &[b"BM"],
+        decoder: Decoder::Image(|bytes| ImageAsset::image_from_bytes(bytes, ImageFormat::Bmp)),
+    },
+    AssetLoader {
+        name: "GIF",
+        extensions: &["gif"],
+        mime_types: &["image/gif"],
+        magic: &[b"GIF87a", b"GIF89a"],
+        decoder: Decoder::Animation(
+            |bytes| ImageAsset::image_from_bytes(bytes, ImageFormat::Gif),
+            |bytes| ImageAsset::frames_from_bytes(bytes, ImageFormat::Gif),
+        ),
+    },
+    AssetLoader {
+        name: "WebP",
+        extensions: &["webp"],
+        mime_types: &["image/webp"],
+        // RIFF containers are recognized by extension, the prefix is not unique
+        magic: &[],
+        decoder: Decoder::Animation(
+            |bytes| ImageAsset::image_from_bytes(bytes, ImageFormat::WebP),
+            |bytes| ImageAsset::frames_from_bytes(bytes, ImageFormat::WebP),
+        ),
+    },
+    AssetLoader {
+        name: "TIFF",
+        extensions: &["tif", "tiff"],
+        mime_types: &["image/tiff"],
+        magic: &[b"II*\0", b"MM\0*"],
+        decoder: Decoder::Image(|bytes| ImageAsset::image_from_bytes(bytes, ImageFormat::Tiff)),
+    },
+    AssetLoader {
+        name: "TGA",
+        extensions: &["tga"],
+        mime_types: &["image/x-tga"],
+        magic: &[],
+        decoder: Decoder::Image(|bytes| ImageAsset::image_from_bytes(bytes, ImageFormat::Tga)),
+    },
+    AssetLoader {
+        name: "OpenEXR",
+        extensions: &["exr"],
+        mime_types: &["image/x-exr"],
+        magic: &[b"\x76\x2f\x31\x01"],
+        decoder: Decoder::Image(|bytes| ImageAsset::image_from_bytes(bytes, ImageFormat::OpenExr)),
+    },
+    AssetLoader {
+        name: "Radiance HDR",
+        extensions: &["hdr"],
+        mime_types: &["image/vnd.radiance"],
+        magic: &[b"#?RADIANCE", b"#?RGBE"],
+        decoder: Decoder::Image(|bytes| ImageAsset::image_from_bytes(bytes, ImageFormat::Hdr)),
+    },
+    AssetLoader {
+        name: "QOI",
+        extensions: &["qoi"],
+        mime_types: &[],
+        magic: &[b"qoif"],
+        decoder: Decoder::Image(|bytes| ImageAsset::image_from_bytes(bytes, ImageFormat::Qoi)),
+    },
+    AssetLoader {
+        name: "glTF binary",
+        extensions: &["glb"],
+        mime_types: &["model/gltf-binary"],
+        magic: &[b"glTF"],
+        decoder: Decoder::Mesh(|bytes| MeshModel::mesh_from_bytes(bytes, "glb")),
+    },
+    AssetLoader {
+        name: "Wavefront OBJ",
+        extensions: &["obj"],
+        mime_types: &["model/obj"],
+        magic: &[],
+        decoder: Decoder::Mesh(|bytes| MeshModel::mesh_from_bytes(bytes, "obj")),
+    },
+    AssetLoader {
+        name: "Video",
+        extensions: sequence::VIDEO_EXTENSIONS,
+        mime_types: &[
+            "video/mp4",
+            "video/quicktime",
+            "video/x-matroska",
+            "video/webm",
+        ],
+        magic: &[],
+        decoder: Decoder::Video,
+    },
+];
+
+impl Default for AssetRegistry {
+    fn default() -> Self {
+        Self {
+            loaders: BUILTIN_LOADERS.to_vec(),
+        }
+    }
+}
+
+impl AssetRegistry {
+    /// Add a format, it takes precedence over the formats registered before it
+    pub fn register(&mut self, loader: AssetLoader) {
+        self.loaders.insert(0, loader);
+    }
+
+    /// Loader for a file, by its leading bytes, its extension or its MIME type
+    pub fn find(&self, name: &str, mime: &str, bytes: &[u8]) -> Option<&AssetLoader> {
+        let by_magic = |weak: bool| {
+            self.loaders.iter().find(|loader| {
+                loader.magic.iter().any(|magic| {
+                    (magic.len() <= WEAK_MAGIC_LEN) == weak && bytes.starts_with(magic)
+                })
+            })
+        };
+        let by_extension = || {
+            let extension = extension(Path::new(name))?;
+            self.loaders
+                .iter()
+                .find(|loader| loader.extensions.contains(&extension.as_str()))
+        };
+        let by_mime = || {
+            self.loaders
+                .iter()
+                .find(|loader| !mime.is_empty() && loader.mime_types.contains(&mime))
+        };
+        by_magic(false)
+            .or_else(by_extension)
+            .or_else(|| by_magic(true))
+            .or_else(by_mime)
+    }
+
+    /// Whether a file has the extension of a registered format, used to pick files from folders
+    pub fn supports_extension(&self, path: &Path) -> bool {
+        extension(path).is_some_and(|extension| {
+            self.loaders
+                .iter()
+                .any(|loader| loader.extensions.contains(&extension.as_str()))
+        })
+    }
+
+    /// Extensions of every registered format, to filter file pickers
+    pub fn extensions(&self) -> Vec<&'static str> {
+        self.loaders
+            .iter()
+            .flat_map(|loader| loader.extensions.iter().copied())
+            .collect()
+    }
+
+    /// e.g. `PNG (.png), JPEG (.jpg, .jpeg)`
+    pub fn supported_formats(&self) -> String {
+        self.loaders
+            .iter()
+            .map(|loader| format!("{} (.{})", loader.name, loader.extensions.join(", .")))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Load a file, or a sequence given by a pattern like `frame_####.png`
+    pub fn load(&self, file: &egui::DroppedFile) -> Result<AssetEnum> {
+        self.load_with_warnings(file, &mut vec![])
+    }
+
+    /// Like [`Self::load`], adding to `warnings` what went wrong without failing the load
+    pub fn load_with_warnings(
+        &self,
+        file: &egui::DroppedFile,
+        warnings: &mut Vec<String>,
+    ) -> Result<AssetEnum> {
+        if let Some(path) = file
+            .path
+            .as_deref()
+            .filter(|path| sequence::is_pattern(path))
+        {
+            return self.load_sequence(file, path);
+        }
+        // videos are read by ffmpeg, there is no need to read them into memory first
+        if let Some(loader) = self
+            .find(&file.name, &file.mime, &[])
+            .filter(|loader| matches!(loader.decoder, Decoder::Video) && file.path.is_some())
+        {
+            return Self::load_video(file)
+                .map(AssetEnum::Image)
+                .context(LoadError::Decode {
+                    format: loader.name,
+                });
+        }
+        let bytes = file_bytes(file)?;
+        let Some(loader) = self.find(&file.name, &file.mime, &bytes) else {
+            bail!(LoadError::UnsupportedFormat {
+                supported: self.supported_formats(),
+            });
+        };
+        let asset = match loader.decoder {
+            Decoder::Image(decode) => {
+                ImageAsset::from_file(file, bytes, decode).map(AssetEnum::Image)
+            }
+            // stills are told apart before decoding, so they are decoded once
+            Decoder::Animation(decode, decode_frames) => match decode_frames(&bytes) {
+                Ok(Some(frames)) if frames.len() > 1 => {
+                    ImageAsset::from_animation(file, bytes, frames, decode_frames)
+                        .map(AssetEnum::Image)
+                }
+                Ok(_) => ImageAsset::from_file(file, bytes, decode).map(AssetEnum::Image),
+                Err(e) => {
+                    // e.g. a corrupt trailing frame, the first one may still decode
+                    warnings.push(format!(
+                        "Failed to decode the frames, loading it as a still: {e:#}"
+                    ));
+                    ImageAsset::from_file(file, bytes, decode).map(AssetEnum::Image)
+                }
+            },
+            Decoder::Mesh(decode) => {
+                MeshModel::from_file(file, &bytes, decode).map(AssetEnum::Model)
+            }
+            Decoder::Video => Self::load_video(file).map(AssetEnum::Image),
+        };
+        asset.context(LoadError::Decode {
+            format: loader.name,
+        })
+    }
+
+    /// Numbered files in the format of the first one
+    fn load_sequence(&self, file: &egui::DroppedFile, pattern: &Path) -> Result<AssetEnum> {
+        let files = sequence::expand_pattern(pattern)?;
+        let first = dropped_file_from_path(&files[0]);
+        let bytes = file_bytes(&first)?;
+        let Some(loader) = self.find(&first.name, "", &bytes) else {
+            bail!(LoadError::UnsupportedFormat {
+                supported: self.supported_formats(),
+            });
+        };
+        let (Decoder::Image(decode) | Decoder::Animation(decode, _)) = loader.decoder else {
+            bail!("Sequences of {} files are not supported", loader.name);
+        };
+        let sequence = Sequence::from_files(files, decode, None);
+        let color_space = ColorSpace::detect(&bytes).unwrap_or_default();
+        ImageAsset::from_sequence(file, sequence, color_space)
+            .map(AssetEnum::Image)
+            .context(LoadError::Decode {
+                format: loader.name,
+            })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_video(file: &egui::DroppedFile) -> Result<ImageAsset> {
+        let path = file.path.as_ref().ok_or_else(|| {
+            LoadError::MissingData("Videos are decoded from files on disk".into())
+        })?;
+        let (frames, fps) = sequence::extract_video(path)?;
+        let decode: ImageDecodeFn = |bytes| ImageAsset::image_from_bytes(bytes, ImageFormat::Png);
+        let sequence = Sequence::from_files(frames, decode, fps);
+        ImageAsset::from_sequence(file, sequence, ColorSpace::Srgb)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn load_video(_file: &egui::DroppedFile) -> Result<ImageAsset> {
+        bail!(LoadError::MissingData(
+            "Videos are decoded with ffmpeg, open them in the desktop app".into()
+        ))
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(name: &str, mime: &str, bytes: &[u8]) -> Option<&'static str> {
+        AssetRegistry::default()
+            .find(name, mime, bytes)
+            .map(|loader| loader.name)
+    }
+
+    #[test]
+    fn extensions_are_matched_case_insensitively() {
+        assert_eq!(found("IMG.PNG", "", &[]), Some("PNG"));
+        assert_eq!(found("photo.JpEg", "", &[]), Some("JPEG"));
+        assert_eq!(found("render.EXR", "", &[]), Some("OpenEXR"));
+        assert_eq!(found("notes.txt", "", &[]), None);
+    }
+
+    #[test]
+    fn files_without_an_extension_are_sniffed() {
+        assert_eq!(found("scan", "", b"\x89PNG\r\n\x1a\n...."), Some("PNG"));
+        assert_eq!(found("frame", "", b"GIF89a...."), Some("GIF"));
+        assert_eq!(found("depth", "", b"\x76\x2f\x31\x01...."), Some("OpenEXR"));
+        assert_eq!(found("unknown", "", b"plain text"), None);
+    }
+
+    #[test]
+    fn content_takes_precedence_over_the_extension() {
+        assert_eq!(
+            found("mislabeled.jpg", "", b"\x89PNG\r\n\x1a\n"),
+            Some("PNG")
+        );
+    }
+
+    #[test]
+    fn extensions_take_precedence_over_short_prefixes() {
+        let mut registry = AssetRegistry::default();
+        registry.register(AssetLoader {
+            name: "In-house",
+            extensions: &["bmx"],
+            mime_types: &[],
+            magic: &[],
+            decoder: Decoder::Mesh(|bytes| MeshModel::mesh_from_bytes(bytes, "obj")),
+        });
+        let found = |name| registry.find(name, "", b"BM....").map(|loader| loader.name);
+        assert_eq!(found("scene.bmx"), Some("In-house"));
+        assert_eq!(found("scan"), Some("BMP"));
+        assert_eq!(found("scan.bmp"), Some("BMP"));
+    }
+
+    #[test]
+    fn mime_types_are_the_last_resort() {
+        assert_eq!(found("blob", "image/webp", b"RIFF....WEBP"), Some("WebP"));
+        assert_eq!(found("blob", "image/x-tga", &[0; 18]), Some("TGA"));
+        assert_eq!(found("picture.png", "image/jpeg", &[]), Some("PNG"));
+        assert_eq!(found("blob", "", &[]), None);
+    }
+}
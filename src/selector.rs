@@ -1,183 +1,867 @@
+use crate::thumbnail::paint_thumbnail;
+use std::collections::BTreeMap;
+
+/// Side of the thumbnails next to item names, in points
+const THUMBNAIL_SIDE: f32 = 40.0;
+
+/// Something the app does to an item, picked in its context menu
+pub enum ItemAction {
+    Rename(usize, String),
+    Duplicate(usize),
+    SetReference(usize),
+}
+
+/// A named set of items that can be collapsed, e.g. the results of one method
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct Group {
+    pub name: String,
+    pub collapsed: bool,
+}
+
+/// Groups and the items in them, by item name, saved with projects
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Grouping {
+    pub groups: Vec<Group>,
+    pub membership: BTreeMap<String, String>,
+}
+
+/// A row of the list
+#[derive(Clone, Copy)]
+enum Entry {
+    Header(usize),
+    Item(usize),
+}
+
+/// A change to the list, applied once it is drawn
+enum Edit {
+    /// Move an item next to another, into the group of that one
+    Move {
+        from: usize,
+        onto: usize,
+        after: bool,
+    },
+    Remove(usize),
+    /// Put an item, or the selection it is part of, into a group or none
+    SetGroup(usize, Option<String>),
+    Ungroup(usize),
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
 pub struct Selector {
+    /// The item shown in the viewer
     pub selected_index: usize,
+    /// Every selected item when more than one is, in list order
+    selection: Vec<usize>,
+    groups: Vec<Group>,
+    /// Group of the items that are in one, by item name
+    membership: BTreeMap<String, String>,
+    /// Show a thumbnail with every item
+    pub thumbnails: bool,
+    /// Item being renamed and its new name
+    #[serde(skip)]
+    renaming: Option<(usize, String)>,
+    /// Name typed for a new group
+    #[serde(skip)]
+    new_group: String,
+}
+
+impl Default for Selector {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Selector {
     pub fn new() -> Self {
-        Self { selected_index: 0 }
+        Self {
+            selected_index: 0,
+            selection: vec![],
+            groups: vec![],
+            membership: BTreeMap::new(),
+            thumbnails: true,
+            renaming: None,
+            new_group: String::new(),
+        }
     }
 
-    pub fn show<T>(&mut self, ui: &mut egui::Ui, items: &mut Vec<T>, name_fn: impl Fn(&T) -> &str) {
-        // handle keyboard input
-        if ui.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
-            self.selected_index = (self.selected_index + items.len() - 1) % items.len();
-        } else if ui.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
-            self.selected_index = (self.selected_index + 1) % items.len();
+    /// Select only `index`
+    pub fn select(&mut self, index: usize) {
+        self.selected_index = index;
+        self.selection.clear();
+    }
+
+    /// Indices of the selected items, in list order
+    pub fn selected(&self) -> Vec<usize> {
+        if self.selection.len() > 1 {
+            self.selection.clone()
+        } else {
+            vec![self.selected_index]
         }
+    }
 
+    /// Forget selected items past the end of the list, e.g. ones that failed to load
+    pub fn clamp(&mut self, len: usize) {
+        self.selection.retain(|&i| i < len);
+        self.selected_index = self.selected_index.min(len.saturating_sub(1));
+    }
+
+    /// Select the same items in the list of only the items at `kept`, e.g. the ones saved with
+    /// the session. A shown item that is not kept is replaced by the kept one before it
+    pub fn keep(&mut self, kept: &[usize]) {
+        let position = |index: usize| kept.iter().position(|&i| i == index);
+        self.selection = self.selection.iter().filter_map(|&i| position(i)).collect();
+        let before = kept.iter().filter(|&&i| i < self.selected_index).count();
+        self.selected_index = position(self.selected_index)
+            .unwrap_or(before.saturating_sub(1))
+            .min(kept.len().saturating_sub(1));
+    }
+
+    pub fn grouping(&self) -> Grouping {
+        Grouping {
+            groups: self.groups.clone(),
+            membership: self.membership.clone(),
+        }
+    }
+
+    pub fn set_grouping(&mut self, grouping: Grouping) {
+        self.groups = grouping.groups;
+        self.membership = grouping.membership;
+    }
+
+    /// Keep the group of an item that was renamed
+    pub fn rename(&mut self, old_name: &str, new_name: &str) {
+        if let Some(group) = self.membership.remove(old_name) {
+            self.membership.insert(new_name.to_owned(), group);
+        }
+    }
+
+    /// Put `name` in the group of `like`, e.g. for a duplicate
+    pub fn group_like(&mut self, name: &str, like: &str) {
+        if let Some(group) = self.membership.get(like).cloned() {
+            self.membership.insert(name.to_owned(), group);
+        }
+    }
+
+    /// Vertical list, `detail_fn` gives secondary text shown right aligned.
+    /// `thumbnail_fn` gives the thumbnail of an item, and whether it is hovered.
+    /// Returns what was picked in the context menu of an item
+    pub fn show<T>(
+        &mut self,
+        ui: &mut egui::Ui,
+        items: &mut Vec<T>,
+        name_fn: impl Fn(&T) -> &str,
+        detail_fn: impl Fn(&T) -> String,
+        thumbnail_fn: impl FnMut(&mut T, &egui::Context, bool) -> Option<egui::load::SizedTexture>,
+    ) -> Option<ItemAction> {
+        let mut action = None;
         ui.scope(|ui| {
             ui.spacing_mut().item_spacing.y = 0.0;
+            action = self.show_list(ui, items, (name_fn, detail_fn, thumbnail_fn), false);
+        });
+        action
+    }
 
-            for (i, item) in items.iter().enumerate() {
-                let is_selected = i == self.selected_index;
-
-                let desired_size =
-                    egui::vec2(ui.available_width(), ui.spacing().interact_size.y + 8.0);
-                let (rect, response) = ui.allocate_exact_size(desired_size, egui::Sense::click());
+    /// Horizontal list, `detail_fn` gives secondary text shown on hover.
+    /// `thumbnail_fn` gives the thumbnail of an item, and whether it is hovered.
+    /// Returns what was picked in the context menu of an item
+    pub fn show_horizontal<T>(
+        &mut self,
+        ui: &mut egui::Ui,
+        items: &mut Vec<T>,
+        name_fn: impl Fn(&T) -> &str,
+        detail_fn: impl Fn(&T) -> String,
+        thumbnail_fn: impl FnMut(&mut T, &egui::Context, bool) -> Option<egui::load::SizedTexture>,
+    ) -> Option<ItemAction> {
+        ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing.x = 0.0;
+            self.show_list(ui, items, (name_fn, detail_fn, thumbnail_fn), true)
+        })
+        .inner
+    }
 
-                // Default background (slightly lighter than panel background)
-                let default_bg = ui.visuals().window_fill.linear_multiply(1.2);
-                ui.painter().rect_filled(rect, 0.0, default_bg);
+    fn show_list<T>(
+        &mut self,
+        ui: &mut egui::Ui,
+        items: &mut Vec<T>,
+        (name_fn, detail_fn, mut thumbnail_fn): (
+            impl Fn(&T) -> &str,
+            impl Fn(&T) -> String,
+            impl FnMut(&mut T, &egui::Context, bool) -> Option<egui::load::SizedTexture>,
+        ),
+        horizontal: bool,
+    ) -> Option<ItemAction> {
+        let names: Vec<String> = items.iter().map(|item| name_fn(item).to_owned()).collect();
+        self.handle_keys(ui, &names, horizontal);
 
-                // Highlight on hover
-                if response.hovered() {
-                    ui.painter()
-                        .rect_filled(rect, 0, ui.visuals().widgets.hovered.bg_fill);
+        let ctx = ui.ctx().clone();
+        let mut action = None;
+        let mut edit = None;
+        for entry in self.entries(&names) {
+            let (entry_edit, entry_action) = match entry {
+                Entry::Header(group) => (self.show_header(ui, group, horizontal), None),
+                Entry::Item(i) => {
+                    let detail = detail_fn(&items[i]);
+                    let item = &mut items[i];
+                    let mut thumbnail = |hovered| thumbnail_fn(item, &ctx, hovered);
+                    let thumbnail = self
+                        .thumbnails
+                        .then_some(&mut thumbnail as &mut dyn FnMut(bool) -> _);
+                    self.show_item(ui, i, &names, &detail, horizontal, thumbnail)
                 }
+            };
+            edit = edit.or(entry_edit);
+            action = action.or(entry_action);
+        }
 
-                // get strip color
-                let strip_color = if is_selected {
-                    Some(ui.visuals().selection.stroke.color)
-                } else {
-                    None
-                };
+        if let Some(edit) = edit {
+            self.apply(edit, items, &names);
+        }
+        action
+    }
 
-                if let Some(color) = strip_color {
-                    // Darker background for selected items
-                    ui.painter()
-                        .rect_filled(rect, 0.0, ui.visuals().widgets.active.bg_fill);
-
-                    // Colored strip on right
-                    let strip_rect = egui::Rect::from_min_max(
-                        egui::pos2(rect.right() - 4.0, rect.top()),
-                        egui::pos2(rect.right(), rect.bottom()),
-                    );
-                    ui.painter().rect_filled(strip_rect, 0.0, color);
-                }
+    /// Arrow keys step through the shown items in display order and select a single item
+    fn handle_keys(&mut self, ui: &egui::Ui, names: &[String], horizontal: bool) {
+        if ui.ctx().wants_keyboard_input() {
+            return;
+        }
+        let (previous, next) = if horizontal {
+            (egui::Key::ArrowLeft, egui::Key::ArrowRight)
+        } else {
+            (egui::Key::ArrowUp, egui::Key::ArrowDown)
+        };
+        let backwards = if ui.input(|i| i.key_pressed(previous)) {
+            true
+        } else if ui.input(|i| i.key_pressed(next)) {
+            false
+        } else {
+            return;
+        };
+        if let Some(index) = self.step(names, backwards) {
+            self.select(index);
+        }
+    }
 
-                // Draw text
-                let text_color = if is_selected {
-                    ui.visuals().selection.stroke.color
-                } else {
-                    ui.visuals().text_color()
-                };
+    /// Item before or after the shown one in display order, skipping collapsed groups
+    fn step(&self, names: &[String], backwards: bool) -> Option<usize> {
+        let order = self.display_order(names);
+        let len = order.len();
+        let position = match order.iter().position(|&i| i == self.selected_index) {
+            Some(position) if backwards => (position + len - 1) % len,
+            Some(position) => (position + 1) % len,
+            // the shown item is in a collapsed group
+            None if backwards => len.checked_sub(1)?,
+            None => 0,
+        };
+        order.get(position).copied()
+    }
 
-                ui.painter().text(
-                    rect.left_center() + egui::vec2(ui.spacing().item_spacing.x, 0.0),
-                    egui::Align2::LEFT_CENTER,
-                    name_fn(item),
-                    egui::TextStyle::Body.resolve(ui.style()),
-                    text_color,
+    /// Indices of the shown items in display order, without the items of collapsed groups
+    fn display_order(&self, names: &[String]) -> Vec<usize> {
+        self.entries(names)
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::Item(i) => Some(i),
+                Entry::Header(_) => None,
+            })
+            .collect()
+    }
+
+    /// Rows in display order, items without a group first
+    fn entries(&self, names: &[String]) -> Vec<Entry> {
+        let group_of = |name: &String| {
+            let group_name = self.membership.get(name)?;
+            self.groups
+                .iter()
+                .position(|group| group.name == *group_name)
+        };
+        let mut entries: Vec<Entry> = (0..names.len())
+            .filter(|&i| group_of(&names[i]).is_none())
+            .map(Entry::Item)
+            .collect();
+        for (g, group) in self.groups.iter().enumerate() {
+            entries.push(Entry::Header(g));
+            if !group.collapsed {
+                entries.extend(
+                    (0..names.len())
+                        .filter(|&i| group_of(&names[i]) == Some(g))
+                        .map(Entry::Item),
                 );
+            }
+        }
+        entries
+    }
 
-                // left click -> select item
-                if response.clicked() {
-                    self.selected_index = i;
-                }
+    fn is_selected(&self, index: usize) -> bool {
+        index == self.selected_index || self.selection.contains(&index)
+    }
 
-                // middle click -> remove item
-                if response.middle_clicked() {
-                    items.remove(i);
-                    if self.selected_index >= items.len() && !items.is_empty() {
-                        self.selected_index = items.len() - 1;
+    /// Ctrl toggles an item in the selection, Shift selects the range from the shown item, as
+    /// displayed, without the items of collapsed groups
+    pub fn click(&mut self, index: usize, modifiers: egui::Modifiers, names: &[String]) {
+        if modifiers.shift {
+            let order = self.display_order(names);
+            let position = |item| order.iter().position(|&i| i == item);
+            self.selection = match (position(self.selected_index), position(index)) {
+                (Some(a), Some(b)) => order[a.min(b)..=a.max(b)].to_vec(),
+                // the shown item is in a collapsed group
+                _ => vec![self.selected_index, index],
+            };
+            self.selection.sort_unstable();
+            self.selection.dedup();
+        } else if modifiers.command {
+            if self.selection.is_empty() {
+                self.selection.push(self.selected_index);
+            }
+            if let Some(position) = self.selection.iter().position(|&i| i == index) {
+                // the last selected item stays selected
+                if self.selection.len() > 1 {
+                    self.selection.remove(position);
+                    if self.selected_index == index {
+                        self.selected_index = self.selection[0];
                     }
-                    break;
                 }
+            } else {
+                self.selection.push(index);
+                self.selection.sort_unstable();
+                self.selected_index = index;
             }
-        });
+        } else {
+            self.select(index);
+        }
     }
 
-    pub fn show_horizontal<T>(
+    /// Height of an item, taller with thumbnails
+    fn item_height(&self, ui: &egui::Ui, horizontal: bool) -> f32 {
+        let text_height = ui.spacing().interact_size.y + 8.0;
+        match (self.thumbnails, horizontal) {
+            (false, _) => text_height,
+            (true, false) => text_height.max(THUMBNAIL_SIDE + 8.0),
+            (true, true) => THUMBNAIL_SIDE + text_height,
+        }
+    }
+
+    fn show_item(
         &mut self,
         ui: &mut egui::Ui,
-        items: &mut Vec<T>,
-        name_fn: impl Fn(&T) -> &str,
-    ) {
-        // handle keyboard input
-        if ui.input(|i| i.key_pressed(egui::Key::ArrowLeft)) {
-            self.selected_index = (self.selected_index + items.len() - 1) % items.len();
-        } else if ui.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
-            self.selected_index = (self.selected_index + 1) % items.len();
+        i: usize,
+        names: &[String],
+        detail: &str,
+        horizontal: bool,
+        thumbnail: Option<&mut dyn FnMut(bool) -> Option<egui::load::SizedTexture>>,
+    ) -> (Option<Edit>, Option<ItemAction>) {
+        let name = names[i].as_str();
+        let font_id = egui::TextStyle::Body.resolve(ui.style());
+        let height = self.item_height(ui, horizontal);
+        let width = if horizontal {
+            let galley = ui.fonts(|f| {
+                f.layout_no_wrap(name.to_owned(), font_id.clone(), ui.visuals().text_color())
+            });
+            let content_width = match thumbnail {
+                Some(_) => galley.size().x.max(THUMBNAIL_SIDE),
+                None => galley.size().x,
+            };
+            content_width + ui.spacing().item_spacing.x * 2.0 + 16.0
+        } else {
+            ui.available_width()
+        };
+
+        if self.renaming.as_ref().is_some_and(|(index, _)| *index == i) {
+            let size = egui::vec2(width.max(120.0), height);
+            return (None, self.show_rename(ui, i, size));
         }
 
-        ui.horizontal(|ui| {
-            ui.spacing_mut().item_spacing.x = 0.0;
+        let (rect, response) =
+            ui.allocate_exact_size(egui::vec2(width, height), egui::Sense::click_and_drag());
+        let is_selected = self.is_selected(i);
+        let is_shown = i == self.selected_index;
+        paint_item(ui, rect, &response, is_selected, is_shown, horizontal);
 
-            for (i, item) in items.iter().enumerate() {
-                let is_selected = i == self.selected_index;
+        // Thumbnail on the left, or on top in the horizontal list
+        let mut text_rect = rect;
+        if let Some(thumbnail) = thumbnail {
+            let thumbnail_rect = if horizontal {
+                text_rect.min.y += THUMBNAIL_SIDE;
+                egui::Rect::from_center_size(
+                    egui::pos2(rect.center().x, rect.top() + 4.0 + THUMBNAIL_SIDE / 2.0),
+                    egui::Vec2::splat(THUMBNAIL_SIDE),
+                )
+            } else {
+                text_rect.min.x += THUMBNAIL_SIDE + 4.0;
+                egui::Rect::from_min_size(
+                    rect.left_top() + egui::vec2(4.0, 4.0),
+                    egui::Vec2::splat(THUMBNAIL_SIDE),
+                )
+            };
+            paint_thumbnail(ui, thumbnail_rect, thumbnail(response.hovered()));
+        }
 
-                // Calculate text size for width
-                let text = name_fn(item);
-                let font_id = egui::TextStyle::Body.resolve(ui.style());
-                let galley = ui.fonts(|f| {
-                    f.layout_no_wrap(text.to_string(), font_id.clone(), ui.visuals().text_color())
-                });
-                let text_width = galley.size().x;
+        // Draw text
+        let text_color = if is_shown {
+            ui.visuals().selection.stroke.color
+        } else {
+            ui.visuals().text_color()
+        };
+        let response = if horizontal {
+            ui.painter().text(
+                text_rect.center(),
+                egui::Align2::CENTER_CENTER,
+                name,
+                font_id,
+                text_color,
+            );
+            response.on_hover_text(detail)
+        } else {
+            ui.painter().text(
+                text_rect.left_center() + egui::vec2(ui.spacing().item_spacing.x, 0.0),
+                egui::Align2::LEFT_CENTER,
+                name,
+                font_id,
+                text_color,
+            );
+            ui.painter().text(
+                rect.right_center() - egui::vec2(ui.spacing().item_spacing.x + 4.0, 0.0),
+                egui::Align2::RIGHT_CENTER,
+                detail,
+                egui::TextStyle::Small.resolve(ui.style()),
+                ui.visuals().weak_text_color(),
+            );
+            response
+        };
 
-                let desired_size = egui::vec2(
-                    text_width + ui.spacing().item_spacing.x * 2.0 + 16.0,
-                    ui.spacing().interact_size.y + 8.0,
-                );
-                let (rect, response) = ui.allocate_exact_size(desired_size, egui::Sense::click());
+        let mut edit = drag_and_drop(ui, &response, i, horizontal);
 
-                // Default background
-                let default_bg = ui.visuals().window_fill.linear_multiply(1.2);
-                ui.painter().rect_filled(rect, 0.0, default_bg);
+        // left click -> select item, with Ctrl or Shift -> add to the selection
+        if response.clicked() {
+            self.click(i, ui.input(|input| input.modifiers), names);
+        }
 
-                // Highlight on hover
-                if response.hovered() {
-                    ui.painter()
-                        .rect_filled(rect, 0, ui.visuals().widgets.hovered.bg_fill);
-                }
+        // middle click -> remove item
+        if response.middle_clicked() {
+            edit = Some(Edit::Remove(i));
+        }
 
-                // Selected styling
-                let strip_color = if is_selected {
-                    Some(ui.visuals().selection.stroke.color)
-                } else {
-                    None
-                };
+        let mut action = None;
+        response.context_menu(|ui| {
+            let (menu_edit, menu_action) = self.item_menu(ui, i, name);
+            edit = edit.take().or(menu_edit);
+            action = menu_action;
+        });
+        (edit, action)
+    }
 
-                if let Some(color) = strip_color {
-                    // Darker background for selected items
-                    ui.painter()
-                        .rect_filled(rect, 0.0, ui.visuals().widgets.active.bg_fill);
-
-                    // Colored strip on bottom
-                    let strip_rect = egui::Rect::from_min_max(
-                        egui::pos2(rect.left(), rect.bottom() - 4.0),
-                        egui::pos2(rect.right(), rect.bottom()),
-                    );
-                    ui.painter().rect_filled(strip_rect, 0.0, color);
+    /// Text field replacing an item while it is renamed, Enter applies and Escape cancels
+    fn show_rename(&mut self, ui: &mut egui::Ui, i: usize, size: egui::Vec2) -> Option<ItemAction> {
+        let (_, name) = self.renaming.as_mut()?;
+        let response = ui.add_sized(size, egui::TextEdit::singleline(name));
+        if response.lost_focus() {
+            let name = self.renaming.take().map(|(_, name)| name)?;
+            let confirmed = ui.input(|input| input.key_pressed(egui::Key::Enter));
+            return (confirmed && !name.trim().is_empty())
+                .then(|| ItemAction::Rename(i, name.trim().to_owned()));
+        }
+        if !response.has_focus() {
+            response.request_focus();
+        }
+        None
+    }
+
+    fn item_menu(
+        &mut self,
+        ui: &mut egui::Ui,
+        i: usize,
+        name: &str,
+    ) -> (Option<Edit>, Option<ItemAction>) {
+        let mut edit = None;
+        let mut action = None;
+        if ui.button("Rename").clicked() {
+            self.renaming = Some((i, name.to_owned()));
+            ui.close();
+        }
+        if ui.button("Duplicate").clicked() {
+            action = Some(ItemAction::Duplicate(i));
+            ui.close();
+        }
+        if ui.button("Set as reference").clicked() {
+            action = Some(ItemAction::SetReference(i));
+            ui.close();
+        }
+        ui.menu_button("Group", |ui| {
+            let current = self.membership.get(name);
+            if ui.selectable_label(current.is_none(), "None").clicked() {
+                edit = Some(Edit::SetGroup(i, None));
+                ui.close();
+            }
+            for group in &self.groups {
+                let is_current = current == Some(&group.name);
+                if ui.selectable_label(is_current, &group.name).clicked() {
+                    edit = Some(Edit::SetGroup(i, Some(group.name.clone())));
+                    ui.close();
                 }
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.new_group)
+                        .hint_text("New group")
+                        .desired_width(100.0),
+                );
+                let name = self.new_group.trim();
+                if ui
+                    .add_enabled(!name.is_empty(), egui::Button::new("Add"))
+                    .clicked()
+                {
+                    edit = Some(Edit::SetGroup(i, Some(name.to_owned())));
+                    self.new_group.clear();
+                    ui.close();
+                }
+            });
+        });
+        ui.separator();
+        if ui.button("Remove").clicked() {
+            edit = Some(Edit::Remove(i));
+            ui.close();
+        }
+        (edit, action)
+    }
 
-                // Draw text
-                let text_color = if is_selected {
-                    ui.visuals().selection.stroke.color
+    /// Header of a group, click to collapse, drop items on it to add them
+    fn show_header(&mut self, ui: &mut egui::Ui, g: usize, horizontal: bool) -> Option<Edit> {
+        let group = &self.groups[g];
+        let text = format!("{} {}", if group.collapsed { "▸" } else { "▾" }, group.name);
+        let font_id = egui::TextStyle::Small.resolve(ui.style());
+        let galley = ui.fonts(|f| f.layout_no_wrap(text, font_id, ui.visuals().weak_text_color()));
+        let padding = ui.spacing().item_spacing.x.max(4.0);
+        let size = if horizontal {
+            egui::vec2(
+                galley.size().x + padding * 2.0,
+                self.item_height(ui, horizontal),
+            )
+        } else {
+            egui::vec2(ui.available_width(), galley.size().y + 8.0)
+        };
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+
+        if response.dnd_hover_payload::<usize>().is_some() {
+            ui.painter()
+                .rect_filled(rect, 0.0, ui.visuals().widgets.hovered.bg_fill);
+        }
+        ui.painter().galley(
+            egui::pos2(
+                rect.left() + padding,
+                rect.center().y - galley.size().y / 2.0,
+            ),
+            galley,
+            ui.visuals().weak_text_color(),
+        );
+
+        if response.clicked() {
+            self.groups[g].collapsed = !self.groups[g].collapsed;
+        }
+        let mut edit = response
+            .dnd_release_payload::<usize>()
+            .map(|from| Edit::SetGroup(*from, Some(self.groups[g].name.clone())));
+        response.context_menu(|ui| {
+            if ui.button("Ungroup").clicked() {
+                edit = Some(Edit::Ungroup(g));
+                ui.close();
+            }
+        });
+        edit
+    }
+
+    fn apply<T>(&mut self, edit: Edit, items: &mut Vec<T>, names: &[String]) {
+        match edit {
+            Edit::Move { from, onto, after } => {
+                match self.membership.get(&names[onto]).cloned() {
+                    Some(group) => self.membership.insert(names[from].clone(), group),
+                    None => self.membership.remove(&names[from]),
+                };
+                self.move_item(items, from, onto + usize::from(after));
+            }
+            Edit::Remove(i) => {
+                items.remove(i);
+                self.removed(i, items.len());
+                // other items with the same name keep its group
+                if !names
+                    .iter()
+                    .enumerate()
+                    .any(|(j, name)| j != i && *name == names[i])
+                {
+                    self.membership.remove(&names[i]);
+                }
+            }
+            Edit::SetGroup(i, group) => {
+                let targets = if self.is_selected(i) {
+                    self.selected()
                 } else {
-                    ui.visuals().text_color()
+                    vec![i]
                 };
-                ui.painter().text(
-                    rect.center(),
-                    egui::Align2::CENTER_CENTER,
-                    text,
-                    font_id,
-                    text_color,
-                );
-
-                // left click -> select item
-                if response.clicked() {
-                    self.selected_index = i;
+                if let Some(group) = &group {
+                    if !self.groups.iter().any(|existing| existing.name == *group) {
+                        self.groups.push(Group {
+                            name: group.clone(),
+                            collapsed: false,
+                        });
+                    }
                 }
-
-                // middle click -> remove item
-                if response.middle_clicked() {
-                    items.remove(i);
-                    if self.selected_index >= items.len() && !items.is_empty() {
-                        self.selected_index = items.len() - 1;
+                for target in targets {
+                    match &group {
+                        Some(group) => {
+                            self.membership.insert(names[target].clone(), group.clone());
+                        }
+                        None => {
+                            self.membership.remove(&names[target]);
+                        }
                     }
-                    break;
                 }
             }
+            Edit::Ungroup(g) => {
+                let group = self.groups.remove(g);
+                self.membership.retain(|_, name| *name != group.name);
+            }
+        }
+    }
+
+    /// Move the item at `from` to before the item at `to`, keeping the selection on the same items
+    fn move_item<T>(&mut self, items: &mut Vec<T>, from: usize, to: usize) {
+        let mut order: Vec<usize> = (0..items.len()).collect();
+        let to = if from < to { to - 1 } else { to };
+        let item = items.remove(from);
+        items.insert(to, item);
+        order.remove(from);
+        order.insert(to, from);
+
+        let new_index = |old: usize| order.iter().position(|&i| i == old).unwrap_or(old);
+        self.selected_index = new_index(self.selected_index);
+        for i in &mut self.selection {
+            *i = new_index(*i);
+        }
+        self.selection.sort_unstable();
+    }
+
+    /// Update the selection after the item at `index` was removed
+    fn removed(&mut self, index: usize, len: usize) {
+        self.selection.retain(|&i| i != index);
+        for i in &mut self.selection {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+        if self.selected_index > index {
+            self.selected_index -= 1;
+        }
+        self.clamp(len);
+    }
+
+    /// Entries of assets that are still loading, middle click cancels. Returns the index to cancel
+    pub fn show_placeholders<'a>(
+        ui: &mut egui::Ui,
+        names: impl Iterator<Item = &'a str>,
+        horizontal: bool,
+    ) -> Option<usize> {
+        let mut cancelled = None;
+        for (i, name) in names.enumerate() {
+            let font_id = egui::TextStyle::Body.resolve(ui.style());
+            let text_color = ui.visuals().weak_text_color();
+            let galley = ui.fonts(|f| f.layout_no_wrap(name.to_owned(), font_id, text_color));
+            let spinner_size = ui.spacing().interact_size.y;
+            let padding = ui.spacing().item_spacing.x;
+
+            let height = ui.spacing().interact_size.y + 8.0;
+            let width = if horizontal {
+                galley.size().x + spinner_size + padding * 3.0
+            } else {
+                ui.available_width()
+            };
+            let (rect, response) =
+                ui.allocate_exact_size(egui::vec2(width, height), egui::Sense::click());
+
+            let default_bg = ui.visuals().window_fill.linear_multiply(1.2);
+            ui.painter().rect_filled(rect, 0.0, default_bg);
+
+            let spinner_rect = egui::Rect::from_center_size(
+                rect.left_center() + egui::vec2(padding + spinner_size / 2.0, 0.0),
+                egui::Vec2::splat(spinner_size * 0.7),
+            );
+            egui::Spinner::new().paint_at(ui, spinner_rect);
+            ui.painter().galley(
+                egui::pos2(
+                    spinner_rect.right() + padding,
+                    rect.center().y - galley.size().y / 2.0,
+                ),
+                galley,
+                text_color,
+            );
+
+            if response
+                .on_hover_text("Loading, middle click to cancel")
+                .middle_clicked()
+            {
+                cancelled = Some(i);
+            }
+        }
+        cancelled
+    }
+}
+
+/// Background of an item, with a strip on the edge facing the viewer for the shown one
+fn paint_item(
+    ui: &egui::Ui,
+    rect: egui::Rect,
+    response: &egui::Response,
+    is_selected: bool,
+    is_shown: bool,
+    horizontal: bool,
+) {
+    // Default background (slightly lighter than panel background)
+    let default_bg = ui.visuals().window_fill.linear_multiply(1.2);
+    ui.painter().rect_filled(rect, 0.0, default_bg);
+
+    // Highlight on hover
+    if response.hovered() {
+        ui.painter()
+            .rect_filled(rect, 0, ui.visuals().widgets.hovered.bg_fill);
+    }
+
+    // Darker background for selected items
+    if is_selected {
+        ui.painter()
+            .rect_filled(rect, 0.0, ui.visuals().widgets.active.bg_fill);
+    }
+
+    if is_shown {
+        let strip_rect = if horizontal {
+            egui::Rect::from_min_max(
+                egui::pos2(rect.left(), rect.bottom() - 4.0),
+                egui::pos2(rect.right(), rect.bottom()),
+            )
+        } else {
+            egui::Rect::from_min_max(
+                egui::pos2(rect.right() - 4.0, rect.top()),
+                egui::pos2(rect.right(), rect.bottom()),
+            )
+        };
+        ui.painter()
+            .rect_filled(strip_rect, 0.0, ui.visuals().selection.stroke.color);
+    }
+}
+
+/// Drag an item onto another to move it there, a line shows where it will go
+fn drag_and_drop(
+    ui: &egui::Ui,
+    response: &egui::Response,
+    i: usize,
+    horizontal: bool,
+) -> Option<Edit> {
+    if response.drag_started() {
+        response.dnd_set_drag_payload(i);
+    }
+    response.dnd_hover_payload::<usize>()?;
+
+    // before or after this item, depending on the half the pointer is in
+    let pointer = ui.input(|input| input.pointer.interact_pos())?;
+    let rect = response.rect;
+    let after = if horizontal {
+        pointer.x > rect.center().x
+    } else {
+        pointer.y > rect.center().y
+    };
+    let stroke = egui::Stroke::new(2.0, ui.visuals().selection.stroke.color);
+    let line = match (horizontal, after) {
+        (true, false) => [rect.left_top(), rect.left_bottom()],
+        (true, true) => [rect.right_top(), rect.right_bottom()],
+        (false, false) => [rect.left_top(), rect.right_top()],
+        (false, true) => [rect.left_bottom(), rect.right_bottom()],
+    };
+    ui.painter().line_segment(line, stroke);
+
+    let from = *response.dnd_release_payload::<usize>()?;
+    (from != i).then_some(Edit::Move {
+        from,
+        onto: i,
+        after,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kept_items_stay_selected() {
+        let mut selector = Selector::new();
+        selector.select(3);
+        selector.click(1, egui::Modifiers::COMMAND, &[]);
+        // items 0 and 2 are not kept
+        selector.keep(&[1, 3, 4]);
+        assert_eq!(selector.selected_index, 0);
+        assert_eq!(selector.selected(), vec![0, 1]);
+
+        let mut selector = Selector::new();
+        selector.select(2);
+        selector.keep(&[1, 3, 4]);
+        assert_eq!(selector.selected_index, 0);
+    }
+
+    #[test]
+    fn steps_follow_the_displayed_order() {
+        let names: Vec<String> = ["a", "b", "c", "d"].map(str::to_owned).into();
+        let mut selector = Selector::new();
+        selector.groups.push(Group {
+            name: "open".to_owned(),
+            collapsed: false,
         });
+        selector.groups.push(Group {
+            name: "closed".to_owned(),
+            collapsed: true,
+        });
+        selector
+            .membership
+            .insert("a".to_owned(), "open".to_owned());
+        selector
+            .membership
+            .insert("c".to_owned(), "closed".to_owned());
+        // shown as b, d, then a in its group, c is hidden
+        selector.select(1);
+        assert_eq!(selector.step(&names, false), Some(3));
+        selector.select(3);
+        assert_eq!(selector.step(&names, false), Some(0));
+        assert_eq!(selector.step(&names, true), Some(1));
+        selector.select(0);
+        assert_eq!(selector.step(&names, false), Some(1));
+    }
+
+    #[test]
+    fn shift_selects_the_displayed_range() {
+        let names: Vec<String> = ["a", "b", "c", "d", "e"].map(str::to_owned).into();
+        let mut selector = Selector::new();
+        for (name, collapsed) in [("first", false), ("second", false), ("closed", true)] {
+            selector.groups.push(Group {
+                name: name.to_owned(),
+                collapsed,
+            });
+        }
+        for (item, group) in [("a", "first"), ("b", "second"), ("c", "first")] {
+            selector
+                .membership
+                .insert(item.to_owned(), group.to_owned());
+        }
+        selector
+            .membership
+            .insert("d".to_owned(), "closed".to_owned());
+        // shown as e, then a and c in the first group and b in the second, d is hidden
+        selector.select(4);
+        selector.click(2, egui::Modifiers::SHIFT, &names);
+        assert_eq!(selector.selected(), vec![0, 2, 4]);
+
+        // b lies between a and c by index, but in another group
+        selector.select(0);
+        selector.click(2, egui::Modifiers::SHIFT, &names);
+        assert_eq!(selector.selected(), vec![0, 2]);
+
+        selector.click(1, egui::Modifiers::SHIFT, &names);
+        assert_eq!(selector.selected(), vec![0, 1, 2]);
     }
 }
@@ -0,0 +1,901 @@
+use crate::asset::read_file;
+use crate::image::color_space::ColorSpace;
+use crate::image::image::ImageDecodeFn;
+use crate::image::pixels::Pixels;
+use crate::metrics::ImageMetrics;
+use anyhow::{Context as _, Result, bail};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, mpsc};
+
+/// Marks the frame number in a sequence path, e.g. `renders/frame_####.png`
+const FRAME_MARK: char = '#';
+
+/// Frames decoded ahead of the one shown, including it. The web build has no threads and
+/// decodes only the shown one
+const PREFETCH_FRAMES: usize = if cfg!(target_arch = "wasm32") { 1 } else { 4 };
+
+/// Extensions of the video containers decoded with ffmpeg
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mov", "mkv", "webm", "avi"];
+
+/// Bytes of video frames kept extracted in the temporary folder, the frames of the videos
+/// opened least recently are removed beyond it
+#[cfg(not(target_arch = "wasm32"))]
+const VIDEO_CACHE_BYTES: u64 = 4 << 30;
+
+/// Where the frames of a sequence come from
+#[derive(Clone)]
+enum Frames {
+    /// One file per frame, decoded when shown
+    Files(Arc<[PathBuf]>, ImageDecodeFn),
+    /// Frames held in memory, e.g. of an animated GIF
+    Decoded(Arc<[Pixels]>),
+    /// Decoded frames freed to stay within the memory budget, and how many there were
+    Evicted(usize),
+}
+
+/// The frames of an image asset that has more than one, e.g. numbered files, a video or an
+/// animation
+#[derive(Clone)]
+pub struct Sequence {
+    frames: Frames,
+    /// Frames per second of the source, if it has its own like a video
+    pub fps: Option<f32>,
+    /// Seconds each frame is shown for, animations time every frame
+    pub delays: Option<Arc<[f32]>>,
+    /// Color spaces decoded frames are converted from and into
+    conversion: Option<(ColorSpace, ColorSpace)>,
+}
+
+impl Sequence {
+    pub fn from_files(files: Vec<PathBuf>, decode: ImageDecodeFn, fps: Option<f32>) -> Self {
+        Self {
+            frames: Frames::Files(files.into(), decode),
+            fps,
+            delays: None,
+            conversion: None,
+        }
+    }
+
+    /// Decoded frames and the seconds each is shown for
+    pub fn animation(frames: Vec<(Pixels, f32)>) -> Self {
+        let (images, delays): (Vec<Pixels>, Vec<f32>) = frames.into_iter().unzip();
+        Self {
+            frames: Frames::Decoded(images.into()),
+            fps: None,
+            delays: Some(delays.into()),
+            conversion: None,
+        }
+    }
+
+    /// A single frame, e.g. a still reference that every frame of a sequence is compared to
+    pub fn still(image: Pixels) -> Self {
+        Self {
+            frames: Frames::Decoded(vec![image].into()),
+            fps: None,
+            delays: None,
+            conversion: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.frames {
+            Frames::Files(files, _) => files.len(),
+            Frames::Decoded(images) => images.len(),
+            Frames::Evicted(len) => *len,
+        }
+    }
+
+    /// Free frames held in memory, returns whether there were any.
+    /// They have to be decoded again before they are shown
+    pub fn evict(&mut self) -> bool {
+        if !matches!(self.frames, Frames::Decoded(_)) {
+            return false;
+        }
+        self.frames = Frames::Evicted(self.len());
+        true
+    }
+
+    pub fn is_evicted(&self) -> bool {
+        matches!(self.frames, Frames::Evicted(_))
+    }
+
+    /// Whether frames are decoded from their files when shown, rather than held in memory
+    pub fn reads_files(&self) -> bool {
+        matches!(self.frames, Frames::Files(..))
+    }
+
+    /// Bytes of the frames held in memory
+    pub fn memory_usage(&self) -> usize {
+        match &self.frames {
+            Frames::Files(..) | Frames::Evicted(_) => 0,
+            Frames::Decoded(images) => images.iter().map(Pixels::memory_usage).sum(),
+        }
+    }
+
+    /// The same frames, converted from the color space `from` into `to` once decoded
+    pub fn converted(mut self, from: ColorSpace, to: ColorSpace) -> Self {
+        self.conversion = (from != to).then_some((from, to));
+        self
+    }
+
+    pub fn decode_frame(&self, index: usize) -> Result<Pixels> {
+        let image = match &self.frames {
+            Frames::Files(files, decode) => {
+                let path = files.get(index).context("No such frame")?;
+                let bytes = read_file(path)?;
+                decode(&bytes).with_context(|| format!("Failed to decode {}", path.display()))
+            }
+            Frames::Decoded(images) => images.get(index).cloned().context("No such frame"),
+            Frames::Evicted(_) => bail!("The frames were evicted, decode them again first"),
+        }?;
+        Ok(match self.conversion {
+            Some((from, to)) => from.convert(&image, to),
+            None => image,
+        })
+    }
+}
+
+/// Index of a frame asked for, and the frame decoded or `None` if it was skipped
+type PrefetchResult = (usize, Option<Result<Pixels>>);
+
+/// Frames of a sequence decoded ahead of the one shown on a worker thread, so that playing or
+/// scrubbing does not decode on the UI thread
+#[derive(Default)]
+pub struct Prefetch {
+    /// Frames asked for and not received yet
+    requested: HashSet<usize>,
+    /// Frames received and not shown yet
+    ready: HashMap<usize, Result<Pixels>>,
+    /// Frame shown next, the worker skips frames that are not soon after it
+    wanted: Arc<AtomicUsize>,
+    worker: Option<(mpsc::Sender<usize>, mpsc::Receiver<PrefetchResult>)>,
+}
+
+impl Prefetch {
+    /// `frame` if it was decoded, it and the frames after it are queued otherwise. `sequence`
+    /// must stay the same until the prefetch is cleared
+    pub fn take(
+        &mut self,
+        ctx: &egui::Context,
+        sequence: &Sequence,
+        frame: usize,
+    ) -> Option<Result<Pixels>> {
+        let len = sequence.len().max(1);
+        let ahead: Vec<usize> = (0..PREFETCH_FRAMES.min(len))
+            .map(|i| (frame + i) % len)
+            .collect();
+        self.wanted.store(frame, Ordering::Relaxed);
+        if let Some((_, results)) = &self.worker {
+            while let Ok((index, decoded)) = results.try_recv() {
+                self.requested.remove(&index);
+                if let Some(decoded) = decoded {
+                    self.ready.insert(index, decoded);
+                }
+            }
+        }
+        // frames behind are not shown again soon
+        self.ready.retain(|index, _| ahead.contains(index));
+        for &index in &ahead {
+            if !self.ready.contains_key(&index) && !self.requested.contains(&index) {
+                self.request(ctx, sequence, index);
+            }
+        }
+        self.ready.remove(&frame)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn request(&mut self, ctx: &egui::Context, sequence: &Sequence, index: usize) {
+        let (jobs, _) = self
+            .worker
+            .get_or_insert_with(|| spawn_prefetch(ctx, sequence.clone(), self.wanted.clone()));
+        if jobs.send(index).is_ok() {
+            self.requested.insert(index);
+        }
+    }
+
+    /// The web build has no threads, its frames are held in memory anyway
+    #[cfg(target_arch = "wasm32")]
+    fn request(&mut self, _ctx: &egui::Context, sequence: &Sequence, index: usize) {
+        self.ready.insert(index, sequence.decode_frame(index));
+    }
+
+    /// Drop the decoded frames and stop the worker, e.g. once the frames change
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Decode the frames asked for on a new thread, until the sender is dropped
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_prefetch(
+    ctx: &egui::Context,
+    sequence: Sequence,
+    wanted: Arc<AtomicUsize>,
+) -> (mpsc::Sender<usize>, mpsc::Receiver<PrefetchResult>) {
+    let (job_sender, job_receiver) = mpsc::channel::<usize>();
+    let (result_sender, result_receiver) = mpsc::channel();
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let len = sequence.len().max(1);
+        for index in job_receiver {
+            // frames left behind by scrubbing are not decoded
+            let offset = (index + len - wanted.load(Ordering::Relaxed) % len) % len;
+            let decoded = (offset < PREFETCH_FRAMES).then(|| sequence.decode_frame(index));
+            if result_sender.send((index, decoded)).is_err() {
+                return;
+            }
+            ctx.request_repaint();
+        }
+    });
+    (job_sender, result_receiver)
+}
+
+/// Whether a path names a sequence of numbered files rather than a file, a file whose name
+/// contains the frame mark is opened as is
+pub fn is_pattern(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().contains(FRAME_MARK))
+        && !path.is_file()
+}
+
+/// The file name around its last run of digits, e.g. `frame_`, `0012` and `.png`
+fn split_number(name: &str) -> Option<(&str, &str, &str)> {
+    let stem_end = name.rfind('.').unwrap_or(name.len());
+    let digits_end = name[..stem_end].rfind(|c: char| c.is_ascii_digit())? + 1;
+    let digits_start = name[..digits_end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |i| i + 1);
+    Some((
+        &name[..digits_start],
+        &name[digits_start..digits_end],
+        &name[digits_end..],
+    ))
+}
+
+/// The numbered files a pattern stands for, sorted by number.
+/// A run of `#` matches a frame number of any width
+pub fn expand_pattern(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let name = pattern
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mark_start = name
+        .find(FRAME_MARK)
+        .context("No # in the sequence pattern")?;
+    let mark_end = name.rfind(FRAME_MARK).map_or(mark_start, |i| i + 1);
+    let (prefix, suffix) = (&name[..mark_start], &name[mark_end..]);
+
+    let folder = pattern
+        .parent()
+        .filter(|folder| !folder.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let entries = std::fs::read_dir(folder)
+        .with_context(|| format!("Failed to list {}", folder.display()))?;
+    let mut frames: Vec<(u64, PathBuf)> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().into_owned();
+            let number = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            Some((number.parse().ok()?, path))
+        })
+        .collect();
+    if frames.is_empty() {
+        bail!("No files match {}", pattern.display());
+    }
+    frames.sort();
+    Ok(frames.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Replace numbered files that only differ in their number, e.g. `frame_0001.png` and
+/// `frame_0002.png`, by one sequence pattern, `frame_####.png`, where the first of them was
+pub fn group_numbered(files: &[egui::DroppedFile]) -> Vec<egui::DroppedFile> {
+    let pattern_of = |file: &egui::DroppedFile| {
+        let path = file.path.as_ref()?;
+        let name = path.file_name()?.to_string_lossy().into_owned();
+        let (prefix, digits, suffix) = split_number(&name)?;
+        let mark = FRAME_MARK.to_string().repeat(digits.len());
+        Some(path.with_file_name(format!("{prefix}{mark}{suffix}")))
+    };
+    let patterns: Vec<Option<PathBuf>> = files.iter().map(pattern_of).collect();
+    let count = |pattern: &PathBuf| patterns.iter().flatten().filter(|p| *p == pattern).count();
+
+    let mut grouped: Vec<egui::DroppedFile> = vec![];
+    let mut seen: Vec<&PathBuf> = vec![];
+    for (file, pattern) in files.iter().zip(&patterns) {
+        match pattern {
+            Some(pattern) if count(pattern) > 1 => {
+                if !seen.contains(&pattern) {
+                    seen.push(pattern);
+                    grouped.push(crate::asset::dropped_file_from_path(pattern));
+                }
+            }
+            _ => grouped.push(file.clone()),
+        }
+    }
+    grouped
+}
+
+/// Extract the frames of a video with the ffmpeg on the `PATH`, or the one `TEXCOMP_FFMPEG`
+/// points to. Frames are cached in the temporary folder, by path, size and modification time,
+/// up to [`VIDEO_CACHE_BYTES`]
+#[cfg(not(target_arch = "wasm32"))]
+pub fn extract_video(path: &Path) -> Result<(Vec<PathBuf>, Option<f32>)> {
+    use std::hash::{Hash as _, Hasher as _};
+
+    let metadata =
+        std::fs::metadata(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut hasher = std::hash::DefaultHasher::new();
+    path.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    metadata.modified().ok().hash(&mut hasher);
+    let root = std::env::temp_dir().join("texcomp-frames");
+    let cache = root.join(format!("{:016x}", hasher.finish()));
+    let frames_pattern = cache.join("frame_######.png");
+    let fps_file = cache.join("fps");
+    let read_fps = || {
+        std::fs::read_to_string(&fps_file)
+            .ok()
+            .and_then(|fps| fps.trim().parse().ok())
+    };
+    if cache.is_dir() {
+        mark_used(&cache);
+        return Ok((expand_pattern(&frames_pattern)?, read_fps()));
+    }
+
+    // extract next to the cache and move it in place once complete
+    let partial = cache.with_extension("partial");
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)
+            .with_context(|| format!("Failed to clear {}", partial.display()))?;
+    }
+    std::fs::create_dir_all(&partial)
+        .with_context(|| format!("Failed to create {}", partial.display()))?;
+    let ffmpeg = std::env::var_os("TEXCOMP_FFMPEG").unwrap_or_else(|| "ffmpeg".into());
+    let output = std::process::Command::new(&ffmpeg)
+        .arg("-hide_banner")
+        .arg("-nostdin")
+        .arg("-i")
+        .arg(path)
+        .arg(partial.join("frame_%06d.png"))
+        .output()
+        .context("Failed to run ffmpeg, install it or point TEXCOMP_FFMPEG to it")?;
+    let log = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        bail!("ffmpeg failed: {}", log.lines().last().unwrap_or_default());
+    }
+
+    // the stream is described like `Video: h264 …, 1920x1080, 30 fps, 30 tbr`
+    let fps = log
+        .split(',')
+        .find_map(|part| part.trim().strip_suffix(" fps")?.parse::<f32>().ok());
+    if let Some(fps) = fps {
+        std::fs::write(partial.join("fps"), fps.to_string())
+            .with_context(|| format!("Failed to write to {}", partial.display()))?;
+    }
+    std::fs::rename(&partial, &cache)
+        .with_context(|| format!("Failed to move the frames to {}", cache.display()))?;
+    mark_used(&cache);
+    if let Err(e) = prune_video_cache(&root, &cache, VIDEO_CACHE_BYTES) {
+        log::warn!("Failed to remove old video frames: {e:#}");
+    }
+    Ok((expand_pattern(&frames_pattern)?, fps))
+}
+
+/// Touch the marker whose modification time tells when the frames of a video were last opened
+#[cfg(not(target_arch = "wasm32"))]
+fn mark_used(cache: &Path) {
+    if let Err(e) = std::fs::write(cache.join("used"), []) {
+        log::warn!("Failed to mark {} as used: {e}", cache.display());
+    }
+}
+
+/// Remove the frames of the videos opened least recently until those in `root` take at most
+/// `max_bytes`, the frames in `keep` stay
+#[cfg(not(target_arch = "wasm32"))]
+fn prune_video_cache(root: &Path, keep: &Path, max_bytes: u64) -> Result<()> {
+    let mut caches = vec![];
+    for entry in std::fs::read_dir(root)? {
+        let path = entry?.path();
+        // extractions in progress are named `*.partial`
+        if !path.is_dir() || path.extension().is_some() {
+            continue;
+        }
+        let mut bytes = 0;
+        for file in std::fs::read_dir(&path)? {
+            bytes += file?.metadata()?.len();
+        }
+        let used = std::fs::metadata(path.join("used")).and_then(|used| used.modified());
+        caches.push((used.ok(), bytes, path));
+    }
+    let mut total: u64 = caches.iter().map(|(_, bytes, _)| bytes).sum();
+    caches.sort_by_key(|(used, ..)| *used);
+    for (_, bytes, path) in caches {
+        if total <= max_bytes {
+            break;
+        }
+        if path != keep {
+            std::fs::remove_dir_all(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            total -= bytes;
+        }
+    }
+    Ok(())
+}
+
+/// A frame compared by the metrics job
+type FrameResult = (usize, usize, Option<ImageMetrics>);
+
+/// Compares the sequences to the reference frame after frame
+struct MetricsJob {
+    reference: Sequence,
+    sequences: Vec<Sequence>,
+    /// Next frame to compare
+    frame: usize,
+    sender: mpsc::Sender<FrameResult>,
+    cancelled: Arc<AtomicBool>,
+    ctx: egui::Context,
+}
+
+impl MetricsJob {
+    /// Compare the next frame of every sequence, `false` once all of them are or the job was
+    /// cancelled
+    fn step(&mut self) -> bool {
+        let frames = self.sequences.iter().map(Sequence::len).max().unwrap_or(0);
+        if self.frame >= frames {
+            return false;
+        }
+        let frame = self.frame;
+        self.frame += 1;
+        let reference = self
+            .reference
+            .decode_frame(frame.min(self.reference.len().saturating_sub(1)))
+            .ok();
+        for (i, sequence) in self.sequences.iter().enumerate() {
+            if self.cancelled.load(Ordering::Relaxed) {
+                return false;
+            }
+            if frame >= sequence.len() {
+                continue;
+            }
+            let metrics = sequence
+                .decode_frame(frame)
+                .ok()
+                .and_then(|image| ImageMetrics::compute(&image, reference.as_ref()?));
+            if self.sender.send((i, frame, metrics)).is_err() {
+                return false;
+            }
+            self.ctx.request_repaint();
+        }
+        true
+    }
+}
+
+/// Metrics of every frame of the sequences against the reference, computed on a thread
+/// natively and one frame per `poll` on the web
+#[derive(Default)]
+pub struct FrameMetrics {
+    /// Id of the asset the sequences are compared to
+    pub reference: String,
+    /// Id of each sequence and its metrics per frame, `None` until computed or if not comparable
+    pub series: Vec<(String, Vec<Option<ImageMetrics>>)>,
+    receiver: Option<mpsc::Receiver<FrameResult>>,
+    cancelled: Arc<AtomicBool>,
+    /// The web build has no threads, `poll` steps the job
+    #[cfg(target_arch = "wasm32")]
+    job: Option<MetricsJob>,
+}
+
+impl FrameMetrics {
+    /// Compare frame after frame, a reference with fewer frames holds its last one
+    pub fn start(
+        &mut self,
+        ctx: &egui::Context,
+        reference: (String, Sequence),
+        sequences: Vec<(String, Sequence)>,
+    ) {
+        self.cancel();
+        let (reference_id, reference) = reference;
+        self.reference = reference_id;
+        self.series = sequences
+            .iter()
+            .map(|(id, sequence)| (id.clone(), vec![None; sequence.len()]))
+            .collect();
+
+        let (sender, receiver) = mpsc::channel();
+        self.receiver = Some(receiver);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.cancelled = cancelled.clone();
+        let job = MetricsJob {
+            reference,
+            sequences: sequences.into_iter().map(|(_, s)| s).collect(),
+            frame: 0,
+            sender,
+            cancelled,
+            ctx: ctx.clone(),
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(move || {
+            let mut job = job;
+            while job.step() {}
+        });
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.job = Some(job);
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.receiver = None;
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.job = None;
+        }
+    }
+
+    /// Take the frames compared since the last call, on the web comparing the next frame first
+    pub fn poll(&mut self) {
+        #[cfg(target_arch = "wasm32")]
+        if self.job.as_mut().is_some_and(|job| !job.step()) {
+            // dropping the sender ends the receiver
+            self.job = None;
+        }
+        let Some(receiver) = &self.receiver else {
+            return;
+        };
+        loop {
+            match receiver.try_recv() {
+                Ok((i, frame, metrics)) => {
+                    if let Some(value) = self
+                        .series
+                        .get_mut(i)
+                        .and_then(|(_, values)| values.get_mut(frame))
+                    {
+                        *value = metrics;
+                    }
+                }
+                Err(mpsc::TryRecvError::Empty) => return,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.receiver = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+}
+
+/// Frames the web build saves at once, each one is a separate download
+pub const MAX_WEB_EXPORT_FRAMES: usize = 32;
+
+/// Saves the frames of a sequence as numbered PNG files, e.g. `spin_0001.png`
+struct ExportJob {
+    frames: Sequence,
+    /// Space the frames are shown in
+    working: ColorSpace,
+    folder: String,
+    stem: String,
+    /// Next frame to save
+    frame: usize,
+    sender: mpsc::Sender<Result<String>>,
+    cancelled: Arc<AtomicBool>,
+    ctx: egui::Context,
+}
+
+impl ExportJob {
+    /// Save the next frame, `false` once all of them are, one failed or the job was cancelled
+    fn step(&mut self) -> bool {
+        if self.frame >= self.frames.len() || self.cancelled.load(Ordering::Relaxed) {
+            return false;
+        }
+        let frame = self.frame;
+        self.frame += 1;
+        let saved = self.frames.decode_frame(frame).and_then(|pixels| {
+            let bytes = crate::export::encode_png(&self.working.display(&pixels))?;
+            let name = format!("{}_{:04}.png", self.stem, frame + 1);
+            crate::export::save_file(&self.folder, &name, &bytes)
+        });
+        let failed = saved.is_err();
+        if self.sender.send(saved).is_err() {
+            return false;
+        }
+        self.ctx.request_repaint();
+        !failed
+    }
+}
+
+/// Frames of a sequence saved on a thread natively and one frame per `poll` on the web
+#[derive(Default)]
+pub struct FrameExport {
+    /// Id of the asset whose frames are saved
+    pub id: String,
+    /// Frames saved so far and how many there are
+    pub progress: (usize, usize),
+    receiver: Option<mpsc::Receiver<Result<String>>>,
+    cancelled: Arc<AtomicBool>,
+    /// The web build has no threads, `poll` steps the job
+    #[cfg(target_arch = "wasm32")]
+    job: Option<ExportJob>,
+}
+
+impl FrameExport {
+    /// Save every frame of `frames`, shown in `working`, as `{stem}_0001.png` and onwards
+    pub fn start(
+        &mut self,
+        ctx: &egui::Context,
+        id: String,
+        frames: Sequence,
+        working: ColorSpace,
+        folder: &str,
+        stem: &str,
+    ) {
+        self.cancel();
+        self.id = id;
+        self.progress = (0, frames.len());
+
+        let (sender, receiver) = mpsc::channel();
+        self.receiver = Some(receiver);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.cancelled = cancelled.clone();
+        let job = ExportJob {
+            frames,
+            working,
+            folder: folder.to_owned(),
+            stem: stem.to_owned(),
+            frame: 0,
+            sender,
+            cancelled,
+            ctx: ctx.clone(),
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(move || {
+            let mut job = job;
+            while job.step() {}
+        });
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.job = Some(job);
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.receiver = None;
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.job = None;
+        }
+    }
+
+    /// Take the frames saved since the last call, on the web saving the next frame first.
+    /// Returns the outcome once the export ended, the number of saved frames or the failure
+    pub fn poll(&mut self) -> Option<Result<usize>> {
+        #[cfg(target_arch = "wasm32")]
+        if self.job.as_mut().is_some_and(|job| !job.step()) {
+            // dropping the sender ends the receiver
+            self.job = None;
+        }
+        let receiver = self.receiver.as_ref()?;
+        loop {
+            match receiver.try_recv() {
+                Ok(Ok(_)) => self.progress.0 += 1,
+                Ok(Err(e)) => {
+                    self.cancel();
+                    return Some(Err(e));
+                }
+                Err(mpsc::TryRecvError::Empty) => return None,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.receiver = None;
+                    return Some(Ok(self.progress.0));
+                }
+            }
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty files with the given names in a new folder of the temporary folder
+    fn folder_with(name: &str, files: &[&str]) -> std::io::Result<PathBuf> {
+        let folder = std::env::temp_dir().join(format!("texcomp_{name}_{}", std::process::id()));
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+        std::fs::create_dir_all(&folder)?;
+        for file in files {
+            std::fs::write(folder.join(file), [])?;
+        }
+        Ok(folder)
+    }
+
+    fn names(paths: &[PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .filter_map(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn numbers_are_split_at_the_last_run_of_digits() {
+        assert_eq!(
+            split_number("frame_0012.png"),
+            Some(("frame_", "0012", ".png"))
+        );
+        assert_eq!(
+            split_number("v2_shot10.exr"),
+            Some(("v2_shot", "10", ".exr"))
+        );
+        assert_eq!(split_number("0007"), Some(("", "0007", "")));
+        // digits in the extension are not a frame number
+        assert_eq!(split_number("texture.mp4"), None);
+        assert_eq!(split_number("render.png"), None);
+    }
+
+    #[test]
+    fn patterns_match_numbers_of_any_width_in_order() -> std::io::Result<()> {
+        let folder = folder_with(
+            "expand",
+            &[
+                "frame_10.png",
+                "frame_9.png",
+                "frame_0011.png",
+                "frame_x.png",
+                "frame_.png",
+                "frame_12.jpg",
+                "other_1.png",
+            ],
+        )?;
+        let frames = expand_pattern(&folder.join("frame_####.png"));
+        std::fs::remove_dir_all(&folder)?;
+
+        let frames = frames.map_err(std::io::Error::other)?;
+        assert_eq!(
+            names(&frames),
+            ["frame_9.png", "frame_10.png", "frame_0011.png"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn patterns_without_files_fail() -> std::io::Result<()> {
+        let folder = folder_with("missing", &["frame_1.jpg"])?;
+        let frames = expand_pattern(&folder.join("frame_#.png"));
+        let without_mark = expand_pattern(&folder.join("frame.png"));
+        std::fs::remove_dir_all(&folder)?;
+
+        assert!(frames.is_err());
+        assert!(without_mark.is_err());
+        assert!(expand_pattern(&folder.join("frame_#.png")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn files_with_a_frame_mark_in_their_name_are_not_patterns() -> std::io::Result<()> {
+        let folder = folder_with("mark", &["shot#2.png"])?;
+        let file = is_pattern(&folder.join("shot#2.png"));
+        let pattern = is_pattern(&folder.join("shot#.png"));
+        std::fs::remove_dir_all(&folder)?;
+
+        assert!(!file);
+        assert!(pattern);
+        Ok(())
+    }
+
+    #[test]
+    fn only_files_that_differ_in_their_number_are_grouped() {
+        let files: Vec<egui::DroppedFile> = [
+            "/renders/frame_0001.png",
+            "/renders/notes.png",
+            "/renders/frame_0002.png",
+            "/renders/frame_10.png",
+            "/renders/shot_3.png",
+        ]
+        .iter()
+        .map(|path| crate::asset::dropped_file_from_path(Path::new(path)))
+        .collect();
+        let grouped: Vec<PathBuf> = group_numbered(&files)
+            .into_iter()
+            .filter_map(|file| file.path)
+            .collect();
+        assert_eq!(
+            names(&grouped),
+            ["frame_####.png", "notes.png", "frame_10.png", "shot_3.png"]
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn the_video_frames_opened_least_recently_are_removed() -> Result<()> {
+        let root = folder_with("video_cache", &[])?;
+        let now = std::time::SystemTime::now();
+        for (name, age) in [("old", 30), ("recent", 20), ("shown", 40)] {
+            let cache = root.join(name);
+            std::fs::create_dir(&cache)?;
+            std::fs::write(cache.join("frame_000001.png"), [0; 100])?;
+            let used = std::fs::File::create(cache.join("used"))?;
+            used.set_modified(now - std::time::Duration::from_secs(age))?;
+        }
+        let pruned = prune_video_cache(&root, &root.join("shown"), 250);
+        let left: Vec<bool> = ["old", "recent", "shown"]
+            .iter()
+            .map(|name| root.join(name).exists())
+            .collect();
+        std::fs::remove_dir_all(&root)?;
+
+        pruned?;
+        assert_eq!(left, [false, true, true]);
+        Ok(())
+    }
+
+    #[test]
+    fn frames_of_files_are_decoded_on_a_worker() -> std::io::Result<()> {
+        let folder = folder_with("prefetch", &["f_1.png", "f_2.png", "f_3.png"])?;
+        let files = (1..=3).map(|i| folder.join(format!("f_{i}.png"))).collect();
+        let decode: ImageDecodeFn = |_| Ok(Pixels::from(image::RgbaImage::new(2, 1)));
+        let sequence = Sequence::from_files(files, decode, None);
+        let ctx = egui::Context::default();
+        let mut prefetch = Prefetch::default();
+        let mut taken = None;
+        for _ in 0..1000 {
+            taken = prefetch.take(&ctx, &sequence, 1);
+            if taken.is_some() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        std::fs::remove_dir_all(&folder)?;
+
+        let size = taken.and_then(|frame| frame.ok()).map(|frame| frame.size());
+        assert_eq!(size, Some([2, 1]));
+        Ok(())
+    }
+
+    #[test]
+    fn frames_are_exported_on_a_thread() -> Result<()> {
+        let folder = folder_with("export", &[])?;
+        let frame = Pixels::from(image::RgbaImage::new(2, 1));
+        let sequence = Sequence::animation(vec![(frame.clone(), 0.1), (frame, 0.1)]);
+        let ctx = egui::Context::default();
+        let mut export = FrameExport::default();
+        export.start(
+            &ctx,
+            "spin.gif".into(),
+            sequence,
+            ColorSpace::Srgb,
+            &folder.to_string_lossy(),
+            "spin",
+        );
+        let mut outcome = None;
+        for _ in 0..1000 {
+            outcome = export.poll();
+            if outcome.is_some() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let saved = names(&expand_pattern(&folder.join("spin_####.png"))?);
+        std::fs::remove_dir_all(&folder)?;
+
+        assert_eq!(outcome.transpose()?, Some(2));
+        assert!(!export.is_running());
+        assert_eq!(saved, ["spin_0001.png", "spin_0002.png"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+use std::time::Duration;
+
+/// Longest side of a thumbnail in pixels
+pub const THUMBNAIL_SIZE: usize = 128;
+
+/// Views of a model around its vertical axis
+const TURNTABLE_FRAMES: usize = 12;
+
+/// Frames per second of a turntable while it spins
+const TURNTABLE_FPS: f64 = 10.0;
+
+/// A small preview of an asset, made when the asset is decoded and uploaded when first drawn.
+/// Models get a turntable of views around them
+#[derive(Clone, Default)]
+pub struct Thumbnail {
+    frames: Vec<egui::ColorImage>,
+    textures: Vec<egui::TextureHandle>,
+}
+
+impl Thumbnail {
+    pub fn of_image(image: &egui::ColorImage) -> Self {
+        Self {
+            frames: downscale(image, THUMBNAIL_SIZE).into_iter().collect(),
+            textures: vec![],
+        }
+    }
+
+    pub fn of_mesh(verts: &[[f32; 3]], indices: &[u32]) -> Self {
+        let frames = (0..TURNTABLE_FRAMES)
+            .map(|frame| {
+                let angle = std::f32::consts::TAU * frame as f32 / TURNTABLE_FRAMES as f32;
+                render_mesh(verts, indices, angle, THUMBNAIL_SIZE)
+            })
+            .collect();
+        Self {
+            frames,
+            textures: vec![],
+        }
+    }
+
+    /// Texture to draw, a turntable turns while `spinning`
+    pub fn texture(
+        &mut self,
+        ctx: &egui::Context,
+        spinning: bool,
+    ) -> Option<egui::load::SizedTexture> {
+        if self.textures.len() != self.frames.len() {
+            let options = egui::TextureOptions {
+                mipmap_mode: Some(egui::TextureFilter::Linear),
+                ..egui::TextureOptions::LINEAR
+            };
+            self.textures = self
+                .frames
+                .iter()
+                .enumerate()
+                .map(|(i, frame)| {
+                    ctx.load_texture(format!("thumbnail {i}"), frame.clone(), options)
+                })
+                .collect();
+        }
+
+        let count = self.textures.len();
+        let frame = if spinning && count > 1 {
+            ctx.request_repaint_after(Duration::from_secs_f64(1.0 / TURNTABLE_FPS));
+            (ctx.input(|i| i.time) * TURNTABLE_FPS) as usize % count
+        } else {
+            0
+        };
+        self.textures
+            .get(frame)
+            .map(egui::load::SizedTexture::from_handle)
+    }
+
+    /// Bytes of the frames in RAM and on the GPU
+    pub fn memory_usage(&self) -> usize {
+        let frame_bytes: usize = self.frames.iter().map(|frame| frame.pixels.len() * 4).sum();
+        frame_bytes * if self.textures.is_empty() { 1 } else { 2 }
+    }
+}
+
+/// Draw a thumbnail centered in `rect`, keeping its aspect ratio. A placeholder without one
+pub fn paint_thumbnail(ui: &egui::Ui, rect: egui::Rect, texture: Option<egui::load::SizedTexture>) {
+    let Some(texture) = texture else {
+        ui.painter().text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            "🖼",
+            egui::FontId::proportional(rect.height() * 0.5),
+            ui.visuals().weak_text_color(),
+        );
+        return;
+    };
+    let scale = (rect.size() / texture.size).min_elem();
+    let image_rect = egui::Rect::from_center_size(rect.center(), texture.size * scale);
+    let uv = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+    ui.painter()
+        .image(texture.id, image_rect, uv, egui::Color32::WHITE);
+}
+
+/// Fit within `max_side`, averaging the pixels each thumbnail pixel covers.
+/// `None` for an empty image
+fn downscale(image: &egui::ColorImage, max_side: usize) -> Option<egui::ColorImage> {
+    let [width, height] = image.size;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let scale = (max_side as f32 / width.max(height) as f32).min(1.0);
+    let size = [
+        ((width as f32 * scale).round() as usize).max(1),
+        ((height as f32 * scale).round() as usize).max(1),
+    ];
+
+    let span = |i: usize, from: usize, to: usize| {
+        let start = i * from / to;
+        start..(((i + 1) * from / to).max(start + 1))
+    };
+    let mut pixels = Vec::with_capacity(size[0] * size[1]);
+    for y in 0..size[1] {
+        let rows = span(y, height, size[1]);
+        for x in 0..size[0] {
+            let columns = span(x, width, size[0]);
+            let mut sum = [0_u64; 4];
+            for source_y in rows.clone() {
+                for source_x in columns.clone() {
+                    let pixel = image[(source_x, source_y)].to_array();
+                    for (channel, value) in sum.iter_mut().zip(pixel) {
+                        *channel += u64::from(value);
+                    }
+                }
+            }
+            let count = (rows.len() * columns.len()) as u64;
+            let [r, g, b, a] = sum.map(|channel| ((channel + count / 2) / count) as u8);
+            pixels.push(egui::Color32::from_rgba_premultiplied(r, g, b, a));
+        }
+    }
+    Some(egui::ColorImage::new(size, pixels))
+}
+
+/// Rasterize a mesh turned by `angle` around its vertical axis and seen slightly from above,
+/// flat shaded on a transparent background
+fn render_mesh(verts: &[[f32; 3]], indices: &[u32], angle: f32, size: usize) -> egui::ColorImage {
+    let mut image = egui::ColorImage::filled([size, size], egui::Color32::TRANSPARENT);
+    let (mut min, mut max) = ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]);
+    for vert in verts {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(vert[axis]);
+            max[axis] = max[axis].max(vert[axis]);
+        }
+    }
+    let center: [f32; 3] = std::array::from_fn(|axis| (min[axis] + max[axis]) / 2.0);
+    let radius = (0..3)
+        .map(|axis| (max[axis] - min[axis]) / 2.0)
+        .map(|half| half * half)
+        .sum::<f32>()
+        .sqrt();
+    if !radius.is_finite() || radius <= 0.0 {
+        return image;
+    }
+
+    // turn around y, then tilt towards the camera looking down -z
+    let (yaw_sin, yaw_cos) = angle.sin_cos();
+    let (pitch_sin, pitch_cos) = 0.4_f32.sin_cos();
+    let to_view = |vert: &[f32; 3]| {
+        let [x, y, z] =
+            std::array::from_fn::<f32, 3, _>(|axis| (vert[axis] - center[axis]) / radius);
+        let (x, z) = (x * yaw_cos + z * yaw_sin, z * yaw_cos - x * yaw_sin);
+        let (y, z) = (y * pitch_cos - z * pitch_sin, y * pitch_sin + z * pitch_cos);
+        [x, y, z]
+    };
+    let projected: Vec<[f32; 3]> = verts.iter().map(to_view).collect();
+    let to_pixel = |[x, y, z]: [f32; 3]| {
+        let half = size as f32 / 2.0;
+        [half + x * half * 0.95, half - y * half * 0.95, z]
+    };
+
+    let mut depth = vec![f32::NEG_INFINITY; size * size];
+    let light = [0.3_f32, 0.5, 0.81];
+    for triangle in indices.chunks_exact(3) {
+        let corner = |i: u32| projected.get(i as usize).copied();
+        let (Some(a), Some(b), Some(c)) = (
+            corner(triangle[0]),
+            corner(triangle[1]),
+            corner(triangle[2]),
+        ) else {
+            continue;
+        };
+
+        // both sides are lit, meshes are not always closed or consistently wound
+        let normal = cross(sub(b, a), sub(c, a));
+        let length = dot(normal, normal).sqrt();
+        if length <= f32::EPSILON {
+            continue;
+        }
+        let shade = 0.25 + 0.75 * (dot(normal, light) / length).abs();
+        let color = egui::Color32::from_rgb(
+            (170.0 * shade) as u8,
+            (185.0 * shade) as u8,
+            (205.0 * shade) as u8,
+        );
+
+        let [a, b, c] = [a, b, c].map(to_pixel);
+        let area = edge(a, b, c);
+        if area.abs() <= f32::EPSILON {
+            continue;
+        }
+        let x0 = a[0].min(b[0]).min(c[0]).floor().max(0.0) as usize;
+        let y0 = a[1].min(b[1]).min(c[1]).floor().max(0.0) as usize;
+        let x1 = (a[0].max(b[0]).max(c[0]).ceil().max(0.0) as usize).min(size);
+        let y1 = (a[1].max(b[1]).max(c[1]).ceil().max(0.0) as usize).min(size);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let p = [x as f32 + 0.5, y as f32 + 0.5, 0.0];
+                let weights = [
+                    edge(b, c, p) / area,
+                    edge(c, a, p) / area,
+                    edge(a, b, p) / area,
+                ];
+                if weights.iter().any(|&weight| weight < 0.0) {
+                    continue;
+                }
+                let z = weights[0] * a[2] + weights[1] * b[2] + weights[2] * c[2];
+                let index = y * size + x;
+                if z > depth[index] {
+                    depth[index] = z;
+                    image.pixels[index] = color;
+                }
+            }
+        }
+    }
+    image
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `p` on screen
+fn edge(a: [f32; 3], b: [f32; 3], p: [f32; 3]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|axis| a[axis] - b[axis])
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
@@ -0,0 +1,388 @@
+use crate::metrics::Metric;
+use crate::sequence::{FrameExport, FrameMetrics, Sequence};
+use std::time::Duration;
+
+/// Colors of the plotted sequences, in order
+pub const SERIES_COLORS: [egui::Color32; 6] = [
+    egui::Color32::from_rgb(90, 170, 255),
+    egui::Color32::from_rgb(255, 150, 60),
+    egui::Color32::from_rgb(110, 210, 110),
+    egui::Color32::from_rgb(230, 90, 200),
+    egui::Color32::from_rgb(240, 220, 80),
+    egui::Color32::from_rgb(170, 140, 255),
+];
+
+/// Height of the plot of the metrics per frame, in points
+const PLOT_HEIGHT: f32 = 70.0;
+
+/// What the timeline was asked to do, besides moving between frames
+#[derive(Clone, Copy)]
+pub enum TimelineAction {
+    /// Compare every frame of the sequences to the reference
+    ComputeMetrics,
+    /// Save every frame of the shown sequence
+    ExportFrames,
+    /// Stop saving the frames
+    CancelExport,
+}
+
+/// Playback of sequences. Every sequence shows the same frame, so comparisons stay in sync
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Timeline {
+    #[serde(skip)]
+    pub frame: usize,
+    #[serde(skip)]
+    pub playing: bool,
+    pub fps: f32,
+    pub looping: bool,
+    /// Play animations with the delays of their frames rather than at `fps`
+    pub file_timing: bool,
+    /// Metric plotted per frame
+    metric: Metric,
+    /// Time the current frame was first shown while playing
+    #[serde(skip)]
+    frame_start: f64,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            frame: 0,
+            playing: false,
+            fps: 24.0,
+            looping: true,
+            file_timing: true,
+            metric: Metric::default(),
+            frame_start: 0.0,
+        }
+    }
+}
+
+impl Timeline {
+    pub fn toggle_playing(&mut self, ctx: &egui::Context) {
+        self.playing = !self.playing;
+        self.frame_start = ctx.input(|i| i.time);
+    }
+
+    /// Move by `delta` frames and pause
+    pub fn step(&mut self, delta: isize) {
+        self.playing = false;
+        self.frame = self.frame.saturating_add_signed(delta);
+    }
+
+    /// Advance while playing, returns the frame to show of a sequence of `count` frames.
+    /// `delays` are the seconds each frame of an animation is shown for
+    pub fn update(&mut self, ctx: &egui::Context, count: usize, delays: Option<&[f32]>) -> usize {
+        if count == 0 {
+            return 0;
+        }
+        self.frame = self.frame.min(count - 1);
+        if !self.playing {
+            return self.frame;
+        }
+
+        // catch up on the frames that were due, at most one loop of them
+        let now = ctx.input(|i| i.time);
+        for _ in 0..count {
+            let interval = self.interval(delays);
+            if now - self.frame_start < interval {
+                break;
+            }
+            self.frame_start += interval;
+            self.frame += 1;
+            if self.frame >= count {
+                if self.looping {
+                    self.frame = 0;
+                } else {
+                    self.frame = count - 1;
+                    self.playing = false;
+                    return self.frame;
+                }
+            }
+        }
+        let interval = self.interval(delays);
+        if now - self.frame_start >= interval {
+            self.frame_start = now;
+        }
+        ctx.request_repaint_after(Duration::from_secs_f64(
+            (self.frame_start + interval - now).max(0.0),
+        ));
+        self.frame
+    }
+
+    /// Seconds the current frame is shown for
+    fn interval(&self, delays: Option<&[f32]>) -> f64 {
+        let delay = delays
+            .filter(|_| self.file_timing)
+            .and_then(|delays| delays.get(self.frame));
+        match delay {
+            Some(&delay) if delay > 0.01 => f64::from(delay),
+            // browsers show frames without a delay for a tenth of a second
+            Some(_) => 0.1,
+            None => 1.0 / f64::from(self.fps.max(0.1)),
+        }
+    }
+
+    /// Transport controls and scrubber along the bottom of `rect`, with the metrics of every
+    /// frame plotted above once computed
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        rect: egui::Rect,
+        count: usize,
+        sequence: Option<&Sequence>,
+        metrics: &FrameMetrics,
+        export: &FrameExport,
+    ) -> Option<TimelineAction> {
+        let margin = 8.0;
+        let row_height = ui.spacing().interact_size.y;
+        let plot_height = if metrics.series.is_empty() {
+            0.0
+        } else {
+            PLOT_HEIGHT + margin
+        };
+        let height = plot_height + 2.0 * row_height + 3.0 * margin;
+        let bar_rect = egui::Rect::from_min_max(
+            egui::pos2(rect.left() + margin, rect.bottom() - margin - height),
+            egui::pos2(rect.right() - margin, rect.bottom() - margin),
+        );
+        ui.painter()
+            .rect_filled(bar_rect, 4.0, egui::Color32::from_black_alpha(180));
+
+        let mut action = None;
+        let inner = bar_rect.shrink(margin);
+        ui.scope_builder(egui::UiBuilder::new().max_rect(inner), |ui| {
+            if !metrics.series.is_empty() {
+                let plot_rect =
+                    egui::Rect::from_min_size(inner.min, egui::vec2(inner.width(), PLOT_HEIGHT));
+                self.show_plot(ui, plot_rect, count, metrics);
+                ui.add_space(PLOT_HEIGHT + margin);
+            }
+
+            let mut frame = self.frame.min(count - 1);
+            ui.spacing_mut().slider_width = inner.width();
+            let scrubber = ui.add(
+                egui::Slider::new(&mut frame, 0..=count - 1)
+                    .show_value(false)
+                    .trailing_fill(true),
+            );
+            if scrubber.changed() {
+                self.frame = frame;
+            }
+
+            ui.horizontal(|ui| {
+                action = self.show_controls(ui, count, sequence, metrics, export);
+            });
+        });
+        action
+    }
+
+    fn show_controls(
+        &mut self,
+        ui: &mut egui::Ui,
+        count: usize,
+        sequence: Option<&Sequence>,
+        metrics: &FrameMetrics,
+        export: &FrameExport,
+    ) -> Option<TimelineAction> {
+        if ui.button("⏮").on_hover_text("First frame").clicked() {
+            self.playing = false;
+            self.frame = 0;
+        }
+        if ui.button("⏪").on_hover_text("Previous frame ,").clicked() {
+            self.step(-1);
+        }
+        let play = if self.playing { "⏸" } else { "▶" };
+        if ui.button(play).on_hover_text("Play or pause K").clicked() {
+            if !self.playing && !self.looping && self.frame + 1 >= count {
+                self.frame = 0;
+            }
+            self.toggle_playing(ui.ctx());
+        }
+        if ui.button("⏩").on_hover_text("Next frame .").clicked() {
+            self.step(1);
+        }
+        ui.label(format!("{} / {count}", self.frame.min(count - 1) + 1));
+
+        ui.separator();
+        let timed = sequence.is_some_and(|sequence| sequence.delays.is_some());
+        if timed {
+            ui.checkbox(&mut self.file_timing, "File timing")
+                .on_hover_text("Show each frame for as long as the animation says");
+        }
+        if !timed || !self.file_timing {
+            ui.add(
+                egui::DragValue::new(&mut self.fps)
+                    .range(0.1..=240.0)
+                    .speed(0.1)
+                    .suffix(" fps"),
+            );
+        }
+        if let Some(source_fps) = sequence.and_then(|sequence| sequence.fps) {
+            if source_fps != self.fps && ui.small_button("Source").clicked() {
+                self.fps = source_fps;
+            }
+        }
+        ui.checkbox(&mut self.looping, "Loop");
+        let mut cancel_export = false;
+        let start_export = if export.is_running() {
+            ui.spinner();
+            let (saved, total) = export.progress;
+            ui.label(format!("Saving {saved} / {total}"));
+            cancel_export = ui.small_button("✖").on_hover_text("Stop saving").clicked();
+            false
+        } else {
+            ui.button("💾 Frames")
+                .on_hover_text("Save every frame as a numbered PNG")
+                .clicked()
+        };
+
+        ui.separator();
+        if metrics.is_running() {
+            ui.spinner();
+        }
+        egui::ComboBox::from_id_salt("timeline_metric")
+            .selected_text(self.metric.label())
+            .show_ui(ui, |ui| {
+                for metric in Metric::ALL {
+                    ui.selectable_value(&mut self.metric, metric, metric.label());
+                }
+            });
+        let compute = ui
+            .button("📈 Metrics per frame")
+            .on_hover_text("Compare every frame of the sequences to the reference")
+            .clicked();
+        if start_export {
+            Some(TimelineAction::ExportFrames)
+        } else if cancel_export {
+            Some(TimelineAction::CancelExport)
+        } else {
+            compute.then_some(TimelineAction::ComputeMetrics)
+        }
+    }
+
+    /// A line per sequence, click or drag to jump to a frame
+    fn show_plot(&mut self, ui: &egui::Ui, rect: egui::Rect, count: usize, metrics: &FrameMetrics) {
+        let response = ui.interact(
+            rect,
+            ui.id().with("timeline_plot"),
+            egui::Sense::click_and_drag(),
+        );
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(120));
+
+        let values: Vec<Vec<Option<f32>>> = metrics
+            .series
+            .iter()
+            .map(|(_, series)| {
+                series
+                    .iter()
+                    .map(|metrics| {
+                        metrics
+                            .map(|metrics| self.metric.value(&metrics))
+                            .filter(|value| value.is_finite())
+                    })
+                    .collect()
+            })
+            .collect();
+        let (low, high) = values
+            .iter()
+            .flatten()
+            .flatten()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), &v| {
+                (low.min(v), high.max(v))
+            });
+        let frames = metrics
+            .series
+            .iter()
+            .map(|(_, series)| series.len())
+            .max()
+            .unwrap_or(0)
+            .max(count);
+        let x = |frame: usize| rect.left() + (frame as f32 + 0.5) / frames as f32 * rect.width();
+
+        if low <= high {
+            let span = (high - low).max(f32::EPSILON);
+            let plot = rect.shrink2(egui::vec2(0.0, 6.0));
+            let y = |value: f32| plot.bottom() - (value - low) / span * plot.height();
+            for (i, series) in values.iter().enumerate() {
+                let stroke = egui::Stroke::new(1.5, SERIES_COLORS[i % SERIES_COLORS.len()]);
+                // frames that are not comparable break the line
+                let mut line = vec![];
+                for (frame, value) in series.iter().enumerate() {
+                    match value {
+                        Some(value) => line.push(egui::pos2(x(frame), y(*value))),
+                        None => {
+                            painter.add(egui::Shape::line(std::mem::take(&mut line), stroke));
+                        }
+                    }
+                }
+                painter.add(egui::Shape::line(line, stroke));
+            }
+            let font = egui::FontId::monospace(10.0);
+            let color = egui::Color32::LIGHT_GRAY;
+            let label = |value: f32| format!("{value:.4}");
+            painter.text(
+                rect.right_top(),
+                egui::Align2::RIGHT_TOP,
+                label(high),
+                font.clone(),
+                color,
+            );
+            painter.text(
+                rect.right_bottom(),
+                egui::Align2::RIGHT_BOTTOM,
+                label(low),
+                font,
+                color,
+            );
+        }
+
+        self.paint_legend(&painter, rect, metrics);
+
+        let cursor = x(self.frame);
+        painter.vline(
+            cursor,
+            rect.y_range(),
+            egui::Stroke::new(1.0, egui::Color32::WHITE),
+        );
+
+        if let Some(pointer) = response.interact_pointer_pos() {
+            let frame = ((pointer.x - rect.left()) / rect.width() * frames as f32).floor();
+            self.playing = false;
+            self.frame = (frame.max(0.0) as usize).min(count - 1);
+        }
+        if let Some(pointer) = response.hover_pos() {
+            let frame = (((pointer.x - rect.left()) / rect.width() * frames as f32).floor()
+                as usize)
+                .min(frames.saturating_sub(1));
+            response.on_hover_ui_at_pointer(|ui| {
+                ui.label(format!("Frame {}", frame + 1));
+                for ((id, _), series) in metrics.series.iter().zip(&values) {
+                    let value = series.get(frame).copied().flatten();
+                    ui.label(match value {
+                        Some(value) => format!("{id}: {value:.4}"),
+                        None => format!("{id}: –"),
+                    });
+                }
+            });
+        }
+    }
+
+    /// Name of each plotted sequence in its color, in the top left corner
+    fn paint_legend(&self, painter: &egui::Painter, rect: egui::Rect, metrics: &FrameMetrics) {
+        let mut pos = rect.left_top() + egui::vec2(4.0, 2.0);
+        for (i, (id, _)) in metrics.series.iter().enumerate() {
+            let text = format!("{id} {} vs {}", self.metric.label(), metrics.reference);
+            let galley = painter.layout_no_wrap(
+                text,
+                egui::FontId::proportional(11.0),
+                SERIES_COLORS[i % SERIES_COLORS.len()],
+            );
+            let height = galley.size().y;
+            painter.galley(pos, galley, egui::Color32::WHITE);
+            pos.y += height;
+        }
+    }
+}
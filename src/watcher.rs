@@ -0,0 +1,186 @@
+use crate::registry::AssetRegistry;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Modification time and size, a change in either means the file was rewritten
+type FileStamp = (Option<SystemTime>, u64);
+
+fn stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok(), metadata.len()))
+}
+
+/// Files found by a poll, reported once they stopped changing for one interval
+#[derive(Default)]
+pub struct Changes {
+    /// Loaded files that were rewritten
+    pub modified: Vec<PathBuf>,
+    /// New files in the watched folders
+    pub created: Vec<PathBuf>,
+}
+
+/// Polls the files of loaded assets and watched folders for changes
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Watcher {
+    pub enabled: bool,
+    /// Seconds between polls
+    pub interval: f32,
+    /// Folders in which new files are loaded automatically
+    pub folders: Vec<PathBuf>,
+    #[serde(skip)]
+    folder_input: String,
+    #[serde(skip)]
+    last_poll: f64,
+    /// Stamps of the files as they were last loaded
+    #[serde(skip)]
+    stamps: HashMap<PathBuf, FileStamp>,
+    /// Stamps seen by the previous poll that differ from the loaded ones
+    #[serde(skip)]
+    pending: HashMap<PathBuf, FileStamp>,
+    /// Files of the watched folders that are not new
+    #[serde(skip)]
+    known_files: HashSet<PathBuf>,
+    #[serde(skip)]
+    scanned_folders: HashSet<PathBuf>,
+}
+
+impl Default for Watcher {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: 0.5,
+            folders: vec![],
+            folder_input: String::new(),
+            last_poll: 0.0,
+            stamps: HashMap::new(),
+            pending: HashMap::new(),
+            known_files: HashSet::new(),
+            scanned_folders: HashSet::new(),
+        }
+    }
+}
+
+impl Watcher {
+    /// Check `paths`, the files of the loaded assets, and the watched folders once the interval passed
+    pub fn poll<'a>(
+        &mut self,
+        ctx: &egui::Context,
+        registry: &AssetRegistry,
+        paths: impl Iterator<Item = &'a Path>,
+    ) -> Changes {
+        let mut changes = Changes::default();
+        if !self.enabled {
+            return changes;
+        }
+
+        let interval = f64::from(self.interval.max(0.1));
+        ctx.request_repaint_after(Duration::from_secs_f64(interval));
+        let time = ctx.input(|i| i.time);
+        if time - self.last_poll < interval {
+            return changes;
+        }
+        self.last_poll = time;
+
+        let loaded: Vec<&Path> = paths.collect();
+        for &path in &loaded {
+            let Some(current) = stamp(path) else {
+                continue;
+            };
+            match self.stamps.get(path) {
+                // first time this file is seen, it was just loaded
+                None => {
+                    self.stamps.insert(path.to_path_buf(), current);
+                }
+                Some(loaded_stamp) if *loaded_stamp == current => {
+                    self.pending.remove(path);
+                }
+                Some(_) => {
+                    if self.is_stable(path, current) {
+                        self.stamps.insert(path.to_path_buf(), current);
+                        changes.modified.push(path.to_path_buf());
+                    }
+                }
+            }
+        }
+
+        for folder in self.folders.clone() {
+            let Ok(entries) = std::fs::read_dir(&folder) else {
+                continue;
+            };
+            let mut files: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && registry.supports_extension(path))
+                .collect();
+            files.sort();
+
+            // files that were there when the folder was added are not new
+            if self.scanned_folders.insert(folder) {
+                self.known_files.extend(files);
+                continue;
+            }
+
+            for path in files {
+                if self.known_files.contains(&path) || loaded.contains(&path.as_path()) {
+                    continue;
+                }
+                let Some(current) = stamp(&path) else {
+                    continue;
+                };
+                if self.is_stable(&path, current) {
+                    self.known_files.insert(path.clone());
+                    changes.created.push(path);
+                }
+            }
+        }
+
+        changes
+    }
+
+    /// Whether a changed file has the same stamp as in the previous poll, so it is done being written
+    fn is_stable(&mut self, path: &Path, current: FileStamp) -> bool {
+        if self.pending.get(path) == Some(&current) {
+            self.pending.remove(path);
+            true
+        } else {
+            self.pending.insert(path.to_path_buf(), current);
+            false
+        }
+    }
+
+    pub fn show_settings(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Reload assets when their files change");
+        ui.add(
+            egui::Slider::new(&mut self.interval, 0.1..=5.0)
+                .text("Check every")
+                .suffix(" s"),
+        );
+
+        ui.separator();
+        ui.label("Load new files from folders:");
+        let mut removed = None;
+        for (i, folder) in self.folders.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.small_button("🗑").clicked() {
+                    removed = Some(i);
+                }
+                ui.label(folder.display().to_string());
+            });
+        }
+        if let Some(i) = removed {
+            let folder = self.folders.remove(i);
+            self.scanned_folders.remove(&folder);
+        }
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.folder_input);
+            let folder = PathBuf::from(self.folder_input.trim());
+            let can_add = folder.is_dir() && !self.folders.contains(&folder);
+            if ui.add_enabled(can_add, egui::Button::new("Add")).clicked() {
+                self.folders.push(folder);
+                self.folder_input.clear();
+            }
+        });
+    }
+}
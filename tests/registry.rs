@@ -0,0 +1,32 @@
+use tex_comp::{AssetLoader, AssetRegistry, Decoder, Pixels};
+
+/// An in-house format: `TINY`, the width and height, then one gray byte per pixel
+fn decode_tiny(bytes: &[u8]) -> anyhow::Result<Pixels> {
+    let [b'T', b'I', b'N', b'Y', width, height, gray @ ..] = bytes else {
+        anyhow::bail!("Not a TINY file");
+    };
+    let buffer = image::GrayImage::from_raw(u32::from(*width), u32::from(*height), gray.to_vec())
+        .ok_or_else(|| anyhow::anyhow!("Truncated TINY file"))?;
+    Ok(image::DynamicImage::ImageLuma8(buffer).into())
+}
+
+#[test]
+fn formats_can_be_registered_from_outside_the_crate() {
+    let mut registry = AssetRegistry::default();
+    registry.register(AssetLoader {
+        name: "TINY",
+        extensions: &["tiny"],
+        mime_types: &[],
+        magic: &[b"TINY"],
+        decoder: Decoder::Image(decode_tiny),
+    });
+
+    let bytes = [b"TINY".as_slice(), &[2, 1, 0, 255]].concat();
+    let loader = registry.find("scan.bin", "", &bytes);
+    let Some(Decoder::Image(decode)) = loader.map(|loader| loader.decoder) else {
+        panic!("the registered format was not found");
+    };
+    let pixels = decode(&bytes).expect("the file decodes");
+    assert_eq!(pixels.size(), [2, 1]);
+    assert_eq!(pixels.get(1, 0), [1.0; 4]);
+}